@@ -0,0 +1,412 @@
+use alloc::string::ToString;
+use alloc::vec;
+use alloc::vec::Vec;
+use core::cmp::Ordering;
+use core::fmt;
+
+use crate::builtins::{self, Operator};
+use crate::error::CalcError;
+use crate::eval::{ExactValue, evaluate_exact};
+use crate::integer;
+use crate::parser::Expression;
+
+// Limbs are base 10^9, so printing in decimal needs no conversion.
+const BASE: u64 = 1_000_000_000;
+
+// Results are capped at about 90,000 digits; anything larger fails with
+// `CalcError::IntegerOverflow` instead of running for minutes.
+const MAX_LIMBS: usize = 10_000;
+
+// Sign and magnitude, with the magnitude's limbs least significant first and
+// no leading zero limbs. Zero has no limbs and is never negative.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub(crate) struct BigInt {
+    negative: bool,
+    limbs: Vec<u32>,
+}
+
+impl BigInt {
+    fn from_i64(n: i64) -> Self {
+        let mut magnitude = n.unsigned_abs();
+        let mut limbs = Vec::new();
+        while magnitude > 0 {
+            limbs.push((magnitude % BASE) as u32);
+            magnitude /= BASE;
+        }
+        BigInt {
+            negative: n < 0,
+            limbs,
+        }
+    }
+
+    fn new(negative: bool, mut limbs: Vec<u32>) -> Result<Self, CalcError> {
+        trim(&mut limbs);
+        if limbs.len() > MAX_LIMBS {
+            return Err(CalcError::IntegerOverflow);
+        }
+        Ok(BigInt {
+            negative: negative && !limbs.is_empty(),
+            limbs,
+        })
+    }
+
+    fn is_zero(&self) -> bool {
+        self.limbs.is_empty()
+    }
+
+    fn is_odd(&self) -> bool {
+        // The base is even, so the lowest limb decides.
+        self.limbs.first().is_some_and(|limb| limb % 2 == 1)
+    }
+
+    fn to_u64(&self) -> Option<u64> {
+        if self.negative {
+            return None;
+        }
+        self.limbs.iter().rev().try_fold(0u64, |acc, &limb| {
+            acc.checked_mul(BASE)?.checked_add(u64::from(limb))
+        })
+    }
+
+    fn to_f64(&self) -> f64 {
+        let magnitude = self
+            .limbs
+            .iter()
+            .rev()
+            .fold(0.0, |acc, &limb| acc * BASE as f64 + f64::from(limb));
+        if self.negative { -magnitude } else { magnitude }
+    }
+
+    fn neg(&self) -> BigInt {
+        BigInt {
+            negative: !self.negative && !self.is_zero(),
+            limbs: self.limbs.clone(),
+        }
+    }
+
+    fn abs(&self) -> BigInt {
+        BigInt {
+            negative: false,
+            limbs: self.limbs.clone(),
+        }
+    }
+
+    fn add(&self, rhs: &BigInt) -> Result<BigInt, CalcError> {
+        if self.negative == rhs.negative {
+            return BigInt::new(self.negative, add_magnitudes(&self.limbs, &rhs.limbs));
+        }
+        match compare_magnitudes(&self.limbs, &rhs.limbs) {
+            Ordering::Less => BigInt::new(rhs.negative, sub_magnitudes(&rhs.limbs, &self.limbs)),
+            _ => BigInt::new(self.negative, sub_magnitudes(&self.limbs, &rhs.limbs)),
+        }
+    }
+
+    fn sub(&self, rhs: &BigInt) -> Result<BigInt, CalcError> {
+        self.add(&rhs.neg())
+    }
+
+    fn mul(&self, rhs: &BigInt) -> Result<BigInt, CalcError> {
+        // The product has at least `a + b - 1` limbs, so an oversized one is
+        // rejected before spending time on the multiplication.
+        if self.limbs.len() + rhs.limbs.len() > MAX_LIMBS + 1 {
+            return Err(CalcError::IntegerOverflow);
+        }
+        BigInt::new(
+            self.negative != rhs.negative,
+            mul_magnitudes(&self.limbs, &rhs.limbs),
+        )
+    }
+
+    // Truncated quotient and remainder; the remainder takes the dividend's
+    // sign, like `eval_i64`.
+    fn div_rem(&self, rhs: &BigInt) -> Result<(BigInt, BigInt), CalcError> {
+        if rhs.is_zero() {
+            return Err(CalcError::DivideByZero);
+        }
+        let (quotient, remainder) = div_rem_magnitudes(&self.limbs, &rhs.limbs);
+        Ok((
+            BigInt::new(self.negative != rhs.negative, quotient)?,
+            BigInt::new(self.negative, remainder)?,
+        ))
+    }
+
+    fn exact_div(&self, rhs: &BigInt) -> Result<BigInt, CalcError> {
+        let (quotient, remainder) = self.div_rem(rhs)?;
+        if !remainder.is_zero() {
+            return Err(CalcError::NonInteger);
+        }
+        Ok(quotient)
+    }
+
+    fn pow(&self, exp: &BigInt) -> Result<BigInt, CalcError> {
+        let one = BigInt::from_i64(1);
+        if self.is_zero() || self.limbs == [1] {
+            if self.is_zero() && exp.negative {
+                return Err(CalcError::DivideByZero);
+            }
+            let result = if exp.is_zero() || (self.negative && !exp.is_odd()) {
+                one
+            } else {
+                self.clone()
+            };
+            return Ok(result);
+        }
+        // Only ±1 have integer reciprocals.
+        if exp.negative {
+            return Err(CalcError::NonInteger);
+        }
+        let mut k = exp.to_u64().ok_or(CalcError::IntegerOverflow)?;
+        let (mut result, mut square) = (one, self.clone());
+        while k > 0 {
+            if k & 1 == 1 {
+                result = result.mul(&square)?;
+            }
+            k >>= 1;
+            if k > 0 {
+                square = square.mul(&square)?;
+            }
+        }
+        Ok(result)
+    }
+
+    fn factorial(&self) -> Result<BigInt, CalcError> {
+        if self.negative {
+            return Err(CalcError::DomainError {
+                name: "!".to_string(),
+                arg: self.to_f64(),
+            });
+        }
+        // Anything past `u32::MAX!` is far beyond the digit cap anyway.
+        let n = self
+            .to_u64()
+            .and_then(|n| u32::try_from(n).ok())
+            .ok_or(CalcError::IntegerOverflow)?;
+        let mut limbs = vec![1];
+        for k in 2..=n {
+            limbs = mul_small(&limbs, k);
+            if limbs.len() > MAX_LIMBS {
+                return Err(CalcError::IntegerOverflow);
+            }
+        }
+        BigInt::new(false, limbs)
+    }
+}
+
+impl Ord for BigInt {
+    fn cmp(&self, other: &Self) -> Ordering {
+        match (self.negative, other.negative) {
+            (false, true) => Ordering::Greater,
+            (true, false) => Ordering::Less,
+            (false, false) => compare_magnitudes(&self.limbs, &other.limbs),
+            (true, true) => compare_magnitudes(&other.limbs, &self.limbs),
+        }
+    }
+}
+
+impl PartialOrd for BigInt {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl fmt::Display for BigInt {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let Some((top, rest)) = self.limbs.split_last() else {
+            return write!(f, "0");
+        };
+        if self.negative {
+            write!(f, "-")?;
+        }
+        write!(f, "{top}")?;
+        for limb in rest.iter().rev() {
+            write!(f, "{limb:09}")?;
+        }
+        Ok(())
+    }
+}
+
+fn trim(limbs: &mut Vec<u32>) {
+    while limbs.last() == Some(&0) {
+        limbs.pop();
+    }
+}
+
+// Both magnitudes must be trimmed, so a longer one is always larger.
+fn compare_magnitudes(a: &[u32], b: &[u32]) -> Ordering {
+    a.len()
+        .cmp(&b.len())
+        .then_with(|| a.iter().rev().cmp(b.iter().rev()))
+}
+
+fn add_magnitudes(a: &[u32], b: &[u32]) -> Vec<u32> {
+    let mut sum = Vec::with_capacity(a.len().max(b.len()) + 1);
+    let mut carry = 0;
+    for i in 0..a.len().max(b.len()) {
+        let total = carry
+            + u64::from(a.get(i).copied().unwrap_or(0))
+            + u64::from(b.get(i).copied().unwrap_or(0));
+        sum.push((total % BASE) as u32);
+        carry = total / BASE;
+    }
+    if carry > 0 {
+        sum.push(carry as u32);
+    }
+    sum
+}
+
+// `a - b`, where `a >= b`.
+fn sub_magnitudes(a: &[u32], b: &[u32]) -> Vec<u32> {
+    let mut diff = Vec::with_capacity(a.len());
+    let mut borrow = 0;
+    for (i, &limb) in a.iter().enumerate() {
+        let mut d = i64::from(limb) - borrow - i64::from(b.get(i).copied().unwrap_or(0));
+        borrow = i64::from(d < 0);
+        if d < 0 {
+            d += BASE as i64;
+        }
+        diff.push(d as u32);
+    }
+    trim(&mut diff);
+    diff
+}
+
+// Schoolbook multiplication. Each partial sum stays below `BASE^2 + 2*BASE`,
+// which fits in `u64`.
+fn mul_magnitudes(a: &[u32], b: &[u32]) -> Vec<u32> {
+    let mut product = vec![0u64; a.len() + b.len()];
+    for (i, &x) in a.iter().enumerate() {
+        let mut carry = 0;
+        for (j, &y) in b.iter().enumerate() {
+            let total = product[i + j] + u64::from(x) * u64::from(y) + carry;
+            product[i + j] = total % BASE;
+            carry = total / BASE;
+        }
+        product[i + b.len()] = carry;
+    }
+    product.into_iter().map(|limb| limb as u32).collect()
+}
+
+fn mul_small(a: &[u32], m: u32) -> Vec<u32> {
+    let mut product = Vec::with_capacity(a.len() + 2);
+    let mut carry = 0;
+    for &limb in a {
+        let total = u64::from(limb) * u64::from(m) + carry;
+        product.push((total % BASE) as u32);
+        carry = total / BASE;
+    }
+    while carry > 0 {
+        product.push((carry % BASE) as u32);
+        carry /= BASE;
+    }
+    trim(&mut product);
+    product
+}
+
+// Long division one limb at a time, finding each quotient limb by binary
+// search. Slower than Knuth's algorithm D but much simpler, and fast enough
+// at the sizes `MAX_LIMBS` allows.
+fn div_rem_magnitudes(a: &[u32], b: &[u32]) -> (Vec<u32>, Vec<u32>) {
+    let mut quotient = vec![0; a.len()];
+    let mut remainder = Vec::new();
+    for i in (0..a.len()).rev() {
+        remainder.insert(0, a[i]);
+        trim(&mut remainder);
+        let (mut lo, mut hi) = (0, (BASE - 1) as u32);
+        while lo < hi {
+            let mid = lo + (hi - lo).div_ceil(2);
+            if compare_magnitudes(&mul_small(b, mid), &remainder) == Ordering::Greater {
+                hi = mid - 1;
+            } else {
+                lo = mid;
+            }
+        }
+        if lo > 0 {
+            remainder = sub_magnitudes(&remainder, &mul_small(b, lo));
+        }
+        quotient[i] = lo;
+    }
+    (quotient, remainder)
+}
+
+// Exact evaluation over arbitrary-precision integers. Like `eval_i64`, every
+// intermediate result must be an integer, but nothing overflows short of the
+// `MAX_LIMBS` cap. Literals still arrive as `f64`, so each must fit in `i64`.
+pub(crate) fn evaluate_bigint(expr: &Expression) -> Result<BigInt, CalcError> {
+    evaluate_exact(expr)
+}
+
+impl ExactValue for BigInt {
+    fn number(n: f64) -> Result<Self, CalcError> {
+        integer::to_i64(n).map(BigInt::from_i64)
+    }
+
+    fn constant(_name: &str, value: f64) -> Result<Self, CalcError> {
+        integer::to_i64(value).map(BigInt::from_i64)
+    }
+
+    fn unary(op: Operator, value: Self) -> Result<Self, CalcError> {
+        match op {
+            "+" => Ok(value),
+            "-" => Ok(value.neg()),
+            "%" => value.exact_div(&BigInt::from_i64(100)),
+            "!" => value.factorial(),
+            _ => Err(CalcError::UnknownFunction(op.to_string())),
+        }
+    }
+
+    fn infix(op: Operator, a: Self, b: Self) -> Result<Self, CalcError> {
+        infix(op, &a, &b)
+    }
+
+    fn call(name: &str, args: &[Self]) -> Result<Self, CalcError> {
+        call(name, args)
+    }
+}
+
+fn infix(op: Operator, a: &BigInt, b: &BigInt) -> Result<BigInt, CalcError> {
+    let truth = |holds: bool| Ok(BigInt::from_i64(i64::from(holds)));
+    match op {
+        "+" => a.add(b),
+        "-" => a.sub(b),
+        "*" => a.mul(b),
+        "/" => a.exact_div(b),
+        "//" => {
+            // Round toward negative infinity, matching the float operator.
+            let (quotient, remainder) = a.div_rem(b)?;
+            if !remainder.is_zero() && a.negative != b.negative {
+                quotient.sub(&BigInt::from_i64(1))
+            } else {
+                Ok(quotient)
+            }
+        }
+        "%" => Ok(a.div_rem(b)?.1),
+        "^" => a.pow(b),
+        "<" => truth(a < b),
+        ">" => truth(a > b),
+        "<=" => truth(a <= b),
+        ">=" => truth(a >= b),
+        "==" => truth(a == b),
+        "!=" => truth(a != b),
+        _ => Err(CalcError::UnknownFunction(op.to_string())),
+    }
+}
+
+fn call(name: &str, args: &[BigInt]) -> Result<BigInt, CalcError> {
+    let name = builtins::normalize_name(name);
+    let (min, max) = match name.as_str() {
+        "abs" | "sign" => (1, Some(1)),
+        "min" | "max" => (1, None),
+        _ => return Err(CalcError::UnknownFunction(name)),
+    };
+    builtins::check_arity(&name, min, max, args.len())?;
+    match name.as_str() {
+        "abs" => Ok(args[0].abs()),
+        "sign" => Ok(BigInt::from_i64(match args[0].cmp(&BigInt::default()) {
+            Ordering::Less => -1,
+            Ordering::Equal => 0,
+            Ordering::Greater => 1,
+        })),
+        "min" => Ok(args.iter().min().cloned().unwrap_or_default()),
+        _ => Ok(args.iter().max().cloned().unwrap_or_default()),
+    }
+}
@@ -1,27 +1,48 @@
+use alloc::format;
+use alloc::string::{String, ToString};
+use alloc::vec::Vec;
+
 use crate::error::CalcError;
+use crate::integer;
+#[cfg(not(feature = "std"))]
+use crate::math::FloatExt;
 
-pub(crate) type Operator = char;
+pub(crate) type Operator = &'static str;
 
 struct BuiltinConst {
     name: &'static str, // stored lowercase
     value: f64,
 }
 
-struct BuiltinFunc {
-    name: &'static str, // stored lowercase
-    min_arity: usize,
-    max_arity: Option<usize>,
-    eval: fn(&[f64]) -> Result<f64, CalcError>,
+pub(crate) type FuncFn = fn(&[f64]) -> Result<f64, CalcError>;
+
+pub(crate) struct BuiltinFunc {
+    pub(crate) name: &'static str, // stored lowercase
+    pub(crate) min_arity: usize,
+    pub(crate) max_arity: Option<usize>,
+    pub(crate) eval: FuncFn,
 }
 
 const CONSTANTS: &[BuiltinConst] = &[
     BuiltinConst {
         name: "pi",
-        value: std::f64::consts::PI,
+        value: core::f64::consts::PI,
     },
     BuiltinConst {
         name: "e",
-        value: std::f64::consts::E,
+        value: core::f64::consts::E,
+    },
+    BuiltinConst {
+        name: "tau",
+        value: core::f64::consts::TAU,
+    },
+    BuiltinConst {
+        name: "inf",
+        value: f64::INFINITY,
+    },
+    BuiltinConst {
+        name: "nan",
+        value: f64::NAN,
     },
 ];
 
@@ -29,7 +50,29 @@ fn sqrt_impl(args: &[f64]) -> Result<f64, CalcError> {
     Ok(args[0].sqrt())
 }
 
+// Real cube root, so `cbrt(-8)` is -2 where `(-8)^(1/3)` is NaN.
+fn cbrt_impl(args: &[f64]) -> Result<f64, CalcError> {
+    Ok(args[0].cbrt())
+}
+
+// `min` and `max` propagate NaN, so a bad value cannot silently drop out of
+// the result: `max(nan, 1)` is NaN. `fmin` and `fmax` skip NaN arguments like
+// C's, and are NaN only when every argument is.
 fn min_impl(args: &[f64]) -> Result<f64, CalcError> {
+    if args.iter().any(|value| value.is_nan()) {
+        return Ok(f64::NAN);
+    }
+    fmin_impl(args)
+}
+
+fn max_impl(args: &[f64]) -> Result<f64, CalcError> {
+    if args.iter().any(|value| value.is_nan()) {
+        return Ok(f64::NAN);
+    }
+    fmax_impl(args)
+}
+
+fn fmin_impl(args: &[f64]) -> Result<f64, CalcError> {
     let mut best = args[0];
     for &value in &args[1..] {
         best = best.min(value);
@@ -37,7 +80,7 @@ fn min_impl(args: &[f64]) -> Result<f64, CalcError> {
     Ok(best)
 }
 
-fn max_impl(args: &[f64]) -> Result<f64, CalcError> {
+fn fmax_impl(args: &[f64]) -> Result<f64, CalcError> {
     let mut best = args[0];
     for &value in &args[1..] {
         best = best.max(value);
@@ -45,6 +88,356 @@ fn max_impl(args: &[f64]) -> Result<f64, CalcError> {
     Ok(best)
 }
 
+fn sin_impl(args: &[f64]) -> Result<f64, CalcError> {
+    Ok(args[0].sin())
+}
+
+fn cos_impl(args: &[f64]) -> Result<f64, CalcError> {
+    Ok(args[0].cos())
+}
+
+fn tan_impl(args: &[f64]) -> Result<f64, CalcError> {
+    Ok(args[0].tan())
+}
+
+// asin/acos are only defined on [-1, 1]; report a domain error instead of returning NaN.
+fn asin_impl(args: &[f64]) -> Result<f64, CalcError> {
+    let x = args[0];
+    if !(-1.0..=1.0).contains(&x) {
+        return Err(CalcError::DomainError {
+            name: "asin".to_string(),
+            arg: x,
+        });
+    }
+    Ok(x.asin())
+}
+
+fn acos_impl(args: &[f64]) -> Result<f64, CalcError> {
+    let x = args[0];
+    if !(-1.0..=1.0).contains(&x) {
+        return Err(CalcError::DomainError {
+            name: "acos".to_string(),
+            arg: x,
+        });
+    }
+    Ok(x.acos())
+}
+
+fn atan_impl(args: &[f64]) -> Result<f64, CalcError> {
+    Ok(args[0].atan())
+}
+
+// atan2(y, x)
+fn atan2_impl(args: &[f64]) -> Result<f64, CalcError> {
+    Ok(args[0].atan2(args[1]))
+}
+
+// Logarithms reject non-positive input instead of returning NaN / -inf.
+fn positive_arg(name: &str, x: f64) -> Result<f64, CalcError> {
+    if x > 0.0 {
+        Ok(x)
+    } else {
+        Err(CalcError::DomainError {
+            name: name.to_string(),
+            arg: x,
+        })
+    }
+}
+
+fn ln_impl(args: &[f64]) -> Result<f64, CalcError> {
+    Ok(positive_arg("ln", args[0])?.ln())
+}
+
+fn log10_impl(args: &[f64]) -> Result<f64, CalcError> {
+    Ok(positive_arg("log10", args[0])?.log10())
+}
+
+fn log2_impl(args: &[f64]) -> Result<f64, CalcError> {
+    Ok(positive_arg("log2", args[0])?.log2())
+}
+
+// log(x) is base 10; log(base, x) uses the given base.
+fn log_impl(args: &[f64]) -> Result<f64, CalcError> {
+    if let [x] = args {
+        return Ok(positive_arg("log", *x)?.log10());
+    }
+    let base = positive_arg("log", args[0])?;
+    if base == 1.0 {
+        return Err(CalcError::DomainError {
+            name: "log".to_string(),
+            arg: base,
+        });
+    }
+    Ok(positive_arg("log", args[1])?.log(base))
+}
+
+fn floor_impl(args: &[f64]) -> Result<f64, CalcError> {
+    Ok(args[0].floor())
+}
+
+fn ceil_impl(args: &[f64]) -> Result<f64, CalcError> {
+    Ok(args[0].ceil())
+}
+
+// Rounds half away from zero: round(2.5) == 3, round(-2.5) == -3. With
+// `digits`, the same at that many decimal places.
+fn round_impl(args: &[f64]) -> Result<f64, CalcError> {
+    let x = args[0];
+    let Some(&digits) = args.get(1) else {
+        return Ok(x.round());
+    };
+    if digits < 0.0 || digits.fract() != 0.0 {
+        return Err(CalcError::DomainError {
+            name: "round".to_string(),
+            arg: digits,
+        });
+    }
+    Ok(round_decimal(x, digits))
+}
+
+// Rounds the shortest decimal form of `x` rather than `x * 10^digits`: the
+// double nearest 2.675 is slightly below it, so scaling would give 2.67 where
+// the written number says 2.68.
+fn round_decimal(x: f64, digits: f64) -> f64 {
+    if !x.is_finite() {
+        return x;
+    }
+    let scientific = format!("{:e}", x.abs());
+    let (mantissa, exponent) = scientific.split_once('e').unwrap_or((&scientific, "0"));
+    let exponent: i64 = exponent.parse().unwrap_or(0);
+    let significant = mantissa.replace('.', "");
+    // Significant digits left of the cut; beyond what `x` has, nothing changes.
+    let keep = exponent as f64 + 1.0 + digits;
+    if keep >= significant.len() as f64 {
+        return x;
+    }
+    if keep < 0.0 {
+        return 0.0 * x.signum();
+    }
+    let keep = keep as usize;
+    let mut kept: u64 = significant[..keep].parse().unwrap_or(0);
+    if significant.as_bytes()[keep] >= b'5' {
+        kept += 1;
+    }
+    let rounded: f64 = format!("{kept}e{}", exponent + 1 - keep as i64)
+        .parse()
+        .unwrap_or(x.abs());
+    rounded.copysign(x)
+}
+
+fn trunc_impl(args: &[f64]) -> Result<f64, CalcError> {
+    Ok(args[0].trunc())
+}
+
+fn abs_impl(args: &[f64]) -> Result<f64, CalcError> {
+    Ok(args[0].abs())
+}
+
+// Unlike f64::signum, zero (of either sign) maps to 0.
+fn sign_impl(args: &[f64]) -> Result<f64, CalcError> {
+    let x = args[0];
+    if x == 0.0 { Ok(0.0) } else { Ok(x.signum()) }
+}
+
+fn exp_impl(args: &[f64]) -> Result<f64, CalcError> {
+    Ok(args[0].exp())
+}
+
+fn sum_impl(args: &[f64]) -> Result<f64, CalcError> {
+    Ok(args.iter().sum())
+}
+
+fn product_impl(args: &[f64]) -> Result<f64, CalcError> {
+    Ok(args.iter().product())
+}
+
+fn mean_impl(args: &[f64]) -> Result<f64, CalcError> {
+    Ok(args.iter().sum::<f64>() / (args.len() as f64))
+}
+
+// Even counts average the two middle values. Like `min` and `max`, a NaN
+// argument makes the result NaN rather than being sorted out of the middle.
+fn median_impl(args: &[f64]) -> Result<f64, CalcError> {
+    if args.iter().any(|value| value.is_nan()) {
+        return Ok(f64::NAN);
+    }
+    let mut sorted = args.to_vec();
+    sorted.sort_by(f64::total_cmp);
+    let mid = sorted.len() / 2;
+    if sorted.len().is_multiple_of(2) {
+        Ok((sorted[mid - 1] + sorted[mid]) / 2.0)
+    } else {
+        Ok(sorted[mid])
+    }
+}
+
+// Above 2^53 consecutive integers are no longer distinct `f64`s, so `n - 1`
+// could equal `n`.
+const MAX_COMBINATORIC_ARG: f64 = 9_007_199_254_740_992.0;
+
+// n and k must be non-negative integers with k <= n <= 2^53.
+fn combinatoric_args(name: &str, args: &[f64]) -> Result<(f64, f64), CalcError> {
+    let (n, k) = (args[0], args[1]);
+    for value in [n, k] {
+        if value < 0.0 || value.fract() != 0.0 || value > MAX_COMBINATORIC_ARG {
+            return Err(CalcError::DomainError {
+                name: name.to_string(),
+                arg: value,
+            });
+        }
+    }
+    if k > n {
+        return Err(CalcError::DomainError {
+            name: name.to_string(),
+            arg: k,
+        });
+    }
+    Ok((n, k))
+}
+
+// Multiplies and divides step by step instead of computing full factorials,
+// so intermediate values stay close to the result. Both loops count in
+// integers and stop once the product overflows, so a huge `k` costs at most a
+// few thousand steps.
+fn ncr_impl(args: &[f64]) -> Result<f64, CalcError> {
+    let (n, k) = combinatoric_args("ncr", args)?;
+    let k = k.min(n - k);
+    let mut result = 1.0;
+    for i in 1..=k as u64 {
+        result = result * (n - k + i as f64) / i as f64;
+        if result.is_infinite() {
+            break;
+        }
+    }
+    Ok(result.round())
+}
+
+fn npr_impl(args: &[f64]) -> Result<f64, CalcError> {
+    let (n, k) = combinatoric_args("npr", args)?;
+    let mut result = 1.0;
+    for i in 0..k as u64 {
+        result *= n - i as f64;
+        if result.is_infinite() {
+            break;
+        }
+    }
+    Ok(result)
+}
+
+fn clamp_impl(args: &[f64]) -> Result<f64, CalcError> {
+    let (x, lo, hi) = (args[0], args[1], args[2]);
+    // f64::clamp panics on these, so report them as a domain error instead.
+    if lo.is_nan() || hi.is_nan() || lo > hi {
+        return Err(CalcError::DomainError {
+            name: "clamp".to_string(),
+            arg: lo,
+        });
+    }
+    Ok(x.clamp(lo, hi))
+}
+
+fn clamp01_impl(args: &[f64]) -> Result<f64, CalcError> {
+    Ok(args[0].clamp(0.0, 1.0))
+}
+
+// `(new - old) / old * 100`: `percent_change(100, 150)` is 50.
+fn percent_change_impl(args: &[f64]) -> Result<f64, CalcError> {
+    let (old, new) = (args[0], args[1]);
+    if old == 0.0 {
+        return Err(CalcError::DivideByZero);
+    }
+    Ok((new - old) / old * 100.0)
+}
+
+// `part / whole * 100`: `percent_of(25, 200)` is 12.5.
+fn percent_of_impl(args: &[f64]) -> Result<f64, CalcError> {
+    let (part, whole) = (args[0], args[1]);
+    if whole == 0.0 {
+        return Err(CalcError::DivideByZero);
+    }
+    Ok(part / whole * 100.0)
+}
+
+// `t` outside `[0, 1]` extrapolates along the same line.
+fn lerp_impl(args: &[f64]) -> Result<f64, CalcError> {
+    let (a, b, t) = (args[0], args[1], args[2]);
+    Ok(a + (b - a) * t)
+}
+
+fn hypot_impl(args: &[f64]) -> Result<f64, CalcError> {
+    Ok(args[0].hypot(args[1]))
+}
+
+fn pow_fn_impl(args: &[f64]) -> Result<f64, CalcError> {
+    Ok(args[0].powf(args[1]))
+}
+
+// root(n, x): odd integer roots of negatives are real (root(3, -8) == -2);
+// any other root of a negative number is a domain error rather than NaN.
+fn root_impl(args: &[f64]) -> Result<f64, CalcError> {
+    let (n, x) = (args[0], args[1]);
+    if n == 0.0 {
+        return Err(CalcError::DomainError {
+            name: "root".to_string(),
+            arg: n,
+        });
+    }
+    if x < 0.0 {
+        if n.fract() != 0.0 || n % 2.0 == 0.0 {
+            return Err(CalcError::DomainError {
+                name: "root".to_string(),
+                arg: x,
+            });
+        }
+        return Ok(-(-x).powf(1.0 / n));
+    }
+    Ok(x.powf(1.0 / n))
+}
+
+// Explicit unit conversions, unaffected by `AngleMode`.
+fn deg2rad_impl(args: &[f64]) -> Result<f64, CalcError> {
+    Ok(args[0].to_radians())
+}
+
+fn rad2deg_impl(args: &[f64]) -> Result<f64, CalcError> {
+    Ok(args[0].to_degrees())
+}
+
+// Wraps an angle into `[0, 2π)`, e.g. after summing several rotations. This is
+// `rem_euclid(TAU)`, with the one-ulp case where a tiny negative remainder
+// rounds up to exactly `TAU` folded back to 0.
+fn normalize_angle_impl(args: &[f64]) -> Result<f64, CalcError> {
+    let tau = core::f64::consts::TAU;
+    let wrapped = args[0] % tau;
+    let wrapped = if wrapped < 0.0 {
+        wrapped + tau
+    } else {
+        wrapped
+    };
+    Ok(if wrapped == tau { 0.0 } else { wrapped })
+}
+
+// Degrees-minutes-seconds to decimal degrees: `dms(30, 15, 0)` is 30.25. The
+// sign of the degrees applies to the whole angle, so `dms(-30, 15, 0)` is
+// -30.25; minutes and seconds must be in `[0, 60)`.
+fn dms_impl(args: &[f64]) -> Result<f64, CalcError> {
+    let (d, m, s) = (args[0], args[1], args[2]);
+    for part in [m, s] {
+        if !(0.0..60.0).contains(&part) {
+            return Err(CalcError::DomainError {
+                name: "dms".to_string(),
+                arg: part,
+            });
+        }
+    }
+    let magnitude = d.abs() + m / 60.0 + s / 3600.0;
+    Ok(if d.is_sign_negative() {
+        -magnitude
+    } else {
+        magnitude
+    })
+}
+
 const FUNCTIONS: &[BuiltinFunc] = &[
     BuiltinFunc {
         name: "sqrt",
@@ -52,6 +445,12 @@ const FUNCTIONS: &[BuiltinFunc] = &[
         max_arity: Some(1),
         eval: sqrt_impl,
     },
+    BuiltinFunc {
+        name: "cbrt",
+        min_arity: 1,
+        max_arity: Some(1),
+        eval: cbrt_impl,
+    },
     BuiltinFunc {
         name: "min",
         min_arity: 1,
@@ -64,9 +463,293 @@ const FUNCTIONS: &[BuiltinFunc] = &[
         max_arity: None,
         eval: max_impl,
     },
+    BuiltinFunc {
+        name: "fmin",
+        min_arity: 1,
+        max_arity: None,
+        eval: fmin_impl,
+    },
+    BuiltinFunc {
+        name: "fmax",
+        min_arity: 1,
+        max_arity: None,
+        eval: fmax_impl,
+    },
+    BuiltinFunc {
+        name: "sin",
+        min_arity: 1,
+        max_arity: Some(1),
+        eval: sin_impl,
+    },
+    BuiltinFunc {
+        name: "cos",
+        min_arity: 1,
+        max_arity: Some(1),
+        eval: cos_impl,
+    },
+    BuiltinFunc {
+        name: "tan",
+        min_arity: 1,
+        max_arity: Some(1),
+        eval: tan_impl,
+    },
+    BuiltinFunc {
+        name: "asin",
+        min_arity: 1,
+        max_arity: Some(1),
+        eval: asin_impl,
+    },
+    BuiltinFunc {
+        name: "acos",
+        min_arity: 1,
+        max_arity: Some(1),
+        eval: acos_impl,
+    },
+    BuiltinFunc {
+        name: "atan",
+        min_arity: 1,
+        max_arity: Some(1),
+        eval: atan_impl,
+    },
+    BuiltinFunc {
+        name: "atan2",
+        min_arity: 2,
+        max_arity: Some(2),
+        eval: atan2_impl,
+    },
+    BuiltinFunc {
+        name: "ln",
+        min_arity: 1,
+        max_arity: Some(1),
+        eval: ln_impl,
+    },
+    BuiltinFunc {
+        name: "log10",
+        min_arity: 1,
+        max_arity: Some(1),
+        eval: log10_impl,
+    },
+    BuiltinFunc {
+        name: "log2",
+        min_arity: 1,
+        max_arity: Some(1),
+        eval: log2_impl,
+    },
+    BuiltinFunc {
+        name: "log",
+        min_arity: 1,
+        max_arity: Some(2),
+        eval: log_impl,
+    },
+    BuiltinFunc {
+        name: "floor",
+        min_arity: 1,
+        max_arity: Some(1),
+        eval: floor_impl,
+    },
+    BuiltinFunc {
+        name: "ceil",
+        min_arity: 1,
+        max_arity: Some(1),
+        eval: ceil_impl,
+    },
+    BuiltinFunc {
+        name: "round",
+        min_arity: 1,
+        max_arity: Some(2),
+        eval: round_impl,
+    },
+    BuiltinFunc {
+        name: "trunc",
+        min_arity: 1,
+        max_arity: Some(1),
+        eval: trunc_impl,
+    },
+    BuiltinFunc {
+        name: "abs",
+        min_arity: 1,
+        max_arity: Some(1),
+        eval: abs_impl,
+    },
+    BuiltinFunc {
+        name: "sign",
+        min_arity: 1,
+        max_arity: Some(1),
+        eval: sign_impl,
+    },
+    BuiltinFunc {
+        name: "exp",
+        min_arity: 1,
+        max_arity: Some(1),
+        eval: exp_impl,
+    },
+    BuiltinFunc {
+        name: "sum",
+        min_arity: 1,
+        max_arity: None,
+        eval: sum_impl,
+    },
+    BuiltinFunc {
+        name: "product",
+        min_arity: 1,
+        max_arity: None,
+        eval: product_impl,
+    },
+    BuiltinFunc {
+        name: "prod",
+        min_arity: 1,
+        max_arity: None,
+        eval: product_impl,
+    },
+    BuiltinFunc {
+        name: "mean",
+        min_arity: 1,
+        max_arity: None,
+        eval: mean_impl,
+    },
+    BuiltinFunc {
+        name: "avg",
+        min_arity: 1,
+        max_arity: None,
+        eval: mean_impl,
+    },
+    BuiltinFunc {
+        name: "median",
+        min_arity: 1,
+        max_arity: None,
+        eval: median_impl,
+    },
+    BuiltinFunc {
+        name: "ncr",
+        min_arity: 2,
+        max_arity: Some(2),
+        eval: ncr_impl,
+    },
+    BuiltinFunc {
+        name: "npr",
+        min_arity: 2,
+        max_arity: Some(2),
+        eval: npr_impl,
+    },
+    BuiltinFunc {
+        name: "clamp",
+        min_arity: 3,
+        max_arity: Some(3),
+        eval: clamp_impl,
+    },
+    BuiltinFunc {
+        name: "clamp01",
+        min_arity: 1,
+        max_arity: Some(1),
+        eval: clamp01_impl,
+    },
+    BuiltinFunc {
+        name: "lerp",
+        min_arity: 3,
+        max_arity: Some(3),
+        eval: lerp_impl,
+    },
+    BuiltinFunc {
+        name: "percent_change",
+        min_arity: 2,
+        max_arity: Some(2),
+        eval: percent_change_impl,
+    },
+    BuiltinFunc {
+        name: "percent_of",
+        min_arity: 2,
+        max_arity: Some(2),
+        eval: percent_of_impl,
+    },
+    BuiltinFunc {
+        name: "hypot",
+        min_arity: 2,
+        max_arity: Some(2),
+        eval: hypot_impl,
+    },
+    BuiltinFunc {
+        name: "pow",
+        min_arity: 2,
+        max_arity: Some(2),
+        eval: pow_fn_impl,
+    },
+    BuiltinFunc {
+        name: "root",
+        min_arity: 2,
+        max_arity: Some(2),
+        eval: root_impl,
+    },
+    BuiltinFunc {
+        name: "deg2rad",
+        min_arity: 1,
+        max_arity: Some(1),
+        eval: deg2rad_impl,
+    },
+    BuiltinFunc {
+        name: "rad2deg",
+        min_arity: 1,
+        max_arity: Some(1),
+        eval: rad2deg_impl,
+    },
+    BuiltinFunc {
+        name: "normalize_angle",
+        min_arity: 1,
+        max_arity: Some(1),
+        eval: normalize_angle_impl,
+    },
+    BuiltinFunc {
+        name: "dms",
+        min_arity: 3,
+        max_arity: Some(3),
+        eval: dms_impl,
+    },
 ];
 
-fn normalize_name(name: &str) -> String {
+pub(crate) fn functions() -> &'static [BuiltinFunc] {
+    FUNCTIONS
+}
+
+/// Name, minimum and maximum arity (`None` for variadic) of every builtin
+/// function, including the lazily evaluated `if`.
+pub fn list_functions() -> Vec<(&'static str, usize, Option<usize>)> {
+    FUNCTIONS
+        .iter()
+        .map(|f| (f.name, f.min_arity, f.max_arity))
+        .chain([("if", 3, Some(3))])
+        .collect()
+}
+
+/// Name and value of every builtin constant.
+pub fn list_constants() -> Vec<(&'static str, f64)> {
+    CONSTANTS.iter().map(|c| (c.name, c.value)).collect()
+}
+
+// Which builtins take or produce angles, so the angle mode can convert them.
+#[derive(Clone, Copy, PartialEq)]
+pub(crate) enum AngleUse {
+    Argument,
+    Result,
+    Both,
+}
+
+const ANGLE_ARGUMENT_FUNCTIONS: &[&str] = &["sin", "cos", "tan"];
+const ANGLE_RESULT_FUNCTIONS: &[&str] = &["asin", "acos", "atan", "atan2"];
+const ANGLE_BOTH_FUNCTIONS: &[&str] = &["normalize_angle"];
+
+pub(crate) fn angle_use(name: &str) -> Option<AngleUse> {
+    let name = normalize_name(name);
+    if ANGLE_ARGUMENT_FUNCTIONS.contains(&name.as_str()) {
+        Some(AngleUse::Argument)
+    } else if ANGLE_RESULT_FUNCTIONS.contains(&name.as_str()) {
+        Some(AngleUse::Result)
+    } else if ANGLE_BOTH_FUNCTIONS.contains(&name.as_str()) {
+        Some(AngleUse::Both)
+    } else {
+        None
+    }
+}
+
+pub(crate) fn normalize_name(name: &str) -> String {
     name.to_ascii_lowercase()
 }
 
@@ -78,30 +761,86 @@ pub(crate) fn eval_constant(name: &str) -> Option<f64> {
         .map(|c| c.value)
 }
 
+// The closest builtin name to a misspelled one, for "did you mean" hints. A
+// name only gets a suggestion within two edits, and only if at most half of it
+// changes, so a one-letter variable is never "corrected" to `e`.
+fn closest_name<'a>(name: &str, candidates: impl Iterator<Item = &'a str>) -> Option<&'a str> {
+    let name = normalize_name(name);
+    let len = name.chars().count();
+    candidates
+        .map(|candidate| (edit_distance(&name, candidate), candidate))
+        .filter(|&(distance, _)| distance > 0 && distance <= 2 && distance * 2 <= len)
+        .min_by_key(|&(distance, _)| distance)
+        .map(|(_, candidate)| candidate)
+}
+
+pub(crate) fn suggest_function(name: &str) -> Option<&'static str> {
+    closest_name(name, list_functions().into_iter().map(|(name, ..)| name))
+}
+
+pub(crate) fn suggest_constant(name: &str) -> Option<&'static str> {
+    closest_name(name, CONSTANTS.iter().map(|c| c.name))
+}
+
+// Levenshtein distance over chars, keeping one row of the table at a time.
+fn edit_distance(a: &str, b: &str) -> usize {
+    let b: Vec<char> = b.chars().collect();
+    let mut row: Vec<usize> = (0..=b.len()).collect();
+    for (i, ca) in a.chars().enumerate() {
+        let mut diagonal = row[0];
+        row[0] = i + 1;
+        for (j, &cb) in b.iter().enumerate() {
+            let substitution = diagonal + usize::from(ca != cb);
+            diagonal = row[j + 1];
+            row[j + 1] = substitution.min(row[j] + 1).min(diagonal + 1);
+        }
+    }
+    row[b.len()]
+}
+
 pub(crate) fn eval_function(name: &str, args: &[f64]) -> Result<f64, CalcError> {
     let normalized = normalize_name(name);
     let Some(func) = FUNCTIONS.iter().find(|f| f.name == normalized) else {
         return Err(CalcError::UnknownFunction(name.to_string()));
     };
 
-    if args.len() < func.min_arity {
+    check_arity(name, func.min_arity, func.max_arity, args.len())?;
+    (func.eval)(args)
+}
+
+// `(min, max)` argument counts of a builtin function; `max` is `None` for
+// variadic functions.
+pub(crate) fn function_arity(name: &str) -> Option<(usize, Option<usize>)> {
+    let name = normalize_name(name);
+    FUNCTIONS
+        .iter()
+        .find(|f| f.name == name)
+        .map(|f| (f.min_arity, f.max_arity))
+}
+
+pub(crate) fn check_arity(
+    name: &str,
+    min_arity: usize,
+    max_arity: Option<usize>,
+    got: usize,
+) -> Result<(), CalcError> {
+    if got < min_arity {
         return Err(CalcError::WrongArity {
             name: name.to_string(),
-            expected: func.min_arity,
-            got: args.len(),
+            expected: min_arity,
+            got,
         });
     }
-    if let Some(max) = func.max_arity {
-        if args.len() > max {
-            return Err(CalcError::WrongArity {
-                name: name.to_string(),
-                expected: max,
-                got: args.len(),
-            });
-        }
+    if let Some(max) = max_arity
+        && got > max
+    {
+        return Err(CalcError::WrongArity {
+            name: name.to_string(),
+            expected: max,
+            got,
+        });
     }
-
-    (func.eval)(args)
+    Ok(())
 }
 
 #[derive(Clone, Copy)]
@@ -110,14 +849,21 @@ enum Assoc {
     Right,
 }
 
+type PrefixFn = fn(f64) -> Result<f64, CalcError>;
+type InfixFn = fn(f64, f64) -> Result<f64, CalcError>;
+
 struct BuiltinOp {
     symbol: Operator,
     // Higher number = binds tighter. Prefix precedence must be < '^' to keep `-2^2` == `-(2^2)`.
     prefix_precedence: Option<u8>,
     infix_precedence: Option<u8>,
     infix_assoc: Option<Assoc>,
-    eval_prefix: Option<fn(f64) -> Result<f64, CalcError>>,
-    eval_infix: Option<fn(f64, f64) -> Result<f64, CalcError>>,
+    // Postfix operators follow their operand (`50%`). A symbol may be prefix or
+    // postfix but not both, since `UnaryOp` does not record which it is.
+    postfix_precedence: Option<u8>,
+    eval_prefix: Option<PrefixFn>,
+    eval_infix: Option<InfixFn>,
+    eval_postfix: Option<PrefixFn>,
 }
 
 fn add_impl(a: f64, b: f64) -> Result<f64, CalcError> {
@@ -135,66 +881,364 @@ fn div_impl(a: f64, b: f64) -> Result<f64, CalcError> {
     }
     Ok(a / b)
 }
+// Truncated remainder (sign follows the dividend), matching Rust's `%`: -5 % 3 == -2.
+fn mod_impl(a: f64, b: f64) -> Result<f64, CalcError> {
+    if b == 0.0 {
+        return Err(CalcError::DivideByZero);
+    }
+    Ok(a % b)
+}
+// Floor division rounds toward negative infinity, like Python: -7 // 2 == -4.
+fn floor_div_impl(a: f64, b: f64) -> Result<f64, CalcError> {
+    Ok(div_impl(a, b)?.floor())
+}
 fn pow_impl(a: f64, b: f64) -> Result<f64, CalcError> {
     Ok(a.powf(b))
 }
 
+// Comparisons yield 1.0 (true) or 0.0 (false). Equality is exact IEEE-754
+// comparison with no tolerance, so `0.1 + 0.2 == 0.3` is 0 and `nan == nan` is 0.
+fn bool_value(b: bool) -> f64 {
+    if b { 1.0 } else { 0.0 }
+}
+fn lt_impl(a: f64, b: f64) -> Result<f64, CalcError> {
+    Ok(bool_value(a < b))
+}
+fn gt_impl(a: f64, b: f64) -> Result<f64, CalcError> {
+    Ok(bool_value(a > b))
+}
+fn le_impl(a: f64, b: f64) -> Result<f64, CalcError> {
+    Ok(bool_value(a <= b))
+}
+fn ge_impl(a: f64, b: f64) -> Result<f64, CalcError> {
+    Ok(bool_value(a >= b))
+}
+fn eq_impl(a: f64, b: f64) -> Result<f64, CalcError> {
+    Ok(bool_value(a == b))
+}
+fn ne_impl(a: f64, b: f64) -> Result<f64, CalcError> {
+    Ok(bool_value(a != b))
+}
+
+// Bitwise and shift operators work on the two's-complement `i64` value of
+// integer operands.
+fn bit_operands(op: Operator, a: f64, b: f64) -> Result<(i64, i64), CalcError> {
+    let to_i64 = |value: f64| match integer::to_i64(value) {
+        Err(CalcError::NonInteger) => Err(CalcError::NonIntegerOperand(op.to_string())),
+        result => result,
+    };
+    Ok((to_i64(a)?, to_i64(b)?))
+}
+fn bit_and_impl(a: f64, b: f64) -> Result<f64, CalcError> {
+    let (a, b) = bit_operands("&", a, b)?;
+    Ok((a & b) as f64)
+}
+fn bit_or_impl(a: f64, b: f64) -> Result<f64, CalcError> {
+    let (a, b) = bit_operands("|", a, b)?;
+    Ok((a | b) as f64)
+}
+fn bit_xor_impl(a: f64, b: f64) -> Result<f64, CalcError> {
+    let (a, b) = bit_operands("~^", a, b)?;
+    Ok((a ^ b) as f64)
+}
+fn shl_impl(a: f64, b: f64) -> Result<f64, CalcError> {
+    let (a, b) = bit_operands("<<", a, b)?;
+    Ok(integer::shift("<<", a, b)? as f64)
+}
+fn shr_impl(a: f64, b: f64) -> Result<f64, CalcError> {
+    let (a, b) = bit_operands(">>", a, b)?;
+    Ok(integer::shift(">>", a, b)? as f64)
+}
+
 fn unary_plus_impl(a: f64) -> Result<f64, CalcError> {
     Ok(a)
 }
 fn unary_minus_impl(a: f64) -> Result<f64, CalcError> {
     Ok(-a)
 }
+fn sqrt_prefix_impl(a: f64) -> Result<f64, CalcError> {
+    Ok(a.sqrt())
+}
+fn percent_impl(a: f64) -> Result<f64, CalcError> {
+    Ok(a / 100.0)
+}
+// Defined for non-negative integers only. Anything past 170! is beyond `f64`,
+// so the loop is skipped rather than multiplied out to infinity.
+fn factorial_impl(n: f64) -> Result<f64, CalcError> {
+    if n < 0.0 || n.fract() != 0.0 || n.is_nan() {
+        return Err(CalcError::DomainError {
+            name: "!".to_string(),
+            arg: n,
+        });
+    }
+    if n > 170.0 {
+        return Ok(f64::INFINITY);
+    }
+    let mut result = 1.0;
+    let mut i = 2.0;
+    while i <= n {
+        result *= i;
+        i += 1.0;
+    }
+    Ok(result)
+}
 
 const OPS: &[BuiltinOp] = &[
     BuiltinOp {
-        symbol: '+',
+        symbol: "+",
         prefix_precedence: Some(25),
         infix_precedence: Some(10),
         infix_assoc: Some(Assoc::Left),
+        postfix_precedence: None,
         eval_prefix: Some(unary_plus_impl),
         eval_infix: Some(add_impl),
+        eval_postfix: None,
     },
     BuiltinOp {
-        symbol: '-',
+        symbol: "-",
         prefix_precedence: Some(25),
         infix_precedence: Some(10),
         infix_assoc: Some(Assoc::Left),
+        postfix_precedence: None,
         eval_prefix: Some(unary_minus_impl),
         eval_infix: Some(sub_impl),
+        eval_postfix: None,
     },
     BuiltinOp {
-        symbol: '*',
+        symbol: "*",
         prefix_precedence: None,
         infix_precedence: Some(20),
         infix_assoc: Some(Assoc::Left),
+        postfix_precedence: None,
         eval_prefix: None,
         eval_infix: Some(mul_impl),
+        eval_postfix: None,
     },
     BuiltinOp {
-        symbol: '/',
+        symbol: "/",
         prefix_precedence: None,
         infix_precedence: Some(20),
         infix_assoc: Some(Assoc::Left),
+        postfix_precedence: None,
         eval_prefix: None,
         eval_infix: Some(div_impl),
+        eval_postfix: None,
     },
     BuiltinOp {
-        symbol: '^',
+        symbol: "//",
+        prefix_precedence: None,
+        infix_precedence: Some(20),
+        infix_assoc: Some(Assoc::Left),
+        postfix_precedence: None,
+        eval_prefix: None,
+        eval_infix: Some(floor_div_impl),
+        eval_postfix: None,
+    },
+    // Infix `%` is modulo; postfix `%` is percent (`50%` is 0.5). See
+    // `Parser::postfix_operator` for how the two are told apart.
+    BuiltinOp {
+        symbol: "%",
+        prefix_precedence: None,
+        infix_precedence: Some(20),
+        infix_assoc: Some(Assoc::Left),
+        postfix_precedence: Some(40),
+        eval_prefix: None,
+        eval_infix: Some(mod_impl),
+        eval_postfix: Some(percent_impl),
+    },
+    // Factorial: `5!` is 120. The lexer's longest match keeps `!=` intact, so
+    // `5!=120` is `5 != 120`; write `(5!) == 120` instead.
+    BuiltinOp {
+        symbol: "!",
+        prefix_precedence: None,
+        infix_precedence: None,
+        infix_assoc: None,
+        postfix_precedence: Some(40),
+        eval_prefix: None,
+        eval_infix: None,
+        eval_postfix: Some(factorial_impl),
+    },
+    BuiltinOp {
+        symbol: "^",
         prefix_precedence: None,
         infix_precedence: Some(30),
         infix_assoc: Some(Assoc::Right),
+        postfix_precedence: None,
         eval_prefix: None,
         eval_infix: Some(pow_impl),
+        eval_postfix: None,
+    },
+    BuiltinOp {
+        symbol: "<",
+        prefix_precedence: None,
+        infix_precedence: Some(5),
+        infix_assoc: Some(Assoc::Left),
+        postfix_precedence: None,
+        eval_prefix: None,
+        eval_infix: Some(lt_impl),
+        eval_postfix: None,
+    },
+    BuiltinOp {
+        symbol: ">",
+        prefix_precedence: None,
+        infix_precedence: Some(5),
+        infix_assoc: Some(Assoc::Left),
+        postfix_precedence: None,
+        eval_prefix: None,
+        eval_infix: Some(gt_impl),
+        eval_postfix: None,
+    },
+    BuiltinOp {
+        symbol: "<=",
+        prefix_precedence: None,
+        infix_precedence: Some(5),
+        infix_assoc: Some(Assoc::Left),
+        postfix_precedence: None,
+        eval_prefix: None,
+        eval_infix: Some(le_impl),
+        eval_postfix: None,
+    },
+    BuiltinOp {
+        symbol: ">=",
+        prefix_precedence: None,
+        infix_precedence: Some(5),
+        infix_assoc: Some(Assoc::Left),
+        postfix_precedence: None,
+        eval_prefix: None,
+        eval_infix: Some(ge_impl),
+        eval_postfix: None,
+    },
+    BuiltinOp {
+        symbol: "==",
+        prefix_precedence: None,
+        infix_precedence: Some(5),
+        infix_assoc: Some(Assoc::Left),
+        postfix_precedence: None,
+        eval_prefix: None,
+        eval_infix: Some(eq_impl),
+        eval_postfix: None,
+    },
+    BuiltinOp {
+        symbol: "!=",
+        prefix_precedence: None,
+        infix_precedence: Some(5),
+        infix_assoc: Some(Assoc::Left),
+        postfix_precedence: None,
+        eval_prefix: None,
+        eval_infix: Some(ne_impl),
+        eval_postfix: None,
+    },
+    // Shifts sit between comparisons and `+`/`-`, as in C: `1 << 2 + 1` is 8.
+    BuiltinOp {
+        symbol: "<<",
+        prefix_precedence: None,
+        infix_precedence: Some(7),
+        infix_assoc: Some(Assoc::Left),
+        postfix_precedence: None,
+        eval_prefix: None,
+        eval_infix: Some(shl_impl),
+        eval_postfix: None,
+    },
+    BuiltinOp {
+        symbol: ">>",
+        prefix_precedence: None,
+        infix_precedence: Some(7),
+        infix_assoc: Some(Assoc::Left),
+        postfix_precedence: None,
+        eval_prefix: None,
+        eval_infix: Some(shr_impl),
+        eval_postfix: None,
+    },
+    // Bitwise operators bind looser than comparisons, tightest first: `&`, xor
+    // (`~^`, since `^` is power), `|`. Inside `|...|` a bar closes the absolute
+    // value, so write `|(a | b)|`.
+    BuiltinOp {
+        symbol: "&",
+        prefix_precedence: None,
+        infix_precedence: Some(4),
+        infix_assoc: Some(Assoc::Left),
+        postfix_precedence: None,
+        eval_prefix: None,
+        eval_infix: Some(bit_and_impl),
+        eval_postfix: None,
+    },
+    BuiltinOp {
+        symbol: "~^",
+        prefix_precedence: None,
+        infix_precedence: Some(3),
+        infix_assoc: Some(Assoc::Left),
+        postfix_precedence: None,
+        eval_prefix: None,
+        eval_infix: Some(bit_xor_impl),
+        eval_postfix: None,
+    },
+    BuiltinOp {
+        symbol: "|",
+        prefix_precedence: None,
+        infix_precedence: Some(2),
+        infix_assoc: Some(Assoc::Left),
+        postfix_precedence: None,
+        eval_prefix: None,
+        eval_infix: Some(bit_or_impl),
+        eval_postfix: None,
+    },
+    // `√x` binds like unary minus, so `√9^2` is `√(9^2)`.
+    BuiltinOp {
+        symbol: "√",
+        prefix_precedence: Some(25),
+        infix_precedence: None,
+        infix_assoc: None,
+        postfix_precedence: None,
+        eval_prefix: Some(sqrt_prefix_impl),
+        eval_infix: None,
+        eval_postfix: None,
     },
 ];
 
-fn find_op(op: Operator) -> Option<&'static BuiltinOp> {
+// Alternative spellings, lexed straight to the canonical symbol so the AST
+// (and `Display`) only ever contains the symbols in `OPS`.
+const OP_ALIASES: &[(&str, Operator)] = &[("×", "*"), ("÷", "/"), ("−", "-"), ("**", "^")];
+
+// Words that lex as operators instead of identifiers: `7 mod 3` is `7 % 3`,
+// `6 xor 3` is `6 ~^ 3`.
+// Matched case-insensitively and only as whole words, so `modulus` stays a name.
+const KEYWORD_OPS: &[(&str, Operator)] = &[("mod", "%"), ("xor", "~^")];
+
+pub(crate) fn keyword_operator(word: &str) -> Option<Operator> {
+    KEYWORD_OPS
+        .iter()
+        .find(|(keyword, _)| keyword.eq_ignore_ascii_case(word))
+        .map(|&(_, op)| op)
+}
+
+fn find_op(op: &str) -> Option<&'static BuiltinOp> {
     OPS.iter().find(|o| o.symbol == op)
 }
 
-pub(crate) fn is_operator_char(ch: char) -> bool {
-    find_op(ch).is_some()
+// Longest operator symbol that `input` starts with, so `<=` wins over `<`.
+// Returns the canonical operator and how many chars were matched.
+pub(crate) fn match_operator(input: &[char]) -> Option<(Operator, usize)> {
+    OPS.iter()
+        .map(|o| (o.symbol, o.symbol))
+        .chain(OP_ALIASES.iter().copied())
+        .filter(|(spelling, _)| {
+            spelling.chars().count() <= input.len()
+                && spelling.chars().zip(input).all(|(a, &b)| a == b)
+        })
+        .max_by_key(|(spelling, _)| spelling.chars().count())
+        .map(|(spelling, op)| (op, spelling.chars().count()))
+}
+
+#[cfg(feature = "serde")]
+pub(crate) fn deserialize_operator<'de, D>(deserializer: D) -> Result<Operator, D::Error>
+where
+    D: serde::Deserializer<'de>,
+{
+    use serde::Deserialize;
+    let symbol = String::deserialize(deserializer)?;
+    find_op(&symbol)
+        .map(|o| o.symbol)
+        .ok_or_else(|| serde::de::Error::custom(alloc::format!("unknown operator: {symbol}")))
 }
 
 pub(crate) fn infix_binding_power(op: Operator) -> Option<(u8, u8)> {
@@ -221,10 +1265,16 @@ pub(crate) fn eval_infix(op: Operator, left: f64, right: f64) -> Result<f64, Cal
     eval(left, right)
 }
 
-pub(crate) fn eval_prefix(op: Operator, value: f64) -> Result<f64, CalcError> {
+pub(crate) fn postfix_binding_power(op: Operator) -> Option<u8> {
+    find_op(op)?.postfix_precedence
+}
+
+// Evaluates a `UnaryOp` node, whose operator is either prefix or postfix.
+pub(crate) fn eval_unary(op: Operator, value: f64) -> Result<f64, CalcError> {
     let info = find_op(op).ok_or_else(|| CalcError::UnknownFunction(op.to_string()))?;
     let eval = info
         .eval_prefix
+        .or(info.eval_postfix)
         .ok_or_else(|| CalcError::UnknownFunction(op.to_string()))?;
     eval(value)
 }
@@ -0,0 +1,471 @@
+use alloc::boxed::Box;
+use alloc::collections::BTreeMap;
+use alloc::rc::Rc;
+use alloc::string::{String, ToString};
+use alloc::vec::Vec;
+use core::cell::Cell;
+
+use crate::builtins::{self, AngleUse};
+use crate::context::Context;
+use crate::error::CalcError;
+use crate::eval::{self, Evaluator};
+use crate::lexer::Lexer;
+use crate::parser::{self, Expression, Statement};
+use crate::random::{self, RANDOM_FUNCTION};
+
+type CustomFn = Box<dyn Fn(&[f64]) -> Result<f64, CalcError>>;
+
+struct RegisteredFunc {
+    name: String, // as registered, for re-keying when case sensitivity changes
+    min_arity: usize,
+    max_arity: Option<usize>,
+    eval: CustomFn,
+    angle: Option<AngleUse>, // only set for builtin trig functions
+}
+
+/// Unit used by the trigonometric builtins. In `Degrees` mode `sin`/`cos`/`tan`
+/// take degrees, `asin`/`acos`/`atan`/`atan2` return degrees and
+/// `normalize_angle` wraps into `[0, 360)`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum AngleMode {
+    #[default]
+    Radians,
+    Degrees,
+}
+
+/// What division by zero produces. `Infinity` follows IEEE 754: `1/0` is
+/// `inf`, `-1/0` is `-inf` and `0/0` (like `x % 0`) is NaN.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum DivByZero {
+    #[default]
+    Error,
+    Infinity,
+}
+
+/// What `^` means. `Xor` is for users used to C: `6^3` is 5. The operator keeps
+/// the precedence and right associativity of power, so `1 + 6^3` is `1 + (6
+/// xor 3)`, and like `~^` it needs integer operands. `**` is lexed as `^`, so
+/// it switches too; use `pow` for powers in `Xor` mode.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum CaretMode {
+    #[default]
+    Power,
+    Xor,
+}
+
+/// A calculator with its own function and constant registry, seeded with the
+/// builtins, plus a variable context and evaluation settings.
+///
+/// Functions and constants registered here are visible only to this
+/// calculator; the free functions (`rustcalc::eval`, ...) always use the
+/// builtin tables. See `Calculator::builder` for configuring one in a single
+/// expression.
+///
+/// A calculator also provides `random()` and `random(lo, hi)`, drawing from
+/// its own generator (see `Calculator::seed_random`). They are not available
+/// to the stateless free functions.
+pub struct Calculator {
+    functions: BTreeMap<String, RegisteredFunc>, // keys lowercase unless case-sensitive
+    constants: BTreeMap<String, (String, f64)>,  // same keys; values keep the registered name
+    context: Context,
+    max_depth: usize,
+    angle_mode: AngleMode,
+    div_by_zero: DivByZero,
+    require_finite: bool,
+    caret_mode: CaretMode,
+    case_sensitive: bool,
+    comma_separators: bool,
+    rng: Rc<Cell<u64>>, // shared with the registered `random` function
+}
+
+impl Default for Calculator {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Calculator {
+    pub fn new() -> Self {
+        let mut functions = BTreeMap::new();
+        for func in builtins::functions() {
+            functions.insert(
+                func.name.to_string(),
+                RegisteredFunc {
+                    name: func.name.to_string(),
+                    min_arity: func.min_arity,
+                    max_arity: func.max_arity,
+                    eval: Box::new(func.eval),
+                    angle: builtins::angle_use(func.name),
+                },
+            );
+        }
+        let rng = Rc::new(Cell::new(random::entropy_seed()));
+        let state = Rc::clone(&rng);
+        functions.insert(
+            RANDOM_FUNCTION.to_string(),
+            RegisteredFunc {
+                name: RANDOM_FUNCTION.to_string(),
+                min_arity: 0,
+                max_arity: Some(2),
+                eval: Box::new(move |args| random::random_impl(&state, args)),
+                angle: None,
+            },
+        );
+        Calculator {
+            functions,
+            constants: BTreeMap::new(),
+            context: Context::new(),
+            max_depth: parser::DEFAULT_MAX_DEPTH,
+            angle_mode: AngleMode::default(),
+            div_by_zero: DivByZero::default(),
+            require_finite: false,
+            caret_mode: CaretMode::default(),
+            case_sensitive: false,
+            comma_separators: false,
+            rng,
+        }
+    }
+
+    pub fn builder() -> CalculatorBuilder {
+        CalculatorBuilder {
+            calc: Calculator::new(),
+        }
+    }
+
+    pub fn set_angle_mode(&mut self, angle_mode: AngleMode) {
+        self.angle_mode = angle_mode;
+    }
+
+    pub fn angle_mode(&self) -> AngleMode {
+        self.angle_mode
+    }
+
+    /// Whether `/` and `%` by zero fail with `CalcError::DivideByZero` (the
+    /// default) or return the IEEE 754 result.
+    pub fn set_div_by_zero(&mut self, div_by_zero: DivByZero) {
+        self.div_by_zero = div_by_zero;
+    }
+
+    pub fn div_by_zero(&self) -> DivByZero {
+        self.div_by_zero
+    }
+
+    /// When enabled, any NaN or infinite intermediate value (`sqrt(-1)`,
+    /// `ln(0)`, `1e308 * 10`, ...) fails with `CalcError::NonFinite`.
+    pub fn set_require_finite(&mut self, require_finite: bool) {
+        self.require_finite = require_finite;
+    }
+
+    pub fn require_finite(&self) -> bool {
+        self.require_finite
+    }
+
+    /// Whether `^` is exponentiation (the default) or bitwise xor.
+    pub fn set_caret_mode(&mut self, caret_mode: CaretMode) {
+        self.caret_mode = caret_mode;
+    }
+
+    pub fn caret_mode(&self) -> CaretMode {
+        self.caret_mode
+    }
+
+    /// When enabled, functions and constants registered on this calculator are
+    /// matched by their exact name, so `f` and `F` can be registered as two
+    /// different functions. Builtins stay case-insensitive (`SQRT(4)` still
+    /// works), and variables are always case-sensitive. Names registered
+    /// earlier stay reachable under the spelling they were registered with;
+    /// if turning this off makes two of them collide (`f` and `F`), only one
+    /// is kept.
+    pub fn set_case_sensitive(&mut self, case_sensitive: bool) {
+        self.case_sensitive = case_sensitive;
+        let functions = core::mem::take(&mut self.functions);
+        self.functions = functions
+            .into_values()
+            .map(|func| (self.registry_key(&func.name), func))
+            .collect();
+        let constants = core::mem::take(&mut self.constants);
+        self.constants = constants
+            .into_values()
+            .map(|(name, value)| (self.registry_key(&name), (name, value)))
+            .collect();
+    }
+
+    pub fn case_sensitive(&self) -> bool {
+        self.case_sensitive
+    }
+
+    /// When enabled, a comma followed by exactly three digits inside a number
+    /// is a thousands separator: `1,000 + 1` is `1001`. Argument commas still
+    /// work when not directly between digits, as in `max(1, 2)`; `max(1,234)`
+    /// reads as the single argument `1234`.
+    pub fn set_comma_separators(&mut self, comma_separators: bool) {
+        self.comma_separators = comma_separators;
+    }
+
+    pub fn comma_separators(&self) -> bool {
+        self.comma_separators
+    }
+
+    /// Restarts `random` from `seed`, so the values it returns repeat exactly
+    /// for the same seed. Unseeded calculators start from a fresh seed each
+    /// (a fixed one without the `std` feature).
+    pub fn seed_random(&mut self, seed: u64) {
+        self.rng.set(seed);
+    }
+
+    /// Maximum nesting depth accepted by the parser (default 256). Deeper
+    /// input fails with `CalcError::NestingTooDeep` instead of overflowing
+    /// the stack. Parentheses, call arguments, prefix operands, right operands
+    /// of `^` and `let` bindings each count as a level; left-associative chains
+    /// such as `1+1+...+1` do not, since nothing walks them recursively.
+    pub fn set_max_depth(&mut self, max_depth: usize) {
+        self.max_depth = max_depth;
+    }
+
+    pub fn max_depth(&self) -> usize {
+        self.max_depth
+    }
+
+    pub fn parse(&self, input: &str) -> Result<Expression, CalcError> {
+        crate::parse_with_max_depth(self.lexer(input), self.max_depth)
+    }
+
+    pub fn parse_statement(&self, input: &str) -> Result<Statement, CalcError> {
+        crate::parse_statement_with_max_depth(self.lexer(input), self.max_depth)
+    }
+
+    pub fn parse_program(&self, input: &str) -> Result<Vec<Statement>, CalcError> {
+        crate::parse_program_with_max_depth(self.lexer(input), self.max_depth)
+    }
+
+    fn lexer(&self, input: &str) -> Lexer {
+        Lexer::new(input).comma_separators(self.comma_separators)
+    }
+
+    /// Registers (or replaces) a function. `max_arity: None` accepts any
+    /// number of arguments `>= min_arity`.
+    pub fn register_function<F>(
+        &mut self,
+        name: &str,
+        min_arity: usize,
+        max_arity: Option<usize>,
+        f: F,
+    ) where
+        F: Fn(&[f64]) -> Result<f64, CalcError> + 'static,
+    {
+        self.functions.insert(
+            self.registry_key(name),
+            RegisteredFunc {
+                name: name.to_string(),
+                min_arity,
+                max_arity,
+                eval: Box::new(f),
+                angle: None,
+            },
+        );
+    }
+
+    /// Registers (or replaces) a constant. Like the builtin constants, it is
+    /// shadowed by context variables and cannot be assigned to.
+    pub fn register_constant(&mut self, name: &str, value: f64) {
+        self.constants
+            .insert(self.registry_key(name), (name.to_string(), value));
+    }
+
+    pub fn constant(&self, name: &str) -> Option<f64> {
+        self.constants
+            .get(&self.registry_key(name))
+            .map(|&(_, value)| value)
+            .or_else(|| builtins::eval_constant(name))
+    }
+
+    /// Variables used by `eval` and `eval_expression`.
+    pub fn context(&self) -> &Context {
+        &self.context
+    }
+
+    pub fn context_mut(&mut self) -> &mut Context {
+        &mut self.context
+    }
+
+    pub fn has_function(&self, name: &str) -> bool {
+        self.function(name).is_some()
+    }
+
+    pub fn call_function(&self, name: &str, args: &[f64]) -> Result<f64, CalcError> {
+        let Some(func) = self.function(name) else {
+            return Err(CalcError::UnknownFunction(name.to_string()));
+        };
+        builtins::check_arity(name, func.min_arity, func.max_arity, args.len())?;
+        match (self.angle_mode, func.angle) {
+            (AngleMode::Degrees, Some(AngleUse::Argument)) => {
+                let radians: Vec<f64> = args.iter().map(|a| a.to_radians()).collect();
+                (func.eval)(&radians)
+            }
+            (AngleMode::Degrees, Some(AngleUse::Result)) => Ok((func.eval)(args)?.to_degrees()),
+            (AngleMode::Degrees, Some(AngleUse::Both)) => {
+                let radians: Vec<f64> = args.iter().map(|a| a.to_radians()).collect();
+                Ok((func.eval)(&radians)?.to_degrees())
+            }
+            _ => (func.eval)(args),
+        }
+    }
+
+    fn registry_key(&self, name: &str) -> String {
+        if self.case_sensitive {
+            name.to_string()
+        } else {
+            builtins::normalize_name(name)
+        }
+    }
+
+    // An exact match first; in case-sensitive mode a builtin (stored
+    // lowercase) is still found under any spelling.
+    fn function(&self, name: &str) -> Option<&RegisteredFunc> {
+        if let Some(func) = self.functions.get(&self.registry_key(name)) {
+            return Some(func);
+        }
+        let normalized = builtins::normalize_name(name);
+        let builtin =
+            normalized == RANDOM_FUNCTION || builtins::function_arity(&normalized).is_some();
+        if builtin {
+            self.functions.get(&normalized)
+        } else {
+            None
+        }
+    }
+
+    /// Evaluates `input` against this calculator's own context.
+    pub fn eval(&self, input: &str) -> Result<f64, CalcError> {
+        self.eval_with(input, &self.context)
+    }
+
+    pub fn eval_expression(&self, expr: &Expression) -> Result<f64, CalcError> {
+        self.eval_expression_with(expr, &self.context)
+    }
+
+    pub fn eval_with(&self, input: &str, ctx: &Context) -> Result<f64, CalcError> {
+        let expr = self.parse(input)?;
+        self.eval_expression_with(&expr, ctx)
+    }
+
+    pub fn eval_expression_with(&self, expr: &Expression, ctx: &Context) -> Result<f64, CalcError> {
+        self.with_evaluator(|evaluator| evaluator.eval(expr, ctx))
+    }
+
+    pub fn eval_statement(&self, stmt: &Statement, ctx: &mut Context) -> Result<f64, CalcError> {
+        self.with_evaluator(|evaluator| eval::evaluate_statement(stmt, ctx, evaluator))
+    }
+
+    pub fn eval_program(
+        &self,
+        statements: &[Statement],
+        ctx: &mut Context,
+    ) -> Result<f64, CalcError> {
+        self.with_evaluator(|evaluator| eval::evaluate_program(statements, ctx, evaluator))
+    }
+
+    fn with_evaluator<T>(&self, f: impl FnOnce(&Evaluator<'_>) -> T) -> T {
+        let call_function = |name: &str, args: &[f64]| self.call_function(name, args);
+        let constant = |name: &str| self.constant(name);
+        f(&Evaluator {
+            call_function: &call_function,
+            constant: &constant,
+            div_by_zero: self.div_by_zero,
+            require_finite: self.require_finite,
+            caret_mode: self.caret_mode,
+        })
+    }
+}
+
+/// Builds a configured `Calculator`:
+///
+/// ```
+/// use rustcalc::{AngleMode, Calculator};
+///
+/// let calc = Calculator::builder()
+///     .angle_mode(AngleMode::Degrees)
+///     .constant("right", 90.0)
+///     .variable("x", 2.0)
+///     .build();
+/// assert_eq!(calc.eval("x * sin(right)").unwrap(), 2.0);
+/// ```
+pub struct CalculatorBuilder {
+    calc: Calculator,
+}
+
+impl CalculatorBuilder {
+    pub fn angle_mode(mut self, angle_mode: AngleMode) -> Self {
+        self.calc.set_angle_mode(angle_mode);
+        self
+    }
+
+    pub fn div_by_zero(mut self, div_by_zero: DivByZero) -> Self {
+        self.calc.set_div_by_zero(div_by_zero);
+        self
+    }
+
+    pub fn require_finite(mut self, require_finite: bool) -> Self {
+        self.calc.set_require_finite(require_finite);
+        self
+    }
+
+    pub fn max_depth(mut self, max_depth: usize) -> Self {
+        self.calc.set_max_depth(max_depth);
+        self
+    }
+
+    pub fn caret_mode(mut self, caret_mode: CaretMode) -> Self {
+        self.calc.set_caret_mode(caret_mode);
+        self
+    }
+
+    pub fn case_sensitive(mut self, case_sensitive: bool) -> Self {
+        self.calc.set_case_sensitive(case_sensitive);
+        self
+    }
+
+    pub fn comma_separators(mut self, comma_separators: bool) -> Self {
+        self.calc.set_comma_separators(comma_separators);
+        self
+    }
+
+    pub fn seed_random(mut self, seed: u64) -> Self {
+        self.calc.seed_random(seed);
+        self
+    }
+
+    pub fn function<F>(
+        mut self,
+        name: &str,
+        min_arity: usize,
+        max_arity: Option<usize>,
+        f: F,
+    ) -> Self
+    where
+        F: Fn(&[f64]) -> Result<f64, CalcError> + 'static,
+    {
+        self.calc.register_function(name, min_arity, max_arity, f);
+        self
+    }
+
+    pub fn constant(mut self, name: &str, value: f64) -> Self {
+        self.calc.register_constant(name, value);
+        self
+    }
+
+    pub fn variable(mut self, name: impl Into<String>, value: f64) -> Self {
+        self.calc.context.set(name, value);
+        self
+    }
+
+    /// Replaces the whole variable context.
+    pub fn context(mut self, context: Context) -> Self {
+        self.calc.context = context;
+        self
+    }
+
+    pub fn build(self) -> Calculator {
+        self.calc
+    }
+}
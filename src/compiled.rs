@@ -0,0 +1,28 @@
+use crate::context::Context;
+use crate::error::CalcError;
+use crate::eval;
+use crate::parser::Expression;
+
+/// An expression parsed once for repeated evaluation, e.g. plotting `x^2 + 1`
+/// over many values of `x` without reparsing the text each time. Created by
+/// `rustcalc::compile`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct CompiledExpr {
+    expr: Expression,
+}
+
+impl CompiledExpr {
+    pub(crate) fn new(expr: Expression) -> Self {
+        CompiledExpr { expr }
+    }
+
+    /// Evaluates with the builtin functions and constants, looking up
+    /// variables in `ctx`.
+    pub fn eval(&self, ctx: &Context) -> Result<f64, CalcError> {
+        eval::evaluate_expression(&self.expr, ctx)
+    }
+
+    pub fn expression(&self) -> &Expression {
+        &self.expr
+    }
+}
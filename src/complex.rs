@@ -0,0 +1,258 @@
+use alloc::string::ToString;
+use alloc::vec::Vec;
+use core::fmt;
+use core::ops::{Add, Div, Mul, Neg, Sub};
+
+use crate::builtins::{self, Operator};
+use crate::error::CalcError;
+use crate::eval::{ExactValue, check_let_name, evaluate_exact};
+#[cfg(not(feature = "std"))]
+use crate::math::FloatExt;
+use crate::parser::Expression;
+
+/// A complex number `re + im·i`, the result of `rustcalc::eval_complex`.
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub struct Complex {
+    pub re: f64,
+    pub im: f64,
+}
+
+impl Complex {
+    /// The imaginary unit.
+    pub const I: Complex = Complex { re: 0.0, im: 1.0 };
+
+    pub fn new(re: f64, im: f64) -> Self {
+        Complex { re, im }
+    }
+
+    /// Magnitude `|z|`.
+    pub fn abs(self) -> f64 {
+        self.re.hypot(self.im)
+    }
+
+    /// Angle from the positive real axis, in `(-π, π]`.
+    pub fn arg(self) -> f64 {
+        self.im.atan2(self.re)
+    }
+
+    pub fn is_real(self) -> bool {
+        self.im == 0.0
+    }
+
+    /// Principal square root; `sqrt(-1)` is exactly `i`.
+    pub fn sqrt(self) -> Complex {
+        let r = self.abs();
+        let re = ((r + self.re) / 2.0).sqrt();
+        let im = ((r - self.re) / 2.0).sqrt();
+        Complex::new(re, if self.im < 0.0 { -im } else { im })
+    }
+
+    pub fn exp(self) -> Complex {
+        let scale = self.re.exp();
+        Complex::new(scale * self.im.cos(), scale * self.im.sin())
+    }
+
+    /// Principal natural logarithm; `ln(-1)` is `πi`.
+    pub fn ln(self) -> Complex {
+        Complex::new(self.abs().ln(), self.arg())
+    }
+
+    /// `self^exp`. Integer exponents multiply exactly, so `i^2` is `-1`
+    /// rather than `-1 + 1.2e-16i`; others go through `exp(exp * ln(self))`.
+    pub fn pow(self, exp: Complex) -> Complex {
+        if exp.is_real() && exp.re.fract() == 0.0 && exp.re.abs() <= i32::MAX as f64 {
+            let n = exp.re as i32;
+            let mut result = Complex::new(1.0, 0.0);
+            let mut base = self;
+            let mut k = n.unsigned_abs();
+            while k > 0 {
+                if k & 1 == 1 {
+                    result = result * base;
+                }
+                base = base * base;
+                k >>= 1;
+            }
+            return if n < 0 {
+                Complex::new(1.0, 0.0) / result
+            } else {
+                result
+            };
+        }
+        if self == Complex::default() {
+            return self;
+        }
+        (exp * self.ln()).exp()
+    }
+}
+
+impl From<f64> for Complex {
+    fn from(re: f64) -> Self {
+        Complex::new(re, 0.0)
+    }
+}
+
+impl Add for Complex {
+    type Output = Complex;
+    fn add(self, rhs: Complex) -> Complex {
+        Complex::new(self.re + rhs.re, self.im + rhs.im)
+    }
+}
+
+impl Sub for Complex {
+    type Output = Complex;
+    fn sub(self, rhs: Complex) -> Complex {
+        Complex::new(self.re - rhs.re, self.im - rhs.im)
+    }
+}
+
+impl Mul for Complex {
+    type Output = Complex;
+    fn mul(self, rhs: Complex) -> Complex {
+        Complex::new(
+            self.re * rhs.re - self.im * rhs.im,
+            self.re * rhs.im + self.im * rhs.re,
+        )
+    }
+}
+
+// IEEE semantics like `f64`: dividing by zero gives infinities or NaN. The
+// evaluator rejects zero divisors before getting here.
+impl Div for Complex {
+    type Output = Complex;
+    fn div(self, rhs: Complex) -> Complex {
+        let denom = rhs.re * rhs.re + rhs.im * rhs.im;
+        Complex::new(
+            (self.re * rhs.re + self.im * rhs.im) / denom,
+            (self.im * rhs.re - self.re * rhs.im) / denom,
+        )
+    }
+}
+
+// Negates as `0 - z`, so a zero part stays `+0.0`: `-1` must keep its
+// imaginary part on the upper side of the branch cut, where `ln(-1)` is `πi`.
+impl Neg for Complex {
+    type Output = Complex;
+    fn neg(self) -> Complex {
+        Complex::new(0.0 - self.re, 0.0 - self.im)
+    }
+}
+
+// `3`, `2i`, `-i`, `1 + 2i`, `1 - 0.5i`.
+impl fmt::Display for Complex {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let imaginary = |f: &mut fmt::Formatter<'_>, im: f64| match im {
+            1.0 => write!(f, "i"),
+            -1.0 => write!(f, "-i"),
+            _ => write!(f, "{im}i"),
+        };
+        match (self.re, self.im) {
+            (re, 0.0) => write!(f, "{re}"),
+            (0.0, im) => imaginary(f, im),
+            (re, im) if im < 0.0 => {
+                write!(f, "{re} - ")?;
+                imaginary(f, -im)
+            }
+            (re, im) => {
+                write!(f, "{re} + ")?;
+                imaginary(f, im)
+            }
+        }
+    }
+}
+
+const IMAGINARY_UNIT: &str = "i";
+
+// Evaluation over complex numbers. `i` is the imaginary unit; `sqrt`, `ln`,
+// `exp` and `abs` accept complex arguments, and every other builtin operator
+// and function still works when its operands are real.
+pub(crate) fn evaluate_complex(expr: &Expression) -> Result<Complex, CalcError> {
+    evaluate_exact(expr)
+}
+
+impl ExactValue for Complex {
+    fn number(n: f64) -> Result<Self, CalcError> {
+        Ok(Complex::from(n))
+    }
+
+    fn constant(_name: &str, value: f64) -> Result<Self, CalcError> {
+        Ok(Complex::from(value))
+    }
+
+    fn identifier(name: &str) -> Result<Self, CalcError> {
+        if builtins::normalize_name(name) == IMAGINARY_UNIT {
+            return Ok(Complex::I);
+        }
+        builtins::eval_constant(name)
+            .map(Complex::from)
+            .ok_or_else(|| CalcError::UnknownIdentifier(name.to_string()))
+    }
+
+    fn check_let(name: &str) -> Result<(), CalcError> {
+        if builtins::normalize_name(name) == IMAGINARY_UNIT {
+            return Err(CalcError::ReservedName(name.to_string()));
+        }
+        check_let_name(name)
+    }
+
+    fn unary(op: Operator, value: Self) -> Result<Self, CalcError> {
+        match op {
+            "+" => Ok(value),
+            "-" => Ok(-value),
+            "%" => Ok(value / Complex::from(100.0)),
+            _ => real_only(op, &[value], |args| builtins::eval_unary(op, args[0])),
+        }
+    }
+
+    fn infix(op: Operator, a: Self, b: Self) -> Result<Self, CalcError> {
+        infix(op, a, b)
+    }
+
+    fn call(name: &str, args: &[Self]) -> Result<Self, CalcError> {
+        call(name, args)
+    }
+}
+
+fn infix(op: Operator, a: Complex, b: Complex) -> Result<Complex, CalcError> {
+    match op {
+        "+" => Ok(a + b),
+        "-" => Ok(a - b),
+        "*" => Ok(a * b),
+        "/" if b == Complex::default() => Err(CalcError::DivideByZero),
+        "/" => Ok(a / b),
+        "^" => Ok(a.pow(b)),
+        _ => real_only(op, &[a, b], |args| {
+            builtins::eval_infix(op, args[0], args[1])
+        }),
+    }
+}
+
+fn call(name: &str, args: &[Complex]) -> Result<Complex, CalcError> {
+    let normalized = builtins::normalize_name(name);
+    let complex: Option<fn(Complex) -> Complex> = match normalized.as_str() {
+        "sqrt" => Some(Complex::sqrt),
+        "exp" => Some(Complex::exp),
+        "ln" => Some(Complex::ln),
+        "abs" => Some(|z: Complex| Complex::from(z.abs())),
+        _ => None,
+    };
+    match complex {
+        Some(f) => {
+            builtins::check_arity(name, 1, Some(1), args.len())?;
+            Ok(f(args[0]))
+        }
+        None => real_only(name, args, |args| builtins::eval_function(name, args)),
+    }
+}
+
+// Falls back to the real builtin when every operand is real.
+fn real_only(
+    name: &str,
+    args: &[Complex],
+    eval: impl FnOnce(&[f64]) -> Result<f64, CalcError>,
+) -> Result<Complex, CalcError> {
+    if !args.iter().all(|z| z.is_real()) {
+        return Err(CalcError::ComplexArgument(name.to_string()));
+    }
+    let real: Vec<f64> = args.iter().map(|z| z.re).collect();
+    eval(&real).map(Complex::from)
+}
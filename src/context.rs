@@ -0,0 +1,43 @@
+use alloc::collections::BTreeMap;
+use alloc::string::String;
+
+/// Variable bindings consulted when evaluating identifiers.
+///
+/// Variables shadow builtin constants: binding `e` makes `e` refer to the
+/// variable instead of Euler's number.
+#[derive(Debug, Clone, Default)]
+pub struct Context {
+    vars: BTreeMap<String, f64>,
+    step_budget: Option<u64>,
+}
+
+impl Context {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn set(&mut self, name: impl Into<String>, value: f64) {
+        self.vars.insert(name.into(), value);
+    }
+
+    pub fn get(&self, name: &str) -> Option<f64> {
+        self.vars.get(name).copied()
+    }
+
+    pub fn remove(&mut self, name: &str) -> Option<f64> {
+        self.vars.remove(name)
+    }
+
+    /// Caps how many expression nodes one evaluation against this context may
+    /// visit; past that it fails with `CalcError::BudgetExceeded`. Each term
+    /// of an iterated sum counts again, so `sum(k, 1, 1000, k^2)` takes about
+    /// 3000 steps. Unlike a timeout this is deterministic. `None`, the default,
+    /// is unlimited.
+    pub fn set_step_budget(&mut self, step_budget: Option<u64>) {
+        self.step_budget = step_budget;
+    }
+
+    pub fn step_budget(&self) -> Option<u64> {
+        self.step_budget
+    }
+}
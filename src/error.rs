@@ -1,41 +1,214 @@
+use crate::builtins;
 use crate::lexer::Token;
-use std::fmt;
+use alloc::format;
+use alloc::string::{String, ToString};
+use core::fmt;
+
+/// Broad category of a `CalcError`, for callers that only care where the
+/// input failed rather than which variant it was.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum ErrorKind {
+    /// The input could not be split into tokens.
+    Lex,
+    /// The tokens do not form a valid expression or statement.
+    Parse,
+    /// The input parsed but could not be evaluated.
+    Eval,
+}
 
 #[derive(Debug, PartialEq, Clone)]
 pub enum CalcError {
-    UnexpectedChar(char),
-    ExpectedToken { expected: Token, got: Token },
-    ExpectedPrimary(Token),
-    ExpectedNumber(Token),
-    ExpectedFractionDigits(Token),
-    UnexpectedTokenAfterExpression(Token),
+    // Lexer/parser errors carry `pos`, the char offset into the input.
+    UnexpectedChar { ch: char, pos: usize },
+    InvalidDigitSeparator { literal: String, pos: usize },
+    NumberOverflow { literal: String, pos: usize },
+    ExpectedToken { expected: Token, got: Token, pos: usize },
+    ExpectedPrimary { got: Token, pos: usize },
+    ExpectedNumber { got: Token, pos: usize },
+    UnexpectedTokenAfterExpression { got: Token, pos: usize },
+    // `pos` is the `(` that is never closed.
+    UnclosedParen { pos: usize },
+    UnexpectedCloseParen { pos: usize },
+    NestingTooDeep { max_depth: usize, pos: usize },
+    EmptyInput,
     UnknownIdentifier(String),
     UnknownFunction(String),
     WrongArity { name: String, expected: usize, got: usize },
     DivideByZero,
+    NonInteger,
+    NonIntegerOperand(String),
+    IntegerOverflow,
+    NonFinite,
+    DomainError { name: String, arg: f64 },
+    ReservedName(String),
+    StackUnderflow(String),
+    LeftoverOperands(usize),
+    ComplexArgument(String),
+    NotRational(String),
+    BudgetExceeded,
+    NoRpnForm(String),
 }
 
 impl fmt::Display for CalcError {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         match self {
-            CalcError::UnexpectedChar(ch) => write!(f, "unexpected character: {ch}"),
-            CalcError::ExpectedToken { expected, got } => {
-                write!(f, "expected token {expected:?}, got {got:?}")
-            }
-            CalcError::ExpectedPrimary(got) => write!(f, "expected expression, got {got:?}"),
-            CalcError::ExpectedNumber(got) => write!(f, "expected number, got {got:?}"),
-            CalcError::ExpectedFractionDigits(got) => write!(f, "expected digits after '.', got {got:?}"),
-            CalcError::UnexpectedTokenAfterExpression(got) => {
-                write!(f, "unexpected token after expression: {got:?}")
-            }
-            CalcError::UnknownIdentifier(name) => write!(f, "unknown identifier: {name}"),
-            CalcError::UnknownFunction(name) => write!(f, "unknown function: {name}"),
+            CalcError::UnexpectedChar { ch, .. } => write!(f, "unexpected character: {ch}"),
+            CalcError::InvalidDigitSeparator { literal, .. } => {
+                write!(f, "misplaced digit separator '_' in {literal}")
+            }
+            CalcError::NumberOverflow { literal, .. } => write!(f, "number too large: {literal}"),
+            CalcError::ExpectedToken { expected, got, .. } => {
+                write!(f, "expected {}, got {}", quoted(expected), quoted(got))
+            }
+            CalcError::ExpectedPrimary { got, .. } => {
+                write!(f, "expected expression, got {}", quoted(got))
+            }
+            CalcError::ExpectedNumber { got, .. } => {
+                write!(f, "expected number, got {}", quoted(got))
+            }
+            CalcError::UnexpectedTokenAfterExpression { got, .. } => {
+                write!(f, "unexpected {} after expression", quoted(got))
+            }
+            CalcError::UnclosedParen { .. } => write!(f, "unclosed '(': missing ')'"),
+            CalcError::UnexpectedCloseParen { .. } => write!(f, "unmatched ')'"),
+            CalcError::NestingTooDeep { max_depth, .. } => {
+                write!(f, "expression nested too deeply (limit {max_depth})")
+            }
+            CalcError::EmptyInput => write!(f, "empty input: enter an expression"),
+            CalcError::UnknownIdentifier(name) => {
+                write!(f, "unknown identifier: {name}")?;
+                write_suggestion(f, self.suggestion())
+            }
+            CalcError::UnknownFunction(name) => {
+                write!(f, "unknown function: {name}")?;
+                write_suggestion(f, self.suggestion())
+            }
             CalcError::WrongArity { name, expected, got } => {
                 write!(f, "wrong number of arguments for {name}: expected {expected}, got {got}")
             }
             CalcError::DivideByZero => write!(f, "division by zero"),
+            CalcError::NonInteger => write!(f, "result is not an integer"),
+            CalcError::NonIntegerOperand(op) => write!(f, "operator {op} needs integer operands"),
+            CalcError::IntegerOverflow => write!(f, "integer overflow"),
+            CalcError::NonFinite => write!(f, "result is not a finite number"),
+            CalcError::DomainError { name, arg } => {
+                write!(f, "argument out of domain for {name}: {arg}")
+            }
+            CalcError::ReservedName(name) => write!(f, "cannot assign to reserved name: {name}"),
+            CalcError::StackUnderflow(name) => write!(f, "not enough operands for {name}"),
+            CalcError::LeftoverOperands(count) => {
+                write!(f, "{count} operand(s) left over without an operator")
+            }
+            CalcError::ComplexArgument(name) => write!(f, "{name} needs real arguments"),
+            CalcError::NotRational(name) => write!(f, "{name} has no exact rational result"),
+            CalcError::BudgetExceeded => write!(f, "evaluation step budget exceeded"),
+            CalcError::NoRpnForm(name) => write!(f, "{name} cannot be written in RPN"),
+        }
+    }
+}
+
+fn write_suggestion(f: &mut fmt::Formatter<'_>, suggestion: Option<&str>) -> fmt::Result {
+    match suggestion {
+        Some(name) => write!(f, " (did you mean '{name}'?)"),
+        None => Ok(()),
+    }
+}
+
+fn quoted(token: &Token) -> String {
+    if token.is_symbol() {
+        format!("'{token}'")
+    } else {
+        token.to_string()
+    }
+}
+
+impl CalcError {
+    /// Char offset into the input for lexer/parser errors; `None` for errors
+    /// raised during evaluation.
+    pub fn position(&self) -> Option<usize> {
+        match self {
+            CalcError::UnexpectedChar { pos, .. }
+            | CalcError::InvalidDigitSeparator { pos, .. }
+            | CalcError::NumberOverflow { pos, .. }
+            | CalcError::ExpectedToken { pos, .. }
+            | CalcError::ExpectedPrimary { pos, .. }
+            | CalcError::ExpectedNumber { pos, .. }
+            | CalcError::UnexpectedTokenAfterExpression { pos, .. }
+            | CalcError::UnclosedParen { pos }
+            | CalcError::UnexpectedCloseParen { pos }
+            | CalcError::NestingTooDeep { pos, .. } => Some(*pos),
+            _ => None,
+        }
+    }
+
+    /// The error's category. Lex and parse errors carry a `position`, except
+    /// `EmptyInput`, which has no offending column.
+    pub fn kind(&self) -> ErrorKind {
+        match self {
+            CalcError::UnexpectedChar { .. }
+            | CalcError::InvalidDigitSeparator { .. }
+            | CalcError::NumberOverflow { .. } => ErrorKind::Lex,
+            CalcError::ExpectedToken { .. }
+            | CalcError::ExpectedPrimary { .. }
+            | CalcError::ExpectedNumber { .. }
+            | CalcError::UnexpectedTokenAfterExpression { .. }
+            | CalcError::UnclosedParen { .. }
+            | CalcError::UnexpectedCloseParen { .. }
+            | CalcError::NestingTooDeep { .. }
+            | CalcError::EmptyInput => ErrorKind::Parse,
+            CalcError::UnknownIdentifier(_)
+            | CalcError::UnknownFunction(_)
+            | CalcError::WrongArity { .. }
+            | CalcError::DivideByZero
+            | CalcError::NonInteger
+            | CalcError::NonIntegerOperand(_)
+            | CalcError::IntegerOverflow
+            | CalcError::NonFinite
+            | CalcError::DomainError { .. }
+            | CalcError::ReservedName(_)
+            | CalcError::StackUnderflow(_)
+            | CalcError::LeftoverOperands(_)
+            | CalcError::ComplexArgument(_)
+            | CalcError::NotRational(_)
+            | CalcError::BudgetExceeded
+            | CalcError::NoRpnForm(_) => ErrorKind::Eval,
+        }
+    }
+
+    /// For an unknown function or identifier, the builtin name it most likely
+    /// misspells: `sqrrt` suggests `sqrt` and `pii` suggests `pi`. Names
+    /// registered on a `Calculator` are not considered.
+    pub fn suggestion(&self) -> Option<&'static str> {
+        match self {
+            CalcError::UnknownFunction(name) => builtins::suggest_function(name),
+            CalcError::UnknownIdentifier(name) => builtins::suggest_constant(name),
+            _ => None,
+        }
+    }
+
+    /// Whether the input failed to lex or parse; everything else is raised
+    /// while evaluating.
+    pub fn is_parse_error(&self) -> bool {
+        self.kind() != ErrorKind::Eval
+    }
+
+    pub fn is_eval_error(&self) -> bool {
+        !self.is_parse_error()
+    }
+
+    /// Renders the error under `input` with a caret at the offending column:
+    ///
+    /// ```text
+    /// 1 + @
+    ///     ^ unexpected character: @
+    /// ```
+    pub fn render(&self, input: &str) -> String {
+        match self.position() {
+            Some(pos) => format!("{input}\n{}^ {self}", " ".repeat(pos)),
+            None => self.to_string(),
         }
     }
 }
 
-impl std::error::Error for CalcError {}
+impl core::error::Error for CalcError {}
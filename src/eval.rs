@@ -1,28 +1,616 @@
+use alloc::collections::BTreeSet;
+use alloc::string::{String, ToString};
+use alloc::vec;
+use alloc::vec::Vec;
+use core::cell::Cell;
+
+use crate::builtins::{self, Operator};
+use crate::calculator::{CaretMode, DivByZero};
+use crate::context::Context;
 use crate::error::CalcError;
-use crate::parser::Expression;
-use crate::builtins;
+#[cfg(not(feature = "std"))]
+use crate::math::FloatExt;
+use crate::parser::{Expression, Statement};
+
+pub(crate) type FunctionLookup<'a> = &'a dyn Fn(&str, &[f64]) -> Result<f64, CalcError>;
+pub(crate) type ConstantLookup<'a> = &'a dyn Fn(&str) -> Option<f64>;
+
+pub(crate) struct Evaluator<'a> {
+    pub(crate) call_function: FunctionLookup<'a>,
+    pub(crate) constant: ConstantLookup<'a>,
+    pub(crate) div_by_zero: DivByZero,
+    pub(crate) require_finite: bool,
+    pub(crate) caret_mode: CaretMode,
+}
+
+// Settings used by the free functions (`rustcalc::eval`, ...).
+const BUILTIN_EVALUATOR: Evaluator<'static> = Evaluator {
+    call_function: &builtins::eval_function,
+    constant: &builtins::eval_constant,
+    div_by_zero: DivByZero::Error,
+    require_finite: false,
+    caret_mode: CaretMode::Power,
+};
+
+// Pending work for the iterative evaluator. Operands are evaluated left to
+// right before their operator is applied, matching a recursive walk.
+enum Step<'e> {
+    Eval(&'e Expression),
+    Unary(Operator),
+    Infix(Operator),
+    RelativePercent(Operator),
+    Call(&'e str, usize),
+    Branch(&'e Expression, &'e Expression),
+    Let(&'e str, &'e Expression),
+    Series {
+        name: &'e str,
+        var: &'e str,
+        body: &'e Expression,
+    },
+}
+
+// `if(cond, then, else)` is evaluated lazily: only the chosen branch runs, so
+// `if(x, 1/x, 0)` is safe for `x == 0`. It is resolved here, before the
+// function registry, because registered functions receive evaluated arguments.
+const IF_FUNCTION: &str = "if";
+
+// Iterated sums and products: `sum(i, 1, 4, i^2)` evaluates the body once per
+// integer `i` from 1 to 4, with `i` bound in a copy of the context. This is
+// the four-argument form whose first argument is a bare name; any other call
+// is the variadic builtin, so `sum(x, 1, 2, 3)` iterates over `x` rather than
+// adding it (write `x + sum(1, 2, 3)`).
+const SUM_FUNCTION: &str = "sum";
+const PRODUCT_FUNCTIONS: &[&str] = &["product", "prod"];
+
+// Bounds the work of one iterated sum or product.
+const MAX_SERIES_TERMS: f64 = 1e6;
+
+// Above 2^53 consecutive integers are no longer distinct `f64`s, so an index
+// there could not step through its range.
+const MAX_SERIES_BOUND: f64 = 9_007_199_254_740_992.0;
+
+fn series_form<'e>(name: &str, args: &'e [Expression]) -> Option<(&'e str, &'e Expression)> {
+    let normalized = builtins::normalize_name(name);
+    if normalized != SUM_FUNCTION && !PRODUCT_FUNCTIONS.contains(&normalized.as_str()) {
+        return None;
+    }
+    match args {
+        [
+            Expression::Identifier(var) | Expression::Constant { name: var, .. },
+            _,
+            _,
+            body,
+        ] => Some((var, body)),
+        _ => None,
+    }
+}
+
+// Postfix percent is relative to the left operand of `+` and `-`: `200 + 10%`
+// is `200 + 200*0.1`, i.e. 220, and `200 - 10%` is 180. Only a percentage that
+// is the direct right operand counts; `200 + (10%)` and `200 + 2*10%` add
+// plain fractions. Everywhere else `x%` is `x/100`.
+pub(crate) const PERCENT: Operator = "%";
+
+const POWER: Operator = "^";
+const BIT_XOR: Operator = "~^";
+
+pub(crate) fn relative_percent(op: Operator, right: &Expression) -> Option<&Expression> {
+    match right {
+        Expression::UnaryOp { op: PERCENT, expr } if matches!(op, "+" | "-") => Some(expr),
+        _ => None,
+    }
+}
+
+pub(crate) fn eval_relative_percent(
+    op: Operator,
+    base: f64,
+    percent: f64,
+) -> Result<f64, CalcError> {
+    let share = base * builtins::eval_unary(PERCENT, percent)?;
+    builtins::eval_infix(op, base, share)
+}
+
+impl Evaluator<'_> {
+    pub(crate) fn eval(&self, expr: &Expression, ctx: &Context) -> Result<f64, CalcError> {
+        self.eval_counted(expr, ctx, &Cell::new(ctx.step_budget()))
+    }
+
+    // Uses explicit stacks instead of recursion so deeply nested input cannot
+    // overflow the call stack. `budget` is the steps left, shared with the
+    // nested evaluations of `let` bodies and sum terms.
+    fn eval_counted(
+        &self,
+        expr: &Expression,
+        ctx: &Context,
+        budget: &Cell<Option<u64>>,
+    ) -> Result<f64, CalcError> {
+        let mut steps = vec![Step::Eval(expr)];
+        let mut values: Vec<f64> = Vec::new();
+
+        while let Some(step) = steps.pop() {
+            if let (Step::Eval(_), Some(left)) = (&step, budget.get()) {
+                let left = left.checked_sub(1).ok_or(CalcError::BudgetExceeded)?;
+                budget.set(Some(left));
+            }
+            match step {
+                Step::Eval(expr) => match expr {
+                    Expression::Number(n) => values.push(self.finite(*n)?),
+                    // The parsed value is only the builtin one; a variable or a
+                    // constant registered on the calculator takes precedence.
+                    Expression::Constant { name, value } => {
+                        let value = ctx
+                            .get(name)
+                            .or_else(|| (self.constant)(name))
+                            .unwrap_or(*value);
+                        values.push(self.finite(value)?);
+                    }
+                    Expression::Identifier(name) => {
+                        let value = ctx
+                            .get(name)
+                            .or_else(|| (self.constant)(name))
+                            .ok_or_else(|| CalcError::UnknownIdentifier(name.clone()))?;
+                        values.push(self.finite(value)?);
+                    }
+                    Expression::UnaryOp { op, expr } => {
+                        steps.push(Step::Unary(op));
+                        steps.push(Step::Eval(expr));
+                    }
+                    Expression::BinaryOp { op, left, right }
+                        if let Some(percent) = relative_percent(op, right) =>
+                    {
+                        steps.push(Step::RelativePercent(op));
+                        steps.push(Step::Eval(percent));
+                        steps.push(Step::Eval(left));
+                    }
+                    Expression::BinaryOp { op, left, right } => {
+                        steps.push(Step::Infix(op));
+                        steps.push(Step::Eval(right));
+                        steps.push(Step::Eval(left));
+                    }
+                    Expression::FunctionCall { name, args }
+                        if builtins::normalize_name(name) == IF_FUNCTION =>
+                    {
+                        builtins::check_arity(name, 3, Some(3), args.len())?;
+                        steps.push(Step::Branch(&args[1], &args[2]));
+                        steps.push(Step::Eval(&args[0]));
+                    }
+                    Expression::FunctionCall { name, args }
+                        if let Some((var, body)) = series_form(name, args) =>
+                    {
+                        steps.push(Step::Series { name, var, body });
+                        steps.push(Step::Eval(&args[2]));
+                        steps.push(Step::Eval(&args[1]));
+                    }
+                    Expression::FunctionCall { name, args } => {
+                        steps.push(Step::Call(name, args.len()));
+                        steps.extend(args.iter().rev().map(Step::Eval));
+                    }
+                    Expression::Parenthesis(inner) => steps.push(Step::Eval(inner)),
+                    Expression::LetBinding { name, value, body } => {
+                        steps.push(Step::Let(name, body));
+                        steps.push(Step::Eval(value));
+                    }
+                },
+                Step::Unary(op) => {
+                    let value = values.pop().expect("operand evaluated");
+                    values.push(self.finite(builtins::eval_unary(op, value)?)?);
+                }
+                Step::Infix(op) => {
+                    let b = values.pop().expect("right operand evaluated");
+                    let a = values.pop().expect("left operand evaluated");
+                    let value = match self.eval_infix(op, a, b) {
+                        Err(CalcError::DivideByZero) if self.div_by_zero == DivByZero::Infinity => {
+                            ieee_division(op, a, b)
+                        }
+                        result => result?,
+                    };
+                    values.push(self.finite(value)?);
+                }
+                Step::RelativePercent(op) => {
+                    let percent = values.pop().expect("percentage evaluated");
+                    let base = values.pop().expect("left operand evaluated");
+                    values.push(self.finite(eval_relative_percent(op, base, percent)?)?);
+                }
+                Step::Branch(then, otherwise) => {
+                    let cond = values.pop().expect("condition evaluated");
+                    steps.push(Step::Eval(if cond != 0.0 { then } else { otherwise }));
+                }
+                // The body runs as a separate evaluation against a copy of the
+                // context; nested `let`s recurse, bounded by the parser's depth
+                // limit like nested sums.
+                Step::Let(name, body) => {
+                    self.check_binding(name)?;
+                    let value = values.pop().expect("bound value evaluated");
+                    let mut child = ctx.clone();
+                    child.set(name, value);
+                    values.push(self.eval_counted(body, &child, budget)?);
+                }
+                Step::Series { name, var, body } => {
+                    let hi = values.pop().expect("upper bound evaluated");
+                    let lo = values.pop().expect("lower bound evaluated");
+                    let total = self.series(name, var, body, (lo, hi), ctx, budget)?;
+                    values.push(self.finite(total)?);
+                }
+                Step::Call(name, argc) => {
+                    let args = values.split_off(values.len() - argc);
+                    values.push(self.finite((self.call_function)(name, &args)?)?);
+                }
+            }
+        }
+
+        Ok(values.pop().expect("expression produced a value"))
+    }
+
+    // Each term is a separate evaluation, so nested sums recurse once per
+    // level; the parser's depth limit bounds that.
+    fn series(
+        &self,
+        name: &str,
+        var: &str,
+        body: &Expression,
+        (lo, hi): (f64, f64),
+        ctx: &Context,
+        budget: &Cell<Option<u64>>,
+    ) -> Result<f64, CalcError> {
+        self.check_binding(var)?;
+        for bound in [lo, hi] {
+            if !bound.is_finite() || bound.fract() != 0.0 || bound.abs() > MAX_SERIES_BOUND {
+                return Err(CalcError::DomainError {
+                    name: name.to_string(),
+                    arg: bound,
+                });
+            }
+        }
+        if hi - lo >= MAX_SERIES_TERMS {
+            return Err(CalcError::DomainError {
+                name: name.to_string(),
+                arg: hi,
+            });
+        }
+        let product = builtins::normalize_name(name) != SUM_FUNCTION;
+        let mut child = ctx.clone();
+        let mut total = if product { 1.0 } else { 0.0 };
+        // An empty range (`lo > hi`) gives the empty sum 0 or product 1.
+        if lo > hi {
+            return Ok(total);
+        }
+        // Counting in integers guarantees the loop ends whatever the bounds.
+        let terms = (hi - lo) as u64;
+        for step in 0..=terms {
+            child.set(var, lo + step as f64);
+            let term = self.eval_counted(body, &child, budget)?;
+            total = if product { total * term } else { total + term };
+        }
+        Ok(total)
+    }
+
+    // The index of a sum is bound like a `let` name, so neither may shadow a
+    // constant.
+    fn check_binding(&self, name: &str) -> Result<(), CalcError> {
+        if (self.constant)(name).is_some() {
+            return Err(CalcError::ReservedName(name.to_string()));
+        }
+        Ok(())
+    }
+
+    fn eval_infix(&self, op: Operator, a: f64, b: f64) -> Result<f64, CalcError> {
+        if op != POWER || self.caret_mode == CaretMode::Power {
+            return builtins::eval_infix(op, a, b);
+        }
+        // Errors name the `^` the user wrote, not the `~^` behind it.
+        builtins::eval_infix(BIT_XOR, a, b).map_err(|err| match err {
+            CalcError::NonIntegerOperand(_) => CalcError::NonIntegerOperand(op.to_string()),
+            err => err,
+        })
+    }
+
+    // Every intermediate value passes through here, so with `require_finite`
+    // a NaN or infinity is caught where it first appears.
+    fn finite(&self, value: f64) -> Result<f64, CalcError> {
+        if self.require_finite && !value.is_finite() {
+            return Err(CalcError::NonFinite);
+        }
+        Ok(value)
+    }
+}
+
+// `/` and `%` by zero under `DivByZero::Infinity`: `1/0` is `inf`, `0/0` and
+// `x % 0` are NaN.
+fn ieee_division(op: Operator, a: f64, b: f64) -> f64 {
+    if op == "%" { a % b } else { a / b }
+}
+
+// A `let` cannot shadow a builtin constant, just as `=` cannot assign to one.
+pub(crate) fn check_let_name(name: &str) -> Result<(), CalcError> {
+    if builtins::eval_constant(name).is_some() {
+        return Err(CalcError::ReservedName(name.to_string()));
+    }
+    Ok(())
+}
+
+// The values bound by enclosing `let`s in `evaluate_exact`, innermost last.
+// Looking names up here instead of substituting each value into its body
+// keeps a chain of `let`s linear; substitution copies it exponentially.
+type Scope<'e, T> = Vec<(&'e str, T)>;
+
+fn lookup<'s, T>(scope: &'s [(&str, T)], name: &str) -> Option<&'s T> {
+    scope
+        .iter()
+        .rev()
+        .find(|(bound, _)| *bound == name)
+        .map(|(_, value)| value)
+}
+
+// The arithmetic of one exact evaluator. `evaluate_exact` walks the tree and
+// handles everything else: parentheses, `let` scopes and relative percents.
+pub(crate) trait ExactValue: Clone {
+    fn number(n: f64) -> Result<Self, CalcError>;
+    fn constant(name: &str, value: f64) -> Result<Self, CalcError>;
+    fn unary(op: Operator, value: Self) -> Result<Self, CalcError>;
+    fn infix(op: Operator, a: Self, b: Self) -> Result<Self, CalcError>;
+    fn call(name: &str, args: &[Self]) -> Result<Self, CalcError>;
+
+    // A name bound by no `let`: a builtin constant, if anything.
+    fn identifier(name: &str) -> Result<Self, CalcError> {
+        match builtins::eval_constant(name) {
+            Some(value) => Self::constant(name, value),
+            None => Err(CalcError::UnknownIdentifier(name.to_string())),
+        }
+    }
+
+    fn check_let(name: &str) -> Result<(), CalcError> {
+        check_let_name(name)
+    }
+}
+
+// Pending work for `evaluate_exact`, in the same order as `Step`. `Bind` and
+// `Unbind` bracket the body of a `let`.
+enum ExactStep<'e> {
+    Eval(&'e Expression),
+    Unary(Operator),
+    Infix(Operator),
+    RelativePercent(Operator),
+    Call(&'e str, usize),
+    Bind(&'e str),
+    Unbind,
+}
+
+// Like `Evaluator::eval`, iterative so that long operator chains cannot
+// overflow the stack.
+pub(crate) fn evaluate_exact<T: ExactValue>(expr: &Expression) -> Result<T, CalcError> {
+    let mut steps = vec![ExactStep::Eval(expr)];
+    let mut values: Vec<T> = Vec::new();
+    let mut scope: Scope<'_, T> = Vec::new();
+    while let Some(step) = steps.pop() {
+        match step {
+            ExactStep::Eval(expr) => match expr {
+                Expression::Number(n) => values.push(T::number(*n)?),
+                Expression::Constant { name, value } => values.push(T::constant(name, *value)?),
+                Expression::Identifier(name) => match lookup(&scope, name) {
+                    Some(value) => values.push(value.clone()),
+                    None => values.push(T::identifier(name)?),
+                },
+                Expression::Parenthesis(inner) => steps.push(ExactStep::Eval(inner)),
+                Expression::LetBinding { name, value, body } => {
+                    T::check_let(name)?;
+                    steps.extend([
+                        ExactStep::Unbind,
+                        ExactStep::Eval(body),
+                        ExactStep::Bind(name),
+                        ExactStep::Eval(value),
+                    ]);
+                }
+                Expression::UnaryOp { op, expr } => {
+                    steps.push(ExactStep::Unary(op));
+                    steps.push(ExactStep::Eval(expr));
+                }
+                Expression::BinaryOp { op, left, right }
+                    if let Some(percent) = relative_percent(op, right) =>
+                {
+                    steps.push(ExactStep::RelativePercent(op));
+                    steps.push(ExactStep::Eval(percent));
+                    steps.push(ExactStep::Eval(left));
+                }
+                Expression::BinaryOp { op, left, right } => {
+                    steps.push(ExactStep::Infix(op));
+                    steps.push(ExactStep::Eval(right));
+                    steps.push(ExactStep::Eval(left));
+                }
+                Expression::FunctionCall { name, args } => {
+                    steps.push(ExactStep::Call(name, args.len()));
+                    steps.extend(args.iter().rev().map(ExactStep::Eval));
+                }
+            },
+            ExactStep::Unary(op) => {
+                let value = values.pop().expect("operand evaluated");
+                values.push(T::unary(op, value)?);
+            }
+            ExactStep::Infix(op) => {
+                let b = values.pop().expect("right operand evaluated");
+                let a = values.pop().expect("left operand evaluated");
+                values.push(T::infix(op, a, b)?);
+            }
+            ExactStep::RelativePercent(op) => {
+                let percent = values.pop().expect("percentage evaluated");
+                let base = values.pop().expect("left operand evaluated");
+                let share = T::infix("*", base.clone(), percent)?;
+                let share = T::infix("/", share, T::number(100.0)?)?;
+                values.push(T::infix(op, base, share)?);
+            }
+            ExactStep::Call(name, argc) => {
+                let args = values.split_off(values.len() - argc);
+                values.push(T::call(name, &args)?);
+            }
+            ExactStep::Bind(name) => {
+                let value = values.pop().expect("bound value evaluated");
+                scope.push((name, value));
+            }
+            ExactStep::Unbind => {
+                scope.pop();
+            }
+        }
+    }
+    Ok(values.pop().expect("expression produced a value"))
+}
+
+// One step of `visit_scoped`. `Bind` and `Unbind` bracket the body of a `let`
+// or an iterated sum, where its name is bound.
+enum Scan<'e> {
+    Visit(&'e Expression),
+    Bind(&'e str),
+    Unbind,
+}
+
+// Calls `f` on every node, left to right in evaluation order, with the names
+// bound by enclosing `let`s and sums. The index argument of a sum is a binding,
+// not a use, so it is skipped.
+fn visit_scoped<'e>(
+    expr: &'e Expression,
+    mut f: impl FnMut(&'e Expression, &[&'e str]) -> Result<(), CalcError>,
+) -> Result<(), CalcError> {
+    let mut bound: Vec<&str> = Vec::new();
+    let mut pending = vec![Scan::Visit(expr)];
+    while let Some(scan) = pending.pop() {
+        let expr = match scan {
+            Scan::Visit(expr) => expr,
+            Scan::Bind(name) => {
+                bound.push(name);
+                continue;
+            }
+            Scan::Unbind => {
+                bound.pop();
+                continue;
+            }
+        };
+        f(expr, &bound)?;
+        match expr {
+            Expression::FunctionCall { name, args }
+                if let Some((var, body)) = series_form(name, args) =>
+            {
+                pending.extend([
+                    Scan::Unbind,
+                    Scan::Visit(body),
+                    Scan::Bind(var),
+                    Scan::Visit(&args[2]),
+                    Scan::Visit(&args[1]),
+                ]);
+            }
+            Expression::LetBinding { name, value, body } => {
+                pending.extend([
+                    Scan::Unbind,
+                    Scan::Visit(body),
+                    Scan::Bind(name),
+                    Scan::Visit(value),
+                ]);
+            }
+            _ => pending.extend(expr.children().into_iter().rev().map(Scan::Visit)),
+        }
+    }
+    Ok(())
+}
 
-pub(crate) fn evaluate_expression(expr: &Expression) -> Result<f64, CalcError> {
-    match expr {
-        Expression::Number(n) => Ok(*n),
-        Expression::Identifier(name) => builtins::eval_constant(name)
-            .ok_or_else(|| CalcError::UnknownIdentifier(name.clone())),
-        Expression::UnaryOp { op, expr } => {
-            let value = evaluate_expression(expr)?;
-            builtins::eval_prefix(*op, value)
+// Checks names without evaluating anything and stops at the first unknown one.
+pub(crate) fn validate(expr: &Expression, known_vars: &BTreeSet<String>) -> Result<(), CalcError> {
+    visit_scoped(expr, |node, bound| match node {
+        Expression::Identifier(name)
+            if !bound.contains(&name.as_str())
+                && !known_vars.contains(name)
+                && builtins::eval_constant(name).is_none() =>
+        {
+            Err(CalcError::UnknownIdentifier(name.clone()))
         }
-        Expression::BinaryOp { op, left, right } => {
-            let a = evaluate_expression(left)?;
-            let b = evaluate_expression(right)?;
-            builtins::eval_infix(*op, a, b)
+        Expression::FunctionCall { name, .. }
+            if builtins::normalize_name(name) != IF_FUNCTION
+                && builtins::function_arity(name).is_none() =>
+        {
+            Err(CalcError::UnknownFunction(name.clone()))
         }
-        Expression::FunctionCall { name, args } => {
-            let mut values = Vec::with_capacity(args.len());
-            for arg in args {
-                values.push(evaluate_expression(arg)?);
+        _ => Ok(()),
+    })
+}
+
+pub(crate) fn evaluate_expression(expr: &Expression, ctx: &Context) -> Result<f64, CalcError> {
+    BUILTIN_EVALUATOR.eval(expr, ctx)
+}
+
+impl Expression {
+    /// Evaluates with the builtin functions and constants.
+    ///
+    /// ```
+    /// let expr = rustcalc::parse("3*4").unwrap();
+    /// assert_eq!(expr.evaluate().unwrap(), 12.0);
+    /// ```
+    pub fn evaluate(&self) -> Result<f64, CalcError> {
+        evaluate_expression(self, &Context::new())
+    }
+
+    /// Like `evaluate`, with variables looked up in `ctx` first.
+    pub fn evaluate_with(&self, ctx: &Context) -> Result<f64, CalcError> {
+        evaluate_expression(self, ctx)
+    }
+
+    /// The variables a caller has to supply: every identifier that is neither
+    /// a builtin constant nor bound by an enclosing `let` or iterated `sum`.
+    ///
+    /// ```
+    /// let expr = rustcalc::parse("let r = 1 in r * x + pi").unwrap();
+    /// assert_eq!(expr.free_variables(), ["x".to_string()].into());
+    /// ```
+    pub fn free_variables(&self) -> BTreeSet<String> {
+        let mut names = BTreeSet::new();
+        let _ = visit_scoped(self, |node, bound| {
+            if let Expression::Identifier(name) = node
+                && !bound.contains(&name.as_str())
+                && builtins::eval_constant(name).is_none()
+            {
+                names.insert(name.clone());
             }
-            builtins::eval_function(name, &values)
+            Ok(())
+        });
+        names
+    }
+}
+
+pub(crate) fn evaluate_statement(
+    stmt: &Statement,
+    ctx: &mut Context,
+    evaluator: &Evaluator<'_>,
+) -> Result<f64, CalcError> {
+    match stmt {
+        Statement::Assignment { name, expr } => {
+            if (evaluator.constant)(name).is_some() {
+                return Err(CalcError::ReservedName(name.clone()));
+            }
+            let value = evaluator.eval(expr, ctx)?;
+            ctx.set(name.clone(), value);
+            Ok(value)
         }
-        Expression::Parenthesis(inner) => evaluate_expression(inner),
+        Statement::Expression(expr) => evaluator.eval(expr, ctx),
     }
 }
+
+pub(crate) fn evaluate_program(
+    statements: &[Statement],
+    ctx: &mut Context,
+    evaluator: &Evaluator<'_>,
+) -> Result<f64, CalcError> {
+    let mut last = Err(CalcError::EmptyInput);
+    for stmt in statements {
+        last = Ok(evaluate_statement(stmt, ctx, evaluator)?);
+    }
+    last
+}
+
+pub(crate) fn evaluate_builtin_statement(
+    stmt: &Statement,
+    ctx: &mut Context,
+) -> Result<f64, CalcError> {
+    evaluate_statement(stmt, ctx, &BUILTIN_EVALUATOR)
+}
+
+pub(crate) fn evaluate_builtin_program(
+    statements: &[Statement],
+    ctx: &mut Context,
+) -> Result<f64, CalcError> {
+    evaluate_program(statements, ctx, &BUILTIN_EVALUATOR)
+}
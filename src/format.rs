@@ -0,0 +1,709 @@
+use alloc::format;
+use alloc::string::{String, ToString};
+use alloc::vec;
+use alloc::vec::Vec;
+use core::fmt::{self, Write};
+
+use crate::builtins::{self, Operator};
+use crate::parser::{Expression, Statement};
+#[cfg(not(feature = "std"))]
+use crate::math::FloatExt;
+
+// Parentheses are only inserted where the operator tables require them, so a
+// parsed expression prints back to text that re-parses to the same tree.
+
+fn infix_bp(op: Operator) -> (u8, u8) {
+    builtins::infix_binding_power(op).unwrap_or((0, 0))
+}
+
+fn prefix_bp(op: Operator) -> u8 {
+    builtins::prefix_binding_power(op).unwrap_or(0)
+}
+
+fn is_postfix(op: Operator) -> bool {
+    builtins::postfix_binding_power(op).is_some()
+}
+
+// A child printed after an operator with binding power `min_bp` stays intact
+// only if its top-level operator binds at least as tightly.
+fn needs_parens_after(child: &Expression, min_bp: u8) -> bool {
+    match child {
+        Expression::BinaryOp { op, .. } => infix_bp(op).0 < min_bp,
+        _ => false,
+    }
+}
+
+// Lowest binding power still "open" at the right edge of `expr` when printed;
+// a following infix operator with `l_bp >= this` would be absorbed into it.
+fn open_right_bp(expr: &Expression) -> u8 {
+    let (bp, tail) = match expr {
+        Expression::BinaryOp { op, right, .. } => (infix_bp(op).1, right),
+        Expression::UnaryOp { op, .. } if is_postfix(op) => return u8::MAX,
+        Expression::UnaryOp { op, expr } => (prefix_bp(op), expr),
+        // A negative literal (e.g. from constant folding) prints like prefix minus.
+        Expression::Number(n) if n.is_sign_negative() => return prefix_bp("-"),
+        // A `let` body extends as far as it can.
+        Expression::LetBinding { .. } => return 0,
+        _ => return u8::MAX,
+    };
+    if needs_parens_after(tail, bp) {
+        bp
+    } else {
+        bp.min(open_right_bp(tail))
+    }
+}
+
+// Postfix operators bind tightest, so only atoms, calls and other postfix
+// operations can precede one unparenthesized.
+fn needs_parens_before_postfix(expr: &Expression) -> bool {
+    match expr {
+        Expression::BinaryOp { .. } | Expression::LetBinding { .. } => true,
+        Expression::UnaryOp { op, .. } => !is_postfix(op),
+        Expression::Number(n) => n.is_sign_negative(),
+        _ => false,
+    }
+}
+
+// Whether `expr` prints with a sign that could also be infix (`-3`, `+x`) at
+// its left edge. After an operator that is also postfix (`5 % -3`) the sign
+// would re-parse as infix, so the caller wraps it in parentheses.
+fn starts_with_sign(mut expr: &Expression) -> bool {
+    loop {
+        expr = match expr {
+            Expression::UnaryOp { op, expr } if is_postfix(op) => {
+                if needs_parens_before_postfix(expr) {
+                    return false;
+                }
+                expr
+            }
+            Expression::UnaryOp { op, .. } => return builtins::infix_binding_power(op).is_some(),
+            Expression::Number(n) => return n.is_sign_negative(),
+            Expression::BinaryOp { op, left, .. } if open_right_bp(left) > infix_bp(op).0 => left,
+            _ => return false,
+        };
+    }
+}
+
+// Display and LaTeX output still to write. Nodes are expanded from a heap
+// stack, so deep trees cannot overflow the call stack.
+enum Piece<'e> {
+    Text(&'e str),
+    Node(&'e Expression),
+}
+
+fn operand(expr: &Expression, parens: bool) -> Vec<Piece<'_>> {
+    if parens {
+        vec![Piece::Text("("), Piece::Node(expr), Piece::Text(")")]
+    } else {
+        vec![Piece::Node(expr)]
+    }
+}
+
+impl fmt::Display for Expression {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mut pending = vec![Piece::Node(self)];
+        while let Some(piece) = pending.pop() {
+            let expr = match piece {
+                Piece::Text(text) => {
+                    f.write_str(text)?;
+                    continue;
+                }
+                Piece::Node(expr) => expr,
+            };
+            let pieces = match expr {
+                Expression::Number(n) => {
+                    write!(f, "{n}")?;
+                    continue;
+                }
+                Expression::Identifier(name) | Expression::Constant { name, .. } => {
+                    f.write_str(name)?;
+                    continue;
+                }
+                Expression::UnaryOp { op, expr } if is_postfix(op) => {
+                    let mut pieces = operand(expr, needs_parens_before_postfix(expr));
+                    pieces.push(Piece::Text(op));
+                    pieces
+                }
+                Expression::UnaryOp { op, expr } => {
+                    let mut pieces = vec![Piece::Text(op)];
+                    pieces.extend(operand(expr, needs_parens_after(expr, prefix_bp(op))));
+                    pieces
+                }
+                Expression::BinaryOp { op, left, right } => {
+                    let (l_bp, r_bp) = infix_bp(op);
+                    let mut pieces = operand(left, open_right_bp(left) <= l_bp);
+                    pieces.extend([Piece::Text(" "), Piece::Text(op), Piece::Text(" ")]);
+                    let parens = needs_parens_after(right, r_bp)
+                        || (is_postfix(op) && starts_with_sign(right));
+                    pieces.extend(operand(right, parens));
+                    pieces
+                }
+                Expression::FunctionCall { name, args } => {
+                    let mut pieces = vec![Piece::Text(name), Piece::Text("(")];
+                    for (i, arg) in args.iter().enumerate() {
+                        if i > 0 {
+                            pieces.push(Piece::Text(", "));
+                        }
+                        pieces.push(Piece::Node(arg));
+                    }
+                    pieces.push(Piece::Text(")"));
+                    pieces
+                }
+                Expression::Parenthesis(inner) => operand(inner, true),
+                Expression::LetBinding { name, value, body } => vec![
+                    Piece::Text("let "),
+                    Piece::Text(name),
+                    Piece::Text(" = "),
+                    Piece::Node(value),
+                    Piece::Text(" in "),
+                    Piece::Node(body),
+                ],
+            };
+            pending.extend(pieces.into_iter().rev());
+        }
+        Ok(())
+    }
+}
+
+// Output of the `Debug` impl still to write: text, or a node to expand at an
+// indentation level (used by `{:#?}`).
+enum DebugPiece<'e> {
+    Text(String),
+    Node(&'e Expression, usize),
+}
+
+enum DebugValue<'e> {
+    Leaf(String),
+    Child(&'e Expression),
+    List(&'e [Expression]),
+}
+
+// Writes what `#[derive(Debug)]` would, expanding nodes from a heap stack so
+// deep trees cannot overflow the call stack.
+impl fmt::Debug for Expression {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let pretty = f.alternate();
+        let mut pending = vec![DebugPiece::Node(self, 0)];
+        while let Some(piece) = pending.pop() {
+            match piece {
+                DebugPiece::Text(text) => f.write_str(&text)?,
+                DebugPiece::Node(expr, level) => {
+                    pending.extend(debug_pieces(expr, level, pretty).into_iter().rev());
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+// One node as a struct or tuple variant, with its children left as pieces.
+fn debug_pieces(expr: &Expression, level: usize, pretty: bool) -> Vec<DebugPiece<'_>> {
+    use DebugValue::{Child, Leaf, List};
+    let (variant, fields) = match expr {
+        Expression::Number(n) => ("Number", vec![("", Leaf(format!("{n:?}")))]),
+        Expression::Identifier(name) => ("Identifier", vec![("", Leaf(format!("{name:?}")))]),
+        Expression::Constant { name, value } => (
+            "Constant",
+            vec![
+                ("name", Leaf(format!("{name:?}"))),
+                ("value", Leaf(format!("{value:?}"))),
+            ],
+        ),
+        Expression::UnaryOp { op, expr } => (
+            "UnaryOp",
+            vec![("op", Leaf(format!("{op:?}"))), ("expr", Child(expr))],
+        ),
+        Expression::BinaryOp { op, left, right } => (
+            "BinaryOp",
+            vec![
+                ("op", Leaf(format!("{op:?}"))),
+                ("left", Child(left)),
+                ("right", Child(right)),
+            ],
+        ),
+        Expression::FunctionCall { name, args } => (
+            "FunctionCall",
+            vec![("name", Leaf(format!("{name:?}"))), ("args", List(args))],
+        ),
+        Expression::Parenthesis(inner) => ("Parenthesis", vec![("", Child(inner))]),
+        Expression::LetBinding { name, value, body } => (
+            "LetBinding",
+            vec![
+                ("name", Leaf(format!("{name:?}"))),
+                ("value", Child(value)),
+                ("body", Child(body)),
+            ],
+        ),
+    };
+    let indent = |level: usize| "    ".repeat(level);
+    let tuple = fields[0].0.is_empty();
+    let (open, close) = if tuple { ("(", ")") } else { (" {", "}") };
+    let mut pieces = vec![DebugPiece::Text(format!("{variant}{open}"))];
+    for (i, (field, value)) in fields.into_iter().enumerate() {
+        let label = if tuple {
+            String::new()
+        } else {
+            format!("{field}: ")
+        };
+        let lead = match (pretty, tuple, i) {
+            (true, ..) => format!("\n{}", indent(level + 1)),
+            (false, true, _) => String::new(),
+            (false, false, 0) => String::from(" "),
+            (false, false, _) => String::from(", "),
+        };
+        pieces.push(DebugPiece::Text(lead + &label));
+        match value {
+            Leaf(text) => pieces.push(DebugPiece::Text(text)),
+            Child(child) => pieces.push(DebugPiece::Node(child, level + 1)),
+            List([]) => pieces.push(DebugPiece::Text(String::from("[]"))),
+            List(items) => {
+                pieces.push(DebugPiece::Text(String::from("[")));
+                for (j, item) in items.iter().enumerate() {
+                    let lead = match (pretty, j) {
+                        (true, _) => format!("\n{}", indent(level + 2)),
+                        (false, 0) => String::new(),
+                        (false, _) => String::from(", "),
+                    };
+                    pieces.push(DebugPiece::Text(lead));
+                    pieces.push(DebugPiece::Node(item, level + 2));
+                    if pretty {
+                        pieces.push(DebugPiece::Text(String::from(",")));
+                    }
+                }
+                let end = if pretty {
+                    format!("\n{}]", indent(level + 1))
+                } else {
+                    String::from("]")
+                };
+                pieces.push(DebugPiece::Text(end));
+            }
+        }
+        if pretty {
+            pieces.push(DebugPiece::Text(String::from(",")));
+        }
+    }
+    let end = match (pretty, tuple) {
+        (true, _) => format!("\n{}{close}", indent(level)),
+        (false, true) => String::from(close),
+        (false, false) => format!(" {close}"),
+    };
+    pieces.push(DebugPiece::Text(end));
+    pieces
+}
+
+impl fmt::Display for Statement {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Statement::Assignment { name, expr } => write!(f, "{name} = {expr}"),
+            Statement::Expression(expr) => write!(f, "{expr}"),
+        }
+    }
+}
+
+impl Expression {
+    /// Renders the AST one node per line, with children indented beneath
+    /// their parent. Unlike `Display`, this shows structure rather than syntax.
+    pub fn to_tree_string(&self) -> String {
+        let mut out = String::new();
+        // Preorder with an explicit stack, so deep trees cannot overflow it.
+        let mut pending = vec![(self, 0)];
+        while let Some((expr, depth)) = pending.pop() {
+            let indent = "  ".repeat(depth);
+            let _ = match expr {
+                Expression::Number(n) => writeln!(out, "{indent}Number {n}"),
+                Expression::Identifier(name) => writeln!(out, "{indent}Identifier {name}"),
+                Expression::Constant { name, .. } => writeln!(out, "{indent}Constant {name}"),
+                Expression::UnaryOp { op, .. } => writeln!(out, "{indent}UnaryOp {op}"),
+                Expression::BinaryOp { op, .. } => writeln!(out, "{indent}BinaryOp {op}"),
+                Expression::FunctionCall { name, .. } => {
+                    writeln!(out, "{indent}FunctionCall {name}")
+                }
+                Expression::Parenthesis(_) => writeln!(out, "{indent}Parenthesis"),
+                Expression::LetBinding { name, .. } => writeln!(out, "{indent}LetBinding {name}"),
+            };
+            pending.extend(
+                expr.children()
+                    .into_iter()
+                    .rev()
+                    .map(|child| (child, depth + 1)),
+            );
+        }
+        out
+    }
+}
+
+impl Expression {
+    /// Renders the expression as LaTeX math: `(1+2)/3` becomes
+    /// `\frac{1 + 2}{3}`, `2^10` becomes `2^{10}` and `sqrt(x)*pi` becomes
+    /// `\sqrt{x} \cdot \pi`. Parentheses are inserted where `Display` would
+    /// need them, except inside fractions, exponents and roots, whose braces
+    /// already group.
+    pub fn to_latex(&self) -> String {
+        let mut out = String::new();
+        write_latex(&mut out, self);
+        out
+    }
+}
+
+// Binary operators whose LaTeX differs from their symbol. `/`, `//` and `^`
+// are laid out structurally instead.
+const LATEX_OPS: &[(Operator, &str)] = &[
+    ("*", r"\cdot"),
+    ("%", r"\bmod"),
+    ("<=", r"\le"),
+    (">=", r"\ge"),
+    ("==", "="),
+    ("!=", r"\ne"),
+    ("&", r"\mathbin{\&}"),
+    ("|", r"\mathbin{|}"),
+    ("~^", r"\oplus"),
+    ("<<", r"\ll"),
+    (">>", r"\gg"),
+];
+
+// Functions with a LaTeX command of their own; others use `\operatorname`.
+const LATEX_FUNCTIONS: &[(&str, &str)] = &[
+    ("sin", r"\sin"),
+    ("cos", r"\cos"),
+    ("tan", r"\tan"),
+    ("asin", r"\arcsin"),
+    ("acos", r"\arccos"),
+    ("atan", r"\arctan"),
+    ("exp", r"\exp"),
+    ("ln", r"\ln"),
+    ("log", r"\log"),
+    ("log10", r"\log_{10}"),
+    ("log2", r"\log_{2}"),
+    ("min", r"\min"),
+    ("max", r"\max"),
+];
+
+const LATEX_CONSTANTS: &[(&str, &str)] = &[("pi", r"\pi"), ("tau", r"\tau"), ("inf", r"\infty")];
+
+fn lookup<'a>(table: &[(&str, &'a str)], key: &str) -> Option<&'a str> {
+    table.iter().find(|(k, _)| *k == key).map(|&(_, v)| v)
+}
+
+// Expands nodes from a heap stack, like `Display`, so deep trees cannot
+// overflow the call stack.
+fn write_latex(out: &mut String, expr: &Expression) {
+    let mut pending = vec![Piece::Node(expr)];
+    while let Some(piece) = pending.pop() {
+        match piece {
+            Piece::Text(text) => out.push_str(text),
+            Piece::Node(expr) => pending.extend(latex_pieces(out, expr).into_iter().rev()),
+        }
+    }
+}
+
+// Writes the part of `expr` that comes before its first child and returns the
+// rest as pieces.
+fn latex_pieces<'e>(out: &mut String, expr: &'e Expression) -> Vec<Piece<'e>> {
+    match expr {
+        Expression::Number(n) if n.is_nan() => out.push_str(r"\mathrm{NaN}"),
+        Expression::Number(n) if n.is_infinite() => {
+            out.push_str(if *n > 0.0 { r"\infty" } else { r"-\infty" })
+        }
+        Expression::Number(n) => {
+            let _ = write!(out, "{n}");
+        }
+        Expression::Identifier(name) | Expression::Constant { name, .. } => {
+            write_latex_name(out, name)
+        }
+        Expression::UnaryOp { op: "√", expr } => {
+            out.push_str(r"\sqrt");
+            return latex_group(expr);
+        }
+        Expression::UnaryOp { op, expr } if is_postfix(op) => {
+            let mut pieces = latex_operand(expr, needs_parens_before_postfix(expr));
+            // A bare `%` starts a comment in LaTeX.
+            if *op == "%" {
+                pieces.push(Piece::Text("\\"));
+            }
+            pieces.push(Piece::Text(op));
+            return pieces;
+        }
+        Expression::UnaryOp { op, expr } => {
+            out.push_str(op);
+            return latex_operand(expr, needs_parens_after(expr, prefix_bp(op)));
+        }
+        Expression::BinaryOp {
+            op: "/",
+            left,
+            right,
+        } => {
+            out.push_str(r"\frac");
+            let mut pieces = latex_group(left);
+            pieces.extend(latex_group(right));
+            return pieces;
+        }
+        Expression::BinaryOp {
+            op: "//",
+            left,
+            right,
+        } => {
+            out.push_str(r"\left\lfloor \frac");
+            let mut pieces = latex_group(left);
+            pieces.extend(latex_group(right));
+            pieces.push(Piece::Text(r" \right\rfloor"));
+            return pieces;
+        }
+        Expression::BinaryOp {
+            op: "^",
+            left,
+            right,
+        } => {
+            let mut pieces = latex_operand(left, open_right_bp(left) <= infix_bp("^").0);
+            pieces.push(Piece::Text("^"));
+            pieces.extend(latex_group(right));
+            return pieces;
+        }
+        Expression::BinaryOp { op, left, right } => {
+            let (l_bp, r_bp) = infix_bp(op);
+            let mut pieces = latex_operand(left, open_right_bp(left) <= l_bp);
+            pieces.extend([
+                Piece::Text(" "),
+                Piece::Text(lookup(LATEX_OPS, op).unwrap_or(op)),
+                Piece::Text(" "),
+            ]);
+            pieces.extend(latex_operand(right, needs_parens_after(right, r_bp)));
+            return pieces;
+        }
+        Expression::FunctionCall { name, args } => return latex_call(out, name, args),
+        Expression::Parenthesis(inner) => return latex_operand(inner, true),
+        Expression::LetBinding { name, value, body } => {
+            out.push_str(r"\text{let } ");
+            write_latex_name(out, name);
+            out.push_str(" = ");
+            return vec![
+                Piece::Node(value),
+                Piece::Text(r" \text{ in } "),
+                Piece::Node(body),
+            ];
+        }
+    }
+    Vec::new()
+}
+
+fn write_latex_name(out: &mut String, name: &str) {
+    let normalized = builtins::normalize_name(name);
+    if let Some(latex) = lookup(LATEX_CONSTANTS, &normalized) {
+        out.push_str(latex);
+    } else if name.chars().count() == 1 {
+        out.push_str(name);
+    } else {
+        let _ = write!(out, r"\mathrm{{{}}}", name.replace('_', r"\_"));
+    }
+}
+
+fn latex_call<'e>(out: &mut String, name: &str, args: &'e [Expression]) -> Vec<Piece<'e>> {
+    let normalized = builtins::normalize_name(name);
+    match (normalized.as_str(), args) {
+        ("sqrt", [x]) => {
+            out.push_str(r"\sqrt");
+            latex_group(x)
+        }
+        ("root", [n, x]) => {
+            out.push_str(r"\sqrt[");
+            let mut pieces = vec![Piece::Node(unparenthesized(n)), Piece::Text("]")];
+            pieces.extend(latex_group(x));
+            pieces
+        }
+        ("cbrt", [x]) => {
+            out.push_str(r"\sqrt[3]");
+            latex_group(x)
+        }
+        ("abs", [x]) => latex_delimited(r"\left|", x, r"\right|"),
+        ("floor", [x]) => latex_delimited(r"\left\lfloor ", x, r" \right\rfloor"),
+        ("ceil", [x]) => latex_delimited(r"\left\lceil ", x, r" \right\rceil"),
+        _ => {
+            match lookup(LATEX_FUNCTIONS, &normalized) {
+                Some(command) => out.push_str(command),
+                None => {
+                    let _ = write!(out, r"\operatorname{{{}}}", name.replace('_', r"\_"));
+                }
+            }
+            out.push_str(r"\left(");
+            let mut pieces = Vec::new();
+            for (i, arg) in args.iter().enumerate() {
+                if i > 0 {
+                    pieces.push(Piece::Text(", "));
+                }
+                pieces.push(Piece::Node(arg));
+            }
+            pieces.push(Piece::Text(r"\right)"));
+            pieces
+        }
+    }
+}
+
+fn latex_operand(expr: &Expression, parens: bool) -> Vec<Piece<'_>> {
+    if parens {
+        latex_delimited(r"\left(", expr, r"\right)")
+    } else {
+        vec![Piece::Node(expr)]
+    }
+}
+
+fn latex_delimited<'e>(open: &'e str, expr: &'e Expression, close: &'e str) -> Vec<Piece<'e>> {
+    vec![
+        Piece::Text(open),
+        Piece::Node(unparenthesized(expr)),
+        Piece::Text(close),
+    ]
+}
+
+// Braces group on their own, so parentheses directly inside them are dropped.
+fn latex_group(expr: &Expression) -> Vec<Piece<'_>> {
+    vec![
+        Piece::Text("{"),
+        Piece::Node(unparenthesized(expr)),
+        Piece::Text("}"),
+    ]
+}
+
+fn unparenthesized(mut expr: &Expression) -> &Expression {
+    while let Expression::Parenthesis(inner) = expr {
+        expr = inner;
+    }
+    expr
+}
+
+/// How numeric results are printed, e.g. by the REPL.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct NumberFormat {
+    /// Significant digits; `None` prints the shortest round-tripping form.
+    pub precision: Option<usize>,
+    /// Use scientific notation for very large or very small magnitudes.
+    pub scientific: bool,
+}
+
+const SCIENTIFIC_ABOVE: f64 = 1e10;
+const SCIENTIFIC_BELOW: f64 = 1e-4;
+
+pub fn format_number(value: f64, format: NumberFormat) -> String {
+    let magnitude = value.abs();
+    let notation =
+        if format.scientific && !(SCIENTIFIC_BELOW..SCIENTIFIC_ABOVE).contains(&magnitude) {
+            Notation::Scientific
+        } else {
+            Notation::Fixed
+        };
+    format_result(
+        value,
+        FormatOptions {
+            precision: format.precision,
+            notation,
+            thousands_separator: None,
+        },
+    )
+}
+
+/// Notation used by `format_result`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Notation {
+    /// Positional: `12345.6`, `0.00042`.
+    #[default]
+    Fixed,
+    /// One digit before the point: `1.23456e4`, `4.2e-4`.
+    Scientific,
+    /// Exponents that are multiples of 3, so the mantissa is in `[1, 1000)`:
+    /// `12.3456e3`, `420e-6`.
+    Engineering,
+}
+
+/// Options for `format_result`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct FormatOptions {
+    /// Significant digits; `None` prints the shortest round-tripping digits.
+    pub precision: Option<usize>,
+    pub notation: Notation,
+    /// Groups the integer digits of `Fixed` output in threes: `Some(',')`
+    /// prints `1,234,567.5`.
+    pub thousands_separator: Option<char>,
+}
+
+/// Formats `value` in the given notation. Rounding to `precision` keeps
+/// trailing zeros in scientific and engineering notation (`1.50e0`), since
+/// they show the precision, but drops them in fixed notation. Zero, infinity
+/// and NaN print as in `f64`'s `Display`.
+pub fn format_result(value: f64, options: FormatOptions) -> String {
+    if !value.is_finite() || value == 0.0 {
+        return value.to_string();
+    }
+    let precision = options.precision.map(|digits| digits.max(1));
+    match options.notation {
+        Notation::Fixed => {
+            let fixed = format_fixed(value, precision);
+            match options.thousands_separator {
+                Some(separator) => group_thousands(&fixed, separator),
+                None => fixed,
+            }
+        }
+        Notation::Scientific => format_scientific(value, precision),
+        Notation::Engineering => format_engineering(value, precision),
+    }
+}
+
+fn format_fixed(value: f64, precision: Option<usize>) -> String {
+    let Some(digits) = precision else {
+        return value.to_string();
+    };
+    let exponent = value.abs().log10().floor() as i32;
+    let decimals = digits as i32 - 1 - exponent;
+    if decimals <= 0 {
+        // Rounding lands left of the decimal point: `123456` to 2 digits is `120000`.
+        let scale = 10f64.powi(-decimals);
+        return ((value / scale).round() * scale).to_string();
+    }
+    let fixed = format!("{:.*}", decimals as usize, value);
+    fixed
+        .trim_end_matches('0')
+        .trim_end_matches('.')
+        .to_string()
+}
+
+fn format_scientific(value: f64, precision: Option<usize>) -> String {
+    match precision {
+        Some(digits) => format!("{:.*e}", digits - 1, value),
+        None => format!("{value:e}"),
+    }
+}
+
+// Moves the decimal point of the scientific form right by up to two places,
+// so the digits are never re-rounded: `1.2345e4` becomes `12.345e3`.
+fn format_engineering(value: f64, precision: Option<usize>) -> String {
+    let scientific = format_scientific(value.abs(), precision);
+    let (mantissa, exponent) = scientific.split_once('e').unwrap_or((&scientific, "0"));
+    let exponent: i32 = exponent.parse().unwrap_or(0);
+    let mut digits = mantissa.replace('.', "");
+    let shift = exponent.rem_euclid(3) as usize;
+    while digits.len() < shift + 1 {
+        digits.push('0');
+    }
+    let (int_part, frac_part) = digits.split_at(shift + 1);
+    let sign = if value < 0.0 { "-" } else { "" };
+    let point = if frac_part.is_empty() { "" } else { "." };
+    format!(
+        "{sign}{int_part}{point}{frac_part}e{}",
+        exponent - shift as i32
+    )
+}
+
+// Inserts `separator` every three digits left of the decimal point.
+fn group_thousands(number: &str, separator: char) -> String {
+    let (sign, unsigned) = match number.strip_prefix('-') {
+        Some(rest) => ("-", rest),
+        None => ("", number),
+    };
+    let (int_part, rest) = unsigned.split_at(unsigned.find('.').unwrap_or(unsigned.len()));
+    let mut grouped = String::from(sign);
+    for (i, digit) in int_part.chars().enumerate() {
+        if i > 0 && (int_part.len() - i) % 3 == 0 {
+            grouped.push(separator);
+        }
+        grouped.push(digit);
+    }
+    grouped.push_str(rest);
+    grouped
+}
@@ -0,0 +1,197 @@
+use alloc::format;
+use alloc::string::{String, ToString};
+use alloc::vec::Vec;
+
+use crate::builtins::{self, Operator};
+use crate::error::CalcError;
+use crate::eval::{ExactValue, evaluate_exact};
+#[cfg(not(feature = "std"))]
+use crate::math::FloatExt;
+use crate::parser::Expression;
+
+// Exact evaluation over `i64`: every intermediate result must be an integer
+// that fits, so `7/2` and `2^63` are errors rather than rounded floats.
+pub(crate) fn evaluate_i64(expr: &Expression) -> Result<i64, CalcError> {
+    evaluate_exact(expr)
+}
+
+impl ExactValue for i64 {
+    fn number(n: f64) -> Result<Self, CalcError> {
+        to_i64(n)
+    }
+
+    fn constant(_name: &str, value: f64) -> Result<Self, CalcError> {
+        to_i64(value)
+    }
+
+    fn unary(op: Operator, value: Self) -> Result<Self, CalcError> {
+        match op {
+            "+" => Ok(value),
+            "-" => value.checked_neg().ok_or(CalcError::IntegerOverflow),
+            "%" => infix("/", value, 100),
+            "!" => factorial(value),
+            _ => Err(CalcError::UnknownFunction(op.to_string())),
+        }
+    }
+
+    fn infix(op: Operator, a: Self, b: Self) -> Result<Self, CalcError> {
+        infix(op, a, b)
+    }
+
+    fn call(name: &str, args: &[Self]) -> Result<Self, CalcError> {
+        call(name, args)
+    }
+}
+
+pub(crate) fn to_i64(value: f64) -> Result<i64, CalcError> {
+    if value.is_nan() || value.fract() != 0.0 {
+        return Err(CalcError::NonInteger);
+    }
+    // `i64::MAX as f64` rounds up to 2^63, which itself does not fit.
+    if value < i64::MIN as f64 || value >= i64::MAX as f64 {
+        return Err(CalcError::IntegerOverflow);
+    }
+    Ok(value as i64)
+}
+
+pub(crate) fn infix(op: &str, a: i64, b: i64) -> Result<i64, CalcError> {
+    let checked = match op {
+        "+" => a.checked_add(b),
+        "-" => a.checked_sub(b),
+        "*" => a.checked_mul(b),
+        "/" | "//" | "%" if b == 0 => return Err(CalcError::DivideByZero),
+        // `checked_rem` first: `i64::MIN % -1` overflows just like the quotient.
+        "/" => match a.checked_rem(b) {
+            Some(0) => a.checked_div(b),
+            Some(_) => return Err(CalcError::NonInteger),
+            None => None,
+        },
+        "//" => a.checked_div(b).map(|q| {
+            // Round toward negative infinity, matching the float operator.
+            if a % b != 0 && (a < 0) != (b < 0) {
+                q - 1
+            } else {
+                q
+            }
+        }),
+        "%" => a.checked_rem(b),
+        "^" => return pow(a, b),
+        "<" => Some(i64::from(a < b)),
+        ">" => Some(i64::from(a > b)),
+        "<=" => Some(i64::from(a <= b)),
+        ">=" => Some(i64::from(a >= b)),
+        "==" => Some(i64::from(a == b)),
+        "!=" => Some(i64::from(a != b)),
+        "&" => Some(a & b),
+        "~^" => Some(a ^ b),
+        "|" => Some(a | b),
+        "<<" | ">>" => return shift(op, a, b),
+        _ => return Err(CalcError::UnknownFunction(op.to_string())),
+    };
+    checked.ok_or(CalcError::IntegerOverflow)
+}
+
+fn pow(base: i64, exp: i64) -> Result<i64, CalcError> {
+    if exp < 0 {
+        // Only ±1 have integer reciprocals.
+        return match base {
+            0 => Err(CalcError::DivideByZero),
+            1 => Ok(1),
+            -1 => Ok(if exp % 2 == 0 { 1 } else { -1 }),
+            _ => Err(CalcError::NonInteger),
+        };
+    }
+    match base {
+        0 | 1 => Ok(if exp == 0 { 1 } else { base }),
+        -1 => Ok(if exp % 2 == 0 { 1 } else { -1 }),
+        _ => u32::try_from(exp)
+            .ok()
+            .and_then(|exp| base.checked_pow(exp))
+            .ok_or(CalcError::IntegerOverflow),
+    }
+}
+
+pub(crate) fn factorial(n: i64) -> Result<i64, CalcError> {
+    if n < 0 {
+        return Err(CalcError::DomainError {
+            name: "!".to_string(),
+            arg: n as f64,
+        });
+    }
+    (2..=n).try_fold(1i64, |acc, k| {
+        acc.checked_mul(k).ok_or(CalcError::IntegerOverflow)
+    })
+}
+
+// Shift amounts must be in `0..64`; anything else is a domain error rather
+// than being masked. `>>` is arithmetic (it keeps the sign, so `-8 >> 1` is
+// -4), and `<<` fails instead of shifting bits out: `1 << 63` overflows.
+pub(crate) fn shift(op: &str, a: i64, b: i64) -> Result<i64, CalcError> {
+    let Some(n) = u32::try_from(b).ok().filter(|&n| n < i64::BITS) else {
+        return Err(CalcError::DomainError {
+            name: op.to_string(),
+            arg: b as f64,
+        });
+    };
+    if op == ">>" {
+        return Ok(a >> n);
+    }
+    let shifted = a << n;
+    if shifted >> n != a {
+        return Err(CalcError::IntegerOverflow);
+    }
+    Ok(shifted)
+}
+
+// The float builtins would lose precision above 2^53, so integer mode only
+// offers the functions that are exact on integers.
+fn call(name: &str, args: &[i64]) -> Result<i64, CalcError> {
+    let name = builtins::normalize_name(name);
+    let (min, max) = match name.as_str() {
+        "abs" | "sign" => (1, Some(1)),
+        "min" | "max" => (1, None),
+        _ => return Err(CalcError::UnknownFunction(name)),
+    };
+    builtins::check_arity(&name, min, max, args.len())?;
+    match name.as_str() {
+        "abs" => args[0].checked_abs().ok_or(CalcError::IntegerOverflow),
+        "sign" => Ok(args[0].signum()),
+        "min" => Ok(args.iter().copied().min().unwrap_or_default()),
+        _ => Ok(args.iter().copied().max().unwrap_or_default()),
+    }
+}
+
+// Prime factorization as an expression that evaluates back to `n`, by trial
+// division up to `sqrt(n)`: `360` is `2^3 * 3^2 * 5`. A negative number gets a
+// leading `-` (`-12` is `-2^2 * 3`), and 0 and 1 are returned as they are.
+pub(crate) fn factor(n: i64) -> String {
+    let mut rest = n.unsigned_abs();
+    if rest <= 1 {
+        return n.to_string();
+    }
+    let mut factors = Vec::new();
+    let mut p = 2;
+    while p <= rest / p {
+        let mut exponent = 0;
+        while rest.is_multiple_of(p) {
+            rest /= p;
+            exponent += 1;
+        }
+        if exponent > 0 {
+            factors.push((p, exponent));
+        }
+        p += if p == 2 { 1 } else { 2 };
+    }
+    if rest > 1 {
+        factors.push((rest, 1));
+    }
+    let terms: Vec<String> = factors
+        .into_iter()
+        .map(|(p, exponent)| match exponent {
+            1 => p.to_string(),
+            _ => format!("{p}^{exponent}"),
+        })
+        .collect();
+    let sign = if n < 0 { "-" } else { "" };
+    format!("{sign}{}", terms.join(" * "))
+}
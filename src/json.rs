@@ -0,0 +1,68 @@
+use alloc::format;
+use alloc::string::{String, ToString};
+
+use serde::Serialize;
+
+use crate::context::Context;
+use crate::error::{CalcError, ErrorKind};
+use crate::eval;
+use crate::parser::Expression;
+
+// `{"input": ..., "expression": ..., "ast": ..., "result": ...}` on success;
+// on failure `result` is replaced by `error`, and a parse error also leaves
+// `expression` and `ast` null.
+#[derive(Serialize)]
+struct Report<'a> {
+    input: &'a str,
+    expression: Option<String>,
+    ast: Option<&'a Expression>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    result: Option<f64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    error: Option<ErrorReport>,
+}
+
+#[derive(Serialize)]
+struct ErrorReport {
+    category: ErrorKind,
+    kind: String,
+    message: String,
+    position: Option<usize>,
+}
+
+impl From<&CalcError> for ErrorReport {
+    fn from(err: &CalcError) -> Self {
+        // The variant name is the start of its `Debug` output.
+        let debug = format!("{err:?}");
+        let kind = debug
+            .split(|c: char| !c.is_alphanumeric())
+            .next()
+            .unwrap_or_default();
+        ErrorReport {
+            category: err.kind(),
+            kind: kind.to_string(),
+            message: err.to_string(),
+            position: err.position(),
+        }
+    }
+}
+
+pub(crate) fn evaluate_to_json(input: &str) -> String {
+    let parsed = crate::parse(input);
+    let (expression, ast, outcome) = match &parsed {
+        Ok(expr) => (
+            Some(expr.to_string()),
+            Some(expr),
+            eval::evaluate_expression(expr, &Context::new()),
+        ),
+        Err(err) => (None, None, Err(err.clone())),
+    };
+    let report = Report {
+        input,
+        expression,
+        ast,
+        result: outcome.as_ref().ok().copied(),
+        error: outcome.as_ref().err().map(ErrorReport::from),
+    };
+    serde_json::to_string(&report).expect("reports serialize to JSON")
+}
@@ -1,55 +1,336 @@
+use alloc::collections::VecDeque;
+use alloc::string::String;
+use alloc::vec::Vec;
+use core::fmt;
+
 use crate::error::CalcError;
 use crate::builtins;
 use crate::builtins::Operator;
 
 #[derive(Debug, PartialEq, Clone)]
 pub enum Token {
-    Number(i32),
+    Number(u64),
     Ident(String),
     DecimalPoint,
+    Fraction(String),
+    Exponent(String),
     Comma,
+    Semicolon,
+    Equals,
     Op(Operator),
     OpenParen,
     CloseParen,
-    EOF,
+    Bar,
+    Eof,
 }
 
-pub(crate) fn tokenize(input: &str) -> Result<Vec<Token>, CalcError> {
-    let mut tokens = Vec::new();
-    let chars: Vec<char> = input.chars().collect();
-    let mut i = 0;
+/// User-facing spelling: symbols print as themselves (`)`, `+`), the rest as a
+/// short description (`number 5`, `end of input`).
+impl fmt::Display for Token {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Token::Number(n) => write!(f, "number {n}"),
+            Token::Ident(name) => write!(f, "identifier {name}"),
+            Token::DecimalPoint => write!(f, "."),
+            Token::Fraction(digits) => write!(f, "digits {digits}"),
+            Token::Exponent(exp) => write!(f, "exponent e{exp}"),
+            Token::Comma => write!(f, ","),
+            Token::Semicolon => write!(f, ";"),
+            Token::Equals => write!(f, "="),
+            Token::Op(op) => write!(f, "{op}"),
+            Token::OpenParen => write!(f, "("),
+            Token::CloseParen => write!(f, ")"),
+            Token::Bar => write!(f, "|"),
+            Token::Eof => write!(f, "end of input"),
+        }
+    }
+}
+
+impl Token {
+    // Whether the token is a literal symbol rather than a described value, so
+    // error messages can quote it: `got '+'` but `got number 5`.
+    pub(crate) fn is_symbol(&self) -> bool {
+        !matches!(
+            self,
+            Token::Number(_)
+                | Token::Ident(_)
+                | Token::Fraction(_)
+                | Token::Exponent(_)
+                | Token::Eof
+        )
+    }
+}
 
-    while i < chars.len() {
-        match chars[i] {
+/// Streaming tokenizer over an input string.
+///
+/// Yields one token at a time and finishes with `Token::Eof`. After an error,
+/// lexing resumes at the next character, so callers can either stop at the
+/// first error or keep going to find the rest.
+pub struct Lexer {
+    chars: Vec<char>,
+    i: usize,
+    // Tokens (with their char offsets) and errors lexed but not yet yielded;
+    // one lexeme such as `1.5e3` can produce several tokens.
+    pending: VecDeque<Result<(Token, usize), CalcError>>,
+    finished: bool,
+    comma_separators: bool,
+}
+
+impl Lexer {
+    pub fn new(input: &str) -> Self {
+        Lexer {
+            chars: input.chars().collect(),
+            i: 0,
+            pending: VecDeque::new(),
+            finished: false,
+            comma_separators: false,
+        }
+    }
+
+    /// Also reads commas as thousands separators inside a number, so `1,000`
+    /// is one literal. A comma only counts when exactly three digits follow
+    /// it, which leaves `max(1,2)` alone but makes `max(1,234)` a single
+    /// `1234`; put a space after argument commas in this mode.
+    pub fn comma_separators(mut self, enabled: bool) -> Self {
+        self.comma_separators = enabled;
+        self
+    }
+
+    fn push(&mut self, token: Token, pos: usize) {
+        self.pending.push_back(Ok((token, pos)));
+    }
+
+    // Like `next`, but also reports the char offset each token starts at.
+    pub(crate) fn next_with_position(&mut self) -> Option<Result<(Token, usize), CalcError>> {
+        loop {
+            if let Some(item) = self.pending.pop_front() {
+                return Some(item);
+            }
+            if self.finished {
+                return None;
+            }
+            if self.i >= self.chars.len() {
+                self.finished = true;
+                return Some(Ok((Token::Eof, self.chars.len())));
+            }
+            let start = self.i;
+            if let Err(err) = self.lex_token(start) {
+                self.pending.push_back(Err(err));
+                self.i = self.i.max(start + 1);
+            }
+        }
+    }
+
+    fn lex_token(&mut self, start: usize) -> Result<(), CalcError> {
+        match self.chars[start] {
             'a'..='z' | 'A'..='Z' | '_' => {
                 let mut ident = String::new();
-                while i < chars.len() && (chars[i].is_ascii_alphanumeric() || chars[i] == '_') {
-                    ident.push(chars[i]);
-                    i += 1;
+                while self.i < self.chars.len()
+                    && (self.chars[self.i].is_ascii_alphanumeric() || self.chars[self.i] == '_')
+                {
+                    ident.push(self.chars[self.i]);
+                    self.i += 1;
                 }
-                tokens.push(Token::Ident(ident));
-                continue;
+                // `_5` / `_1_000` look like numbers with a leading separator.
+                if ident.starts_with('_')
+                    && ident.chars().any(|c| c.is_ascii_digit())
+                    && ident.chars().all(|c| c.is_ascii_digit() || c == '_')
+                {
+                    return Err(CalcError::InvalidDigitSeparator {
+                        literal: ident,
+                        pos: start,
+                    });
+                }
+                match builtins::keyword_operator(&ident) {
+                    Some(op) => self.push(Token::Op(op), start),
+                    None => self.push(Token::Ident(ident), start),
+                }
+                return Ok(());
             }
             '0'..='9' => {
-                let mut num = 0;
-                while i < chars.len() && chars[i].is_ascii_digit() {
-                    num = num * 10 + chars[i].to_digit(10).unwrap() as i32;
-                    i += 1;
+                let mut digits = self.lex_digits()?;
+                while let Some(group) = self.comma_group() {
+                    digits.push_str(&group);
+                }
+                let mut num: u64 = 0;
+                for digit in digits.chars() {
+                    num = num
+                        .checked_mul(10)
+                        .and_then(|n| n.checked_add(u64::from(digit.to_digit(10).unwrap())))
+                        .ok_or_else(|| CalcError::NumberOverflow {
+                            literal: digits.clone(),
+                            pos: start,
+                        })?;
+                }
+                self.push(Token::Number(num), start);
+                // A trailing point (`5.`) belongs to the literal here, so an
+                // exponent can still follow it: `5.e3`.
+                if self.chars.get(self.i) == Some(&'.')
+                    && !matches!(self.chars.get(self.i + 1), Some('0'..='9' | '_'))
+                {
+                    self.push(Token::DecimalPoint, self.i);
+                    self.i += 1;
                 }
-                tokens.push(Token::Number(num));
-                continue;
+                self.lex_exponent()?;
+                return Ok(());
+            }
+            '.' => {
+                self.push(Token::DecimalPoint, start);
+                self.i += 1;
+                // Keep the raw digits so leading zeros (`1.05`) survive.
+                if matches!(self.chars.get(self.i), Some('0'..='9' | '_')) {
+                    let digits_start = self.i;
+                    let digits = self.lex_digits()?;
+                    self.push(Token::Fraction(digits), digits_start);
+                    self.lex_exponent()?;
+                }
+                return Ok(());
+            }
+            // Before the operator table, which also lists `|` (bitwise or): the
+            // parser decides whether a bar is abs or or.
+            '|' => self.push(Token::Bar, start),
+            _ if let Some((op, len)) = builtins::match_operator(&self.chars[start..]) => {
+                self.push(Token::Op(op), start);
+                self.i += len;
+                return Ok(());
             }
-            '.' => tokens.push(Token::DecimalPoint),
-            ',' => tokens.push(Token::Comma),
-            ch if builtins::is_operator_char(ch) => tokens.push(Token::Op(ch)),
-            '(' => tokens.push(Token::OpenParen),
-            ')' => tokens.push(Token::CloseParen),
-            ' ' => {} // Ignore whitespace
-            other => return Err(CalcError::UnexpectedChar(other)),
+            'π' => self.push(Token::Ident(String::from("pi")), start),
+            ',' => self.push(Token::Comma, start),
+            ';' => self.push(Token::Semicolon, start),
+            '=' => self.push(Token::Equals, start),
+            '(' => self.push(Token::OpenParen, start),
+            ')' => self.push(Token::CloseParen, start),
+            // Tabs and line breaks too, so pasted input works; `;` separates statements.
+            c if c.is_whitespace() => {}
+            other => {
+                return Err(CalcError::UnexpectedChar {
+                    ch: other,
+                    pos: start,
+                });
+            }
+        }
+        self.i += 1;
+        Ok(())
+    }
+
+    // Reads a run of digits, dropping `_` separators. A separator must sit
+    // between two digits: `1_000` is fine, `_1`, `1_` and `1__0` are not.
+    fn lex_digits(&mut self) -> Result<String, CalcError> {
+        let start = self.i;
+        let mut digits = String::new();
+        let mut malformed = false;
+        while self.i < self.chars.len()
+            && (self.chars[self.i].is_ascii_digit() || self.chars[self.i] == '_')
+        {
+            if self.chars[self.i] == '_' {
+                let prev_is_digit = self.i > start && self.chars[self.i - 1].is_ascii_digit();
+                let next_is_digit = self
+                    .chars
+                    .get(self.i + 1)
+                    .is_some_and(|c| c.is_ascii_digit());
+                malformed |= !(prev_is_digit && next_is_digit);
+            } else {
+                digits.push(self.chars[self.i]);
+            }
+            self.i += 1;
+        }
+        if malformed {
+            return Err(CalcError::InvalidDigitSeparator {
+                literal: self.chars[start..self.i].iter().collect(),
+                pos: start,
+            });
+        }
+        Ok(digits)
+    }
+
+    // With comma separators on, consumes `,ddd` when it is a whole digit group.
+    fn comma_group(&mut self) -> Option<String> {
+        if !self.comma_separators || self.chars.get(self.i) != Some(&',') {
+            return None;
+        }
+        let group = self.chars.get(self.i + 1..self.i + 4)?;
+        let longer = self
+            .chars
+            .get(self.i + 4)
+            .is_some_and(|c| c.is_ascii_digit() || *c == '_');
+        if !group.iter().all(char::is_ascii_digit) || longer {
+            return None;
+        }
+        self.i += 4;
+        Some(group.iter().collect())
+    }
+
+    // `e`/`E` directly after a numeric literal starts an exponent only when
+    // digits follow (optionally signed); otherwise it is left for the
+    // identifier lexer, so `2e` stays `2` followed by the constant `e`.
+    fn lex_exponent(&mut self) -> Result<(), CalcError> {
+        let start = self.i;
+        if !matches!(self.chars.get(start), Some('e' | 'E')) {
+            return Ok(());
+        }
+        let mut exp = String::new();
+        let mut j = start + 1;
+        if let Some(&sign @ ('+' | '-')) = self.chars.get(j) {
+            exp.push(sign);
+            j += 1;
+        }
+        if !self.chars.get(j).is_some_and(|c| c.is_ascii_digit()) {
+            return Ok(());
         }
-        i += 1;
+        self.i = j;
+        exp.push_str(&self.lex_digits()?);
+        self.push(Token::Exponent(exp), start);
+        Ok(())
     }
+}
+
+impl Iterator for Lexer {
+    type Item = Result<Token, CalcError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.next_with_position()
+            .map(|item| item.map(|(token, _)| token))
+    }
+}
+
+pub(crate) fn tokenize(input: &str) -> Result<Vec<Token>, CalcError> {
+    Lexer::new(input).collect()
+}
 
-    tokens.push(Token::EOF);
-    Ok(tokens)
+// Returns the tokens together with the char offset at which each one starts.
+pub(crate) fn tokenize_with_positions(input: &str) -> Result<(Vec<Token>, Vec<usize>), CalcError> {
+    collect_with_positions(Lexer::new(input))
+}
+
+pub(crate) fn collect_with_positions(
+    mut lexer: Lexer,
+) -> Result<(Vec<Token>, Vec<usize>), CalcError> {
+    let (mut tokens, mut positions) = (Vec::new(), Vec::new());
+    while let Some(item) = lexer.next_with_position() {
+        let (token, pos) = item?;
+        tokens.push(token);
+        positions.push(pos);
+    }
+    Ok((tokens, positions))
+}
+
+// Like `tokenize_with_positions`, but keeps going after errors and returns all
+// of them. Each bad lexeme becomes an empty identifier, so the parser still
+// sees an operand there.
+pub(crate) fn tokenize_recovering(input: &str) -> (Vec<Token>, Vec<usize>, Vec<CalcError>) {
+    let mut lexer = Lexer::new(input);
+    let (mut tokens, mut positions, mut errors) = (Vec::new(), Vec::new(), Vec::new());
+    while let Some(item) = lexer.next_with_position() {
+        let (token, pos) = match item {
+            Ok(positioned) => positioned,
+            Err(err) => {
+                let pos = err.position().unwrap_or_default();
+                errors.push(err);
+                (Token::Ident(String::new()), pos)
+            }
+        };
+        tokens.push(token);
+        positions.push(pos);
+    }
+    (tokens, positions, errors)
 }
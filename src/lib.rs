@@ -1,24 +1,270 @@
+#![cfg_attr(not(feature = "std"), no_std)]
+
+extern crate alloc;
+
+use alloc::collections::{BTreeMap, BTreeSet};
+use alloc::vec::Vec;
+
+#[cfg(not(any(feature = "std", feature = "libm")))]
+compile_error!("rustcalc needs either the `std` or the `libm` feature");
+
+#[cfg(feature = "bigint")]
+mod bigint;
+mod calculator;
+mod compiled;
+mod complex;
+mod context;
 mod error;
 mod eval;
+mod format;
+mod integer;
+#[cfg(feature = "serde_json")]
+mod json;
 mod builtins;
 mod lexer;
+#[cfg(not(feature = "std"))]
+mod math;
 mod parser;
+mod random;
+mod rational;
+mod rpn;
+mod simplify;
+mod visit;
 
-pub use error::CalcError;
-pub use parser::Expression;
+pub use builtins::{list_constants, list_functions};
+pub use calculator::{AngleMode, Calculator, CalculatorBuilder, CaretMode, DivByZero};
+pub use compiled::CompiledExpr;
+pub use complex::Complex;
+pub use context::Context;
+pub use error::{CalcError, ErrorKind};
+pub use format::{FormatOptions, Notation, NumberFormat, format_number, format_result};
+pub use lexer::{Lexer, Token};
+pub use parser::{Expression, Statement};
+pub use rational::Rational;
+
+/// Splits `input` into tokens, ending with `Token::Eof`.
+///
+/// ```
+/// use rustcalc::{Token, tokenize};
+///
+/// assert_eq!(
+///     tokenize("1+2").unwrap(),
+///     vec![Token::Number(1), Token::Op("+"), Token::Number(2), Token::Eof]
+/// );
+/// ```
+pub fn tokenize(input: &str) -> Result<Vec<Token>, CalcError> {
+    lexer::tokenize(input)
+}
 
 pub fn parse(input: &str) -> Result<Expression, CalcError> {
-    let tokens = lexer::tokenize(input)?;
-    parser::parse_tokens(&tokens)
+    parse_with_max_depth(Lexer::new(input), parser::DEFAULT_MAX_DEPTH)
+}
+
+pub(crate) fn parse_with_max_depth(
+    lexer: Lexer,
+    max_depth: usize,
+) -> Result<Expression, CalcError> {
+    let (tokens, positions) = lexer::collect_with_positions(lexer)?;
+    parser::parse_tokens_at(&tokens, &positions, max_depth)
+}
+
+/// Like `parse`, but recovers after each error and reports every problem in
+/// `input`, ordered by position. An empty result means the input parses.
+pub fn parse_all_errors(input: &str) -> Vec<CalcError> {
+    let (tokens, positions, mut errors) = lexer::tokenize_recovering(input);
+    errors.extend(parser::collect_parse_errors(
+        &tokens,
+        &positions,
+        parser::DEFAULT_MAX_DEPTH,
+    ));
+    errors.sort_by_key(|err| err.position());
+    errors
+}
+
+pub fn parse_statement(input: &str) -> Result<Statement, CalcError> {
+    parse_statement_with_max_depth(Lexer::new(input), parser::DEFAULT_MAX_DEPTH)
+}
+
+pub(crate) fn parse_statement_with_max_depth(
+    lexer: Lexer,
+    max_depth: usize,
+) -> Result<Statement, CalcError> {
+    let (tokens, positions) = lexer::collect_with_positions(lexer)?;
+    parser::parse_statement_tokens_at(&tokens, &positions, max_depth)
+}
+
+pub fn parse_program(input: &str) -> Result<Vec<Statement>, CalcError> {
+    parse_program_with_max_depth(Lexer::new(input), parser::DEFAULT_MAX_DEPTH)
+}
+
+pub(crate) fn parse_program_with_max_depth(
+    lexer: Lexer,
+    max_depth: usize,
+) -> Result<Vec<Statement>, CalcError> {
+    let (tokens, positions) = lexer::collect_with_positions(lexer)?;
+    parser::parse_program_tokens_at(&tokens, &positions, max_depth)
 }
 
 pub fn eval(input: &str) -> Result<f64, CalcError> {
-    let expr = parse(input)?;
-    eval::evaluate_expression(&expr)
+    eval_with(input, &Context::new())
 }
 
 pub fn eval_expression(expr: &Expression) -> Result<f64, CalcError> {
-    eval::evaluate_expression(expr)
+    eval_expression_with(expr, &Context::new())
+}
+
+/// Evaluates `input` exactly over `i64`. Fractional intermediate results
+/// (`7/2`) give `CalcError::NonInteger` and out-of-range ones (`2^63`) give
+/// `CalcError::IntegerOverflow`. Only `abs`, `sign`, `min` and `max` are
+/// available as functions.
+pub fn eval_i64(input: &str) -> Result<i64, CalcError> {
+    integer::evaluate_i64(&parse(input)?)
+}
+
+/// Prime factorization of `n`, written as an expression that evaluates back to
+/// `n`: `factor(360)` is `"2^3 * 3^2 * 5"`, a prime is returned alone, and
+/// negative numbers get a leading `-` (`"-2^2 * 3"`). 0 and 1 have no prime
+/// factors and come back as `"0"` and `"1"`.
+pub fn factor(n: i64) -> alloc::string::String {
+    integer::factor(n)
+}
+
+/// Evaluates `input` exactly over arbitrary-precision integers and returns the
+/// result in decimal: `2^64` is `"18446744073709551616"` and `100!` has all 158
+/// digits. Inexact division gives `CalcError::NonInteger`; as with `eval_i64`,
+/// literals must fit in `i64` and only `abs`, `sign`, `min` and `max` are
+/// available as functions. Results past about 90,000 digits give
+/// `CalcError::IntegerOverflow`. Requires the `bigint` feature.
+#[cfg(feature = "bigint")]
+pub fn eval_bigint(input: &str) -> Result<alloc::string::String, CalcError> {
+    bigint::evaluate_bigint(&parse(input)?).map(|n| alloc::format!("{n}"))
+}
+
+/// Evaluates exactly over fractions, so `1/3 + 1/3 + 1/3` is exactly `1`.
+/// Decimal literals are read as written (`0.1` is `1/10`). Irrational
+/// constants, functions other than `abs`, `sign`, `floor`, `ceil`, `min` and
+/// `max`, and fractional powers give `CalcError::NotRational`; results that do
+/// not fit an `i64` fraction give `CalcError::IntegerOverflow`.
+pub fn eval_rational(input: &str) -> Result<Rational, CalcError> {
+    rational::evaluate_rational(&parse(input)?)
+}
+
+/// Evaluates over complex numbers, with `i` as the imaginary unit:
+/// `sqrt(-1)` is `i` and `(1+2i)*(1-2i)` is `5`. `sqrt`, `ln`, `exp` and `abs`
+/// take complex arguments; other functions and operators beyond `+ - * / ^`
+/// need real operands and fail with `CalcError::ComplexArgument` otherwise.
+pub fn eval_complex(input: &str) -> Result<Complex, CalcError> {
+    complex::evaluate_complex(&parse(input)?)
+}
+
+/// Evaluates reverse Polish notation: `3 4 + 5 *` is `(3 + 4) * 5`. Unary
+/// minus and postfix percent are spelled `neg` and `percent`, and a variadic
+/// function takes its argument count in parentheses: `1 2 3 max(3)`. Missing operands give
+/// `CalcError::StackUnderflow`, unused ones `CalcError::LeftoverOperands`.
+pub fn eval_rpn(input: &str) -> Result<f64, CalcError> {
+    rpn::evaluate_rpn(input)
+}
+
+/// Converts infix tokens (as from `tokenize`) to postfix order with the
+/// shunting-yard algorithm, for stack machines and other backends. The output
+/// ends with `Token::Eof` and uses the conventions of `eval_rpn`: unary minus
+/// is `Token::Ident("neg")`, and a variadic call is followed by its argument
+/// count in parentheses. `let` bindings have no postfix form and are rejected.
+///
+/// ```
+/// use rustcalc::{Token, to_postfix, tokenize};
+///
+/// assert_eq!(
+///     to_postfix(&tokenize("1+2*3").unwrap()).unwrap(),
+///     vec![
+///         Token::Number(1),
+///         Token::Number(2),
+///         Token::Number(3),
+///         Token::Op("*"),
+///         Token::Op("+"),
+///         Token::Eof,
+///     ]
+/// );
+/// ```
+pub fn to_postfix(tokens: &[Token]) -> Result<Vec<Token>, CalcError> {
+    rpn::to_postfix(tokens)
+}
+
+pub fn eval_with(input: &str, ctx: &Context) -> Result<f64, CalcError> {
+    let expr = parse(input)?;
+    eval::evaluate_expression(&expr, ctx)
+}
+
+/// Parses and evaluates `input`, reporting the outcome as JSON: the input, the
+/// normalized expression, its serialized `Expression` tree under `ast`, and
+/// either `result` or an `error` object with its `category` (`"Eval"`), `kind`
+/// (`"DivideByZero"`), `message` and `position`. Requires the `serde_json`
+/// feature.
+#[cfg(feature = "serde_json")]
+pub fn eval_json(input: &str) -> alloc::string::String {
+    json::evaluate_to_json(input)
+}
+
+/// Parses `input` once for repeated evaluation with `CompiledExpr::eval`.
+///
+/// ```
+/// use rustcalc::{Context, compile};
+///
+/// let f = compile("x^2 + 1").unwrap();
+/// let mut ctx = Context::new();
+/// ctx.set("x", 3.0);
+/// assert_eq!(f.eval(&ctx).unwrap(), 10.0);
+/// ```
+pub fn compile(input: &str) -> Result<CompiledExpr, CalcError> {
+    parse(input).map(CompiledExpr::new)
+}
+
+/// Evaluates each of `inputs` against `ctx`, returning one result per input in
+/// order; an error only affects its own entry. Repeated inputs are parsed once.
+pub fn eval_batch(inputs: &[&str], ctx: &Context) -> Vec<Result<f64, CalcError>> {
+    let mut parsed: BTreeMap<&str, Result<Expression, CalcError>> = BTreeMap::new();
+    inputs
+        .iter()
+        .map(|&input| {
+            let expr = parsed.entry(input).or_insert_with(|| parse(input));
+            eval::evaluate_expression(expr.as_ref().map_err(Clone::clone)?, ctx)
+        })
+        .collect()
+}
+
+pub fn eval_expression_with(expr: &Expression, ctx: &Context) -> Result<f64, CalcError> {
+    eval::evaluate_expression(expr, ctx)
+}
+
+pub fn eval_statement(stmt: &Statement, ctx: &mut Context) -> Result<f64, CalcError> {
+    eval::evaluate_builtin_statement(stmt, ctx)
+}
+
+/// Checks, without evaluating, that `expr` only refers to builtin functions
+/// and constants, names in `known_vars`, and names bound by an enclosing `let`
+/// or iterated `sum`/`prod`. Reports the first unknown name, left to right, as
+/// `CalcError::UnknownIdentifier` or `CalcError::UnknownFunction`.
+///
+/// ```
+/// use std::collections::BTreeSet;
+///
+/// let expr = rustcalc::parse("x + sin(y)").unwrap();
+/// let known: BTreeSet<String> = ["x".to_string()].into();
+/// assert_eq!(
+///     rustcalc::validate(&expr, &known),
+///     Err(rustcalc::CalcError::UnknownIdentifier("y".to_string()))
+/// );
+/// ```
+pub fn validate(
+    expr: &Expression,
+    known_vars: &BTreeSet<alloc::string::String>,
+) -> Result<(), CalcError> {
+    eval::validate(expr, known_vars)
+}
+
+/// Runs each statement in order against `ctx` and returns the last value.
+pub fn eval_program(statements: &[Statement], ctx: &mut Context) -> Result<f64, CalcError> {
+    eval::evaluate_builtin_program(statements, ctx)
 }
 
 #[cfg(test)]
@@ -42,11 +288,11 @@ mod tests {
         let input = "12 + 34 - 5";
         let expected_tokens = vec![
             Token::Number(12),
-            Token::Op('+'),
+            Token::Op("+"),
             Token::Number(34),
-            Token::Op('-'),
+            Token::Op("-"),
             Token::Number(5),
-            Token::EOF,
+            Token::Eof,
         ];
         assert_eq!(crate::lexer::tokenize(input).unwrap(), expected_tokens);
     }
@@ -55,16 +301,16 @@ mod tests {
     fn test_parse_tokens_structure() {
         let tokens = vec![
             Token::Number(12),
-            Token::Op('+'),
+            Token::Op("+"),
             Token::Number(34),
-            Token::Op('-'),
+            Token::Op("-"),
             Token::Number(5),
-            Token::EOF,
+            Token::Eof,
         ];
         let expected_expression = Expression::BinaryOp {
-            op: '-',
+            op: "-",
             left: Box::new(Expression::BinaryOp {
-                op: '+',
+                op: "+",
                 left: Box::new(Expression::Number(12.0)),
                 right: Box::new(Expression::Number(34.0)),
             }),
@@ -77,14 +323,14 @@ mod tests {
     fn test_parse_tokens_parentheses_after_plus() {
         let tokens = vec![
             Token::Number(1),
-            Token::Op('+'),
+            Token::Op("+"),
             Token::OpenParen,
             Token::Number(1),
             Token::CloseParen,
-            Token::EOF,
+            Token::Eof,
         ];
         let expected_expression = Expression::BinaryOp {
-            op: '+',
+            op: "+",
             left: Box::new(Expression::Number(1.0)),
             right: Box::new(Expression::Parenthesis(Box::new(Expression::Number(1.0)))),
         };
@@ -93,9 +339,9 @@ mod tests {
 
     #[test]
     fn test_parse_tokens_unary_minus() {
-        let tokens = vec![Token::Op('-'), Token::Number(1), Token::EOF];
+        let tokens = vec![Token::Op("-"), Token::Number(1), Token::Eof];
         let expected_expression = Expression::UnaryOp {
-            op: '-',
+            op: "-",
             expr: Box::new(Expression::Number(1.0)),
         };
         assert_eq!(crate::parser::parse_tokens(&tokens).unwrap(), expected_expression);
@@ -105,16 +351,16 @@ mod tests {
     fn test_parse_tokens_plus_then_unary_minus() {
         let tokens = vec![
             Token::Number(1),
-            Token::Op('+'),
-            Token::Op('-'),
+            Token::Op("+"),
+            Token::Op("-"),
             Token::Number(1),
-            Token::EOF,
+            Token::Eof,
         ];
         let expected_expression = Expression::BinaryOp {
-            op: '+',
+            op: "+",
             left: Box::new(Expression::Number(1.0)),
             right: Box::new(Expression::UnaryOp {
-                op: '-',
+                op: "-",
                 expr: Box::new(Expression::Number(1.0)),
             }),
         };
@@ -199,4 +445,3048 @@ mod tests {
             }
         );
     }
+
+    #[test]
+    fn test_parse_function_call_args() {
+        assert_eq!(
+            parse("foo()").unwrap(),
+            Expression::FunctionCall {
+                name: "foo".to_string(),
+                args: vec![],
+            }
+        );
+        assert_eq!(
+            parse("max(1,2)").unwrap(),
+            Expression::FunctionCall {
+                name: "max".to_string(),
+                args: vec![Expression::Number(1.0), Expression::Number(2.0)],
+            }
+        );
+    }
+
+    #[test]
+    fn test_eval_function_call_arg_counts() {
+        assert_close(eval_input("max(4)").unwrap(), 4.0);
+        assert_close(eval_input("max(4,7)").unwrap(), 7.0);
+        assert_close(eval_input("min(4,7,-1)").unwrap(), -1.0);
+    }
+
+    #[test]
+    fn test_eval_nested_function_calls() {
+        assert_close(eval_input("max(min(1,2),3)").unwrap(), 3.0);
+        assert_close(eval_input("min(max(1,2),sqrt(16))").unwrap(), 2.0);
+    }
+
+    #[test]
+    fn test_error_trailing_comma_in_call() {
+        assert_eq!(
+            parse("max(1,2,)").unwrap_err(),
+            CalcError::ExpectedPrimary {
+                got: Token::CloseParen,
+                pos: 8
+            }
+        );
+    }
+
+    #[test]
+    fn test_parse_input_tokens_decimal() {
+        assert_eq!(
+            crate::lexer::tokenize("1.05").unwrap(),
+            vec![
+                Token::Number(1),
+                Token::DecimalPoint,
+                Token::Fraction("05".to_string()),
+                Token::Eof,
+            ]
+        );
+    }
+
+    #[test]
+    fn test_eval_decimal_leading_zeros() {
+        let cases = [
+            ("1.05", 1.05),
+            ("0.001", 0.001),
+            ("3.00000001", 3.00000001),
+            ("2.5", 2.5),
+        ];
+        for (input, expected) in cases {
+            let actual = eval_input(input).unwrap();
+            assert!(
+                (actual - expected).abs() < 1e-12,
+                "{input}: expected {expected}, got {actual}"
+            );
+        }
+    }
+
+    #[test]
+    fn test_trailing_decimal_point() {
+        assert_eq!(eval_input("5.").unwrap(), 5.0);
+        assert_eq!(eval_input("5.+1==6").unwrap(), 1.0);
+        assert_eq!(eval_input("1.+2").unwrap(), 3.0);
+        assert_eq!(eval_input("5.3").unwrap(), 5.3);
+        assert_eq!(eval_input("(5.)").unwrap(), 5.0);
+        assert_eq!(eval_input("max(5., 2)").unwrap(), 5.0);
+        assert_eq!(eval_input("5.!").unwrap(), 120.0);
+        assert_eq!(eval_rpn("5. 1 +").unwrap(), 6.0);
+        assert_eq!(parse("5.").unwrap().to_string(), "5");
+        // A point can end one literal or start the next, not both.
+        assert!(matches!(
+            eval_input("5..5"),
+            Err(CalcError::UnexpectedTokenAfterExpression { .. })
+        ));
+        // Nor is it a multiplication, like any two literals in a row.
+        assert!(matches!(
+            eval_input("5. 3"),
+            Err(CalcError::UnexpectedTokenAfterExpression { .. })
+        ));
+        // An exponent can follow it.
+        assert_eq!(eval_input("5.e3").unwrap(), 5000.0);
+        assert_eq!(eval_input("1.E-2").unwrap(), 0.01);
+        assert_eq!(eval_input("2.e").unwrap(), 2.0 * std::f64::consts::E);
+    }
+
+    #[test]
+    fn test_eval_trig() {
+        assert_close(eval_input("sin(0)").unwrap(), 0.0);
+        assert_close(eval_input("cos(0)").unwrap(), 1.0);
+        assert_close(eval_input("tan(0)").unwrap(), 0.0);
+        assert_close(eval_input("sin(pi/2)").unwrap(), 1.0);
+        assert_close(eval_input("cos(pi)").unwrap(), -1.0);
+        assert_close(eval_input("tan(pi/4)").unwrap(), 1.0);
+    }
+
+    #[test]
+    fn test_eval_inverse_trig() {
+        assert_close(eval_input("asin(1)").unwrap(), std::f64::consts::FRAC_PI_2);
+        assert_close(eval_input("acos(1)").unwrap(), 0.0);
+        assert_close(eval_input("atan(1)").unwrap(), std::f64::consts::FRAC_PI_4);
+        assert_close(
+            eval_input("atan2(1,1)").unwrap(),
+            std::f64::consts::FRAC_PI_4,
+        );
+        assert_close(
+            eval_input("atan2(1,-1)").unwrap(),
+            3.0 * std::f64::consts::FRAC_PI_4,
+        );
+    }
+
+    #[test]
+    fn test_error_inverse_trig_domain() {
+        assert_eq!(
+            eval_input("asin(2)").unwrap_err(),
+            CalcError::DomainError {
+                name: "asin".to_string(),
+                arg: 2.0
+            }
+        );
+        assert_eq!(
+            eval_input("acos(-1.5)").unwrap_err(),
+            CalcError::DomainError {
+                name: "acos".to_string(),
+                arg: -1.5
+            }
+        );
+    }
+
+    #[test]
+    fn test_eval_logarithms() {
+        assert_close(eval_input("ln(e)").unwrap(), 1.0);
+        assert_close(eval_input("log10(1000)").unwrap(), 3.0);
+        assert_close(eval_input("log2(8)").unwrap(), 3.0);
+        assert_close(eval_input("log(100)").unwrap(), 2.0);
+        assert_close(eval_input("log(2, 8)").unwrap(), 3.0);
+    }
+
+    #[test]
+    fn test_error_logarithm_domain() {
+        assert_eq!(
+            eval_input("ln(0)").unwrap_err(),
+            CalcError::DomainError {
+                name: "ln".to_string(),
+                arg: 0.0
+            }
+        );
+        assert_eq!(
+            eval_input("log2(-8)").unwrap_err(),
+            CalcError::DomainError {
+                name: "log2".to_string(),
+                arg: -8.0
+            }
+        );
+        assert_eq!(
+            eval_input("log(1, 8)").unwrap_err(),
+            CalcError::DomainError {
+                name: "log".to_string(),
+                arg: 1.0
+            }
+        );
+    }
+
+    #[test]
+    fn test_eval_rounding() {
+        assert_eq!(eval_input("round(2.5)").unwrap(), 3.0);
+        assert_eq!(eval_input("round(-2.5)").unwrap(), -3.0);
+        assert_eq!(eval_input("round(2.4)").unwrap(), 2.0);
+        assert_eq!(eval_input("floor(-1.5)").unwrap(), -2.0);
+        assert_eq!(eval_input("ceil(1.1)").unwrap(), 2.0);
+        assert_eq!(eval_input("trunc(-1.9)").unwrap(), -1.0);
+    }
+
+    #[test]
+    fn test_eval_abs_and_sign() {
+        assert_eq!(eval_input("abs(-3.5)").unwrap(), 3.5);
+        assert_eq!(eval_input("abs(2)").unwrap(), 2.0);
+        assert_eq!(eval_input("abs(0)").unwrap(), 0.0);
+        assert_eq!(eval_input("sign(-7)").unwrap(), -1.0);
+        assert_eq!(eval_input("sign(7)").unwrap(), 1.0);
+        assert_eq!(eval_input("sign(0)").unwrap(), 0.0);
+        assert_eq!(eval_input("sign(-0)").unwrap(), 0.0);
+    }
+
+    #[test]
+    fn test_eval_exp() {
+        assert_close(eval_input("exp(0)").unwrap(), 1.0);
+        assert_close(eval_input("exp(1)").unwrap(), std::f64::consts::E);
+        assert_close(eval_input("exp(ln(5))").unwrap(), 5.0);
+    }
+
+    #[test]
+    fn test_eval_modulo() {
+        assert_eq!(eval_input("5 % 3").unwrap(), 2.0);
+        assert_eq!(eval_input("-5 % 3").unwrap(), -2.0);
+        assert_eq!(eval_input("5 % (-3)").unwrap(), 2.0);
+        assert_close(eval_input("5.5 % 2").unwrap(), 1.5);
+        assert_eq!(eval_input("1 + 7 % 4 * 2").unwrap(), 7.0);
+    }
+
+    #[test]
+    fn test_error_modulo_by_zero() {
+        assert_eq!(eval_input("5 % 0").unwrap_err(), CalcError::DivideByZero);
+    }
+
+    #[test]
+    fn test_eval_tau_inf_nan() {
+        assert_close(eval_input("tau").unwrap(), eval_input("2*pi").unwrap());
+        let inf = eval_input("inf").unwrap();
+        assert!(inf.is_infinite() && inf > 0.0);
+        assert!(eval_input("-inf").unwrap().is_infinite());
+        assert!(eval_input("nan").unwrap().is_nan());
+    }
+
+    #[test]
+    fn test_eval_routes_through_builtins() {
+        for name in ["pi", "e", "tau", "PI", "Tau"] {
+            assert_eq!(
+                eval_input(name).unwrap(),
+                crate::builtins::eval_constant(name).unwrap()
+            );
+        }
+        for (input, name, args) in [
+            ("exp(2)", "exp", vec![2.0]),
+            ("ATAN2(1,2)", "atan2", vec![1.0, 2.0]),
+            ("log(3,81)", "log", vec![3.0, 81.0]),
+        ] {
+            assert_eq!(
+                eval_input(input).unwrap(),
+                crate::builtins::eval_function(name, &args).unwrap()
+            );
+        }
+    }
+
+    #[test]
+    fn test_pemdas_left_associative() {
+        assert_eq!(eval_input("1-2-3").unwrap(), -4.0);
+        assert_eq!(eval_input("8/4/2").unwrap(), 1.0);
+        assert_eq!(eval_input("7%4%2").unwrap(), 1.0);
+    }
+
+    #[test]
+    fn test_parse_binding_power_structure() {
+        // 1-2-3 == (1-2)-3
+        assert_eq!(
+            parse("1-2-3").unwrap(),
+            Expression::BinaryOp {
+                op: "-",
+                left: Box::new(Expression::BinaryOp {
+                    op: "-",
+                    left: Box::new(Expression::Number(1.0)),
+                    right: Box::new(Expression::Number(2.0)),
+                }),
+                right: Box::new(Expression::Number(3.0)),
+            }
+        );
+        // 2^3^2 == 2^(3^2)
+        assert_eq!(
+            parse("2^3^2").unwrap(),
+            Expression::BinaryOp {
+                op: "^",
+                left: Box::new(Expression::Number(2.0)),
+                right: Box::new(Expression::BinaryOp {
+                    op: "^",
+                    left: Box::new(Expression::Number(3.0)),
+                    right: Box::new(Expression::Number(2.0)),
+                }),
+            }
+        );
+        // -2^2 == -(2^2)
+        assert_eq!(
+            parse("-2^2").unwrap(),
+            Expression::UnaryOp {
+                op: "-",
+                expr: Box::new(Expression::BinaryOp {
+                    op: "^",
+                    left: Box::new(Expression::Number(2.0)),
+                    right: Box::new(Expression::Number(2.0)),
+                }),
+            }
+        );
+    }
+
+    #[test]
+    fn test_pemdas_mixed_precedence() {
+        assert_eq!(eval_input("2+3*4^2").unwrap(), 50.0);
+        assert_eq!(eval_input("2*3^2").unwrap(), 18.0);
+        assert_eq!(eval_input("-3*-2").unwrap(), 6.0);
+        assert_eq!(eval_input("2^-1").unwrap(), 0.5);
+    }
+
+    #[test]
+    fn test_parse_unary_plus_and_nested_negation() {
+        assert_eq!(
+            parse("+1").unwrap(),
+            Expression::UnaryOp {
+                op: "+",
+                expr: Box::new(Expression::Number(1.0)),
+            }
+        );
+        assert_eq!(
+            parse("-(1)").unwrap(),
+            Expression::UnaryOp {
+                op: "-",
+                expr: Box::new(Expression::Parenthesis(Box::new(Expression::Number(1.0)))),
+            }
+        );
+        assert_eq!(
+            parse("--1").unwrap(),
+            Expression::UnaryOp {
+                op: "-",
+                expr: Box::new(Expression::UnaryOp {
+                    op: "-",
+                    expr: Box::new(Expression::Number(1.0)),
+                }),
+            }
+        );
+        assert_eq!(eval_input("--1").unwrap(), 1.0);
+        assert_eq!(eval_input("+-2").unwrap(), -2.0);
+    }
+
+    #[test]
+    fn test_eval_with_context_variables() {
+        let mut ctx = Context::new();
+        ctx.set("x", 3.0);
+        ctx.set("y", 4.0);
+        assert_eq!(eval_with("x*x", &ctx).unwrap(), 9.0);
+        assert_eq!(eval_with("x^2 + y", &ctx).unwrap(), 13.0);
+        assert_eq!(
+            eval_with("z + 1", &ctx).unwrap_err(),
+            CalcError::UnknownIdentifier("z".to_string())
+        );
+        assert_close(eval_with("pi", &ctx).unwrap(), std::f64::consts::PI);
+    }
+
+    #[test]
+    fn test_eval_context_shadows_constants() {
+        let mut ctx = Context::new();
+        ctx.set("e", 2.0);
+        assert_eq!(eval_with("e*3", &ctx).unwrap(), 6.0);
+        assert_eq!(ctx.remove("e"), Some(2.0));
+        assert_close(eval_with("e", &ctx).unwrap(), std::f64::consts::E);
+    }
+
+    #[test]
+    fn test_parse_assignment_statement() {
+        assert_eq!(
+            parse_statement("x = 3 + 4").unwrap(),
+            Statement::Assignment {
+                name: "x".to_string(),
+                expr: Expression::BinaryOp {
+                    op: "+",
+                    left: Box::new(Expression::Number(3.0)),
+                    right: Box::new(Expression::Number(4.0)),
+                },
+            }
+        );
+        assert_eq!(
+            parse_statement("x + 1").unwrap(),
+            Statement::Expression(parse("x + 1").unwrap())
+        );
+    }
+
+    #[test]
+    fn test_eval_assignment_then_use() {
+        let mut ctx = Context::new();
+        let stmt = parse_statement("x = 5").unwrap();
+        assert_eq!(eval_statement(&stmt, &mut ctx).unwrap(), 5.0);
+        let stmt = parse_statement("x + 1").unwrap();
+        assert_eq!(eval_statement(&stmt, &mut ctx).unwrap(), 6.0);
+        let stmt = parse_statement("x = x * 2").unwrap();
+        assert_eq!(eval_statement(&stmt, &mut ctx).unwrap(), 10.0);
+        assert_eq!(ctx.get("x"), Some(10.0));
+    }
+
+    #[test]
+    fn test_error_assign_reserved_name() {
+        let mut ctx = Context::new();
+        for name in ["pi", "E"] {
+            let stmt = parse_statement(&format!("{name} = 3")).unwrap();
+            assert_eq!(
+                eval_statement(&stmt, &mut ctx).unwrap_err(),
+                CalcError::ReservedName(name.to_string())
+            );
+        }
+    }
+
+    #[test]
+    fn test_error_misplaced_equals() {
+        assert_eq!(
+            parse_statement("1 = 2").unwrap_err(),
+            CalcError::UnexpectedTokenAfterExpression {
+                got: Token::Equals,
+                pos: 2
+            }
+        );
+        assert!(parse("x = 1").is_err());
+    }
+
+    #[test]
+    fn test_calculator_register_custom_function() {
+        let mut calc = Calculator::new();
+        calc.register_function("dbl", 1, Some(1), |args| Ok(args[0] * 2.0));
+        assert_eq!(calc.eval("dbl(21)").unwrap(), 42.0);
+        assert_eq!(calc.eval("DBL(sqrt(4)) + max(1, 2)").unwrap(), 6.0);
+        assert_eq!(
+            calc.eval("dbl(1, 2)").unwrap_err(),
+            CalcError::WrongArity {
+                name: "dbl".to_string(),
+                expected: 1,
+                got: 2
+            }
+        );
+        assert_eq!(
+            eval_input("dbl(21)").unwrap_err(),
+            CalcError::UnknownFunction("dbl".to_string())
+        );
+    }
+
+    #[test]
+    fn test_calculator_override_and_context() {
+        let mut calc = Calculator::new();
+        calc.register_function("sqrt", 1, Some(1), |_| Ok(-1.0));
+        calc.register_function("celsius_to_f", 1, Some(1), |args| {
+            Ok(args[0] * 9.0 / 5.0 + 32.0)
+        });
+        assert_eq!(calc.eval("sqrt(9)").unwrap(), -1.0);
+
+        let mut ctx = Context::new();
+        let stmt = parse_statement("t = celsius_to_f(100)").unwrap();
+        assert_eq!(calc.eval_statement(&stmt, &mut ctx).unwrap(), 212.0);
+        assert_eq!(calc.eval_with("t - 12", &ctx).unwrap(), 200.0);
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn test_serde_round_trip() {
+        let expr = parse("2*(3+4)").unwrap();
+        let json = serde_json::to_string(&expr).unwrap();
+        assert!(
+            json.starts_with(r#"{"type":"BinaryOp","value":{"op":"*""#),
+            "{json}"
+        );
+        let back: Expression = serde_json::from_str(&json).unwrap();
+        assert_eq!(back, expr);
+        assert_eq!(
+            eval_expression(&back).unwrap(),
+            eval_expression(&expr).unwrap()
+        );
+
+        let stmt = parse_statement("x = max(1, 2)").unwrap();
+        let json = serde_json::to_string(&stmt).unwrap();
+        assert_eq!(serde_json::from_str::<Statement>(&json).unwrap(), stmt);
+    }
+
+    #[test]
+    fn test_display_round_trip() {
+        let inputs = [
+            "1+2*3",
+            "(1+2)*3",
+            "2^3^2",
+            "(2^3)^2",
+            "-2^2",
+            "(-2)^2",
+            "1-(2-3)",
+            "1-2-3",
+            "-(1+2)",
+            "max(1, min(2,3), sqrt(4))",
+            "foo()",
+            "2*pi + e % 3",
+            "1.05 / x",
+            "--1",
+        ];
+        for input in inputs {
+            let expr = parse(input).unwrap();
+            let printed = expr.to_string();
+            assert_eq!(
+                parse(&printed).unwrap(),
+                expr,
+                "{input} printed as {printed}"
+            );
+        }
+        assert_eq!(parse("1+2*3").unwrap().to_string(), "1 + 2 * 3");
+        assert_eq!(parse("max(1,2)").unwrap().to_string(), "max(1, 2)");
+
+        // Long chains print without recursing.
+        let deep = parse(&format!("1{}", "*2-1".repeat(100_000))).unwrap();
+        let printed = deep.to_string();
+        assert!(printed.starts_with("1 * 2 - 1 * 2 - 1"));
+        assert_eq!(parse(&printed).unwrap(), deep);
+    }
+
+    #[test]
+    fn test_display_inserts_required_parens() {
+        let num = |n: f64| Box::new(Expression::Number(n));
+        let bin = |op, left, right| Expression::BinaryOp { op, left, right };
+        assert_eq!(
+            bin("+", num(1.0), Box::new(bin("*", num(2.0), num(3.0)))).to_string(),
+            "1 + 2 * 3"
+        );
+        assert_eq!(
+            bin("*", Box::new(bin("+", num(1.0), num(2.0))), num(3.0)).to_string(),
+            "(1 + 2) * 3"
+        );
+        assert_eq!(
+            bin("-", num(1.0), Box::new(bin("-", num(2.0), num(3.0)))).to_string(),
+            "1 - (2 - 3)"
+        );
+        assert_eq!(
+            bin("^", Box::new(bin("^", num(2.0), num(3.0))), num(2.0)).to_string(),
+            "(2 ^ 3) ^ 2"
+        );
+        let neg_two = Expression::UnaryOp {
+            op: "-",
+            expr: num(2.0),
+        };
+        let pow = bin("^", Box::new(neg_two), num(2.0));
+        assert_eq!(pow.to_string(), "(-2) ^ 2");
+        assert_eq!(
+            eval(&pow.to_string()).unwrap(),
+            eval_expression(&pow).unwrap()
+        );
+        assert_eq!(parse_statement("x = 1+2").unwrap().to_string(), "x = 1 + 2");
+    }
+
+    #[test]
+    fn test_parse_input_tokens_exponent() {
+        assert_eq!(
+            crate::lexer::tokenize("2.5e-2").unwrap(),
+            vec![
+                Token::Number(2),
+                Token::DecimalPoint,
+                Token::Fraction("5".to_string()),
+                Token::Exponent("-2".to_string()),
+                Token::Eof,
+            ]
+        );
+        assert_eq!(
+            crate::lexer::tokenize("2e").unwrap(),
+            vec![Token::Number(2), Token::Ident("e".to_string()), Token::Eof]
+        );
+    }
+
+    #[test]
+    fn test_eval_exponent_notation() {
+        assert_eq!(eval_input("1e3").unwrap(), 1000.0);
+        assert_eq!(eval_input("2.5e-2").unwrap(), 0.025);
+        assert_eq!(eval_input("1E2").unwrap(), 100.0);
+        assert_eq!(eval_input("2E-4").unwrap(), 0.0002);
+        assert_eq!(eval_input("1.5e+3 + 1").unwrap(), 1501.0);
+        assert_close(eval_input("e*1e1").unwrap(), 10.0 * std::f64::consts::E);
+    }
+
+    #[test]
+    fn test_eval_exponent_without_digits_is_constant_e() {
+        assert_close(eval_input("2e").unwrap(), 2.0 * std::f64::consts::E);
+    }
+
+    #[test]
+    fn test_eval_digit_separators() {
+        assert_eq!(eval_input("1_000 + 1").unwrap(), 1001.0);
+        assert_eq!(eval_input("1_000_000").unwrap(), 1_000_000.0);
+        assert_close(eval_input("1.234_56").unwrap(), 1.23456);
+        assert_eq!(eval_input("1e1_0").unwrap(), 1e10);
+        let mut ctx = Context::new();
+        ctx.set("_x_1", 2.0);
+        assert_eq!(eval_with("_x_1 * 1_0", &ctx).unwrap(), 20.0);
+    }
+
+    #[test]
+    fn test_error_misplaced_digit_separators() {
+        for (input, literal, pos) in [
+            ("_5", "_5", 0),
+            ("5_", "5_", 0),
+            ("5__0", "5__0", 0),
+            ("1._5", "_5", 2),
+            ("1 + 1.5_", "5_", 6),
+        ] {
+            assert_eq!(
+                crate::lexer::tokenize(input).unwrap_err(),
+                CalcError::InvalidDigitSeparator {
+                    literal: literal.to_string(),
+                    pos
+                },
+                "{input}"
+            );
+        }
+    }
+
+    #[test]
+    fn test_eval_large_integer_literals() {
+        assert_eq!(eval_input("3000000000").unwrap(), 3e9);
+        assert_eq!(eval_input("18446744073709551615").unwrap(), u64::MAX as f64);
+    }
+
+    #[test]
+    fn test_error_integer_literal_overflow() {
+        let digits = "9".repeat(400);
+        assert_eq!(
+            eval_input(&digits).unwrap_err(),
+            CalcError::NumberOverflow {
+                literal: digits.clone(),
+                pos: 0
+            }
+        );
+        assert_eq!(
+            eval_input("18446744073709551616").unwrap_err(),
+            CalcError::NumberOverflow {
+                literal: "18446744073709551616".to_string(),
+                pos: 0
+            }
+        );
+    }
+
+    #[test]
+    fn test_error_positions() {
+        let err = parse("1 @").unwrap_err();
+        assert_eq!(err, CalcError::UnexpectedChar { ch: '@', pos: 2 });
+        assert_eq!(err.render("1 @"), "1 @\n  ^ unexpected character: @");
+
+        let err = parse("(1 + 2").unwrap_err();
+        assert_eq!(err, CalcError::UnclosedParen { pos: 0 });
+        assert_eq!(err.position(), Some(0));
+
+        assert_eq!(parse("(").unwrap_err().position(), Some(1));
+        assert_eq!(parse("1 + 2 3").unwrap_err().position(), Some(6));
+        assert_eq!(eval_input("1/0").unwrap_err().position(), None);
+        assert_eq!(
+            eval_input("1/0").unwrap_err().render("1/0"),
+            "division by zero"
+        );
+    }
+
+    #[test]
+    fn test_eval_deeply_nested_expression() {
+        let input = format!("1{}", "+1".repeat(50_000));
+        assert_eq!(eval_input(&input).unwrap(), 50_001.0);
+        assert_eq!(eval_i64(&input).unwrap(), 50_001);
+        assert_eq!(eval_rational(&input).unwrap(), Rational::from(50_001));
+        assert_eq!(eval_complex(&input).unwrap(), Complex::from(50_001.0));
+        #[cfg(feature = "bigint")]
+        assert_eq!(eval_bigint(&input).unwrap(), "50001");
+
+        let input = format!("2{}", "-1".repeat(50_000));
+        assert_eq!(eval_input(&input).unwrap(), -49_998.0);
+        assert_eq!(eval_i64(&input).unwrap(), -49_998);
+        assert_eq!(eval_rational(&input).unwrap(), Rational::from(-49_998));
+        assert_eq!(eval_complex(&input).unwrap(), Complex::from(-49_998.0));
+        #[cfg(feature = "bigint")]
+        assert_eq!(eval_bigint(&input).unwrap(), "-49998");
+    }
+
+    #[test]
+    fn test_clone_and_debug_deep_expression() {
+        let deep = parse(&format!("1{}", "+1".repeat(200_000))).unwrap();
+        let copy = deep.clone();
+        assert_eq!(copy, deep);
+        assert_eq!(copy.simplify(), Expression::Number(200_001.0));
+        let debug = format!("{deep:?}");
+        assert!(debug.starts_with("BinaryOp { op: \"+\", left: BinaryOp {"));
+    }
+
+    #[test]
+    fn test_debug_matches_derived_format() {
+        let expr = parse("let a = -x in max(pi, (2.5), 1 + a, f())").unwrap();
+        assert_eq!(
+            format!("{expr:?}"),
+            "LetBinding { name: \"a\", value: UnaryOp { op: \"-\", expr: Identifier(\"x\") }, \
+             body: FunctionCall { name: \"max\", args: [Constant { name: \"pi\", \
+             value: 3.141592653589793 }, Parenthesis(Number(2.5)), BinaryOp { op: \"+\", \
+             left: Number(1.0), right: Identifier(\"a\") }, FunctionCall { name: \"f\", \
+             args: [] }] } }"
+        );
+        let pretty = r#"LetBinding {
+    name: "a",
+    value: UnaryOp {
+        op: "-",
+        expr: Identifier(
+            "x",
+        ),
+    },
+    body: FunctionCall {
+        name: "max",
+        args: [
+            Constant {
+                name: "pi",
+                value: 3.141592653589793,
+            },
+            Parenthesis(
+                Number(
+                    2.5,
+                ),
+            ),
+            BinaryOp {
+                op: "+",
+                left: Number(
+                    1.0,
+                ),
+                right: Identifier(
+                    "a",
+                ),
+            },
+            FunctionCall {
+                name: "f",
+                args: [],
+            },
+        ],
+    },
+}"#;
+        assert_eq!(format!("{expr:#?}"), pretty);
+    }
+
+    #[test]
+    fn test_eval_error_order_preserved() {
+        assert_eq!(eval_input("1/0 + a").unwrap_err(), CalcError::DivideByZero);
+        assert_eq!(
+            eval_input("a + 1/0").unwrap_err(),
+            CalcError::UnknownIdentifier("a".to_string())
+        );
+        assert_eq!(
+            eval_input("max(ln(0), 1/0)").unwrap_err(),
+            CalcError::DomainError {
+                name: "ln".to_string(),
+                arg: 0.0
+            }
+        );
+    }
+
+    #[test]
+    fn test_error_nesting_too_deep() {
+        let input = "(".repeat(10_000);
+        assert_eq!(
+            parse(&input).unwrap_err(),
+            CalcError::NestingTooDeep {
+                max_depth: 256,
+                pos: 256
+            }
+        );
+        let input = format!("{}1", "-".repeat(10_000));
+        assert!(matches!(
+            eval_input(&input).unwrap_err(),
+            CalcError::NestingTooDeep { .. }
+        ));
+        let input = format!("{}1{}", "(".repeat(100), ")".repeat(100));
+        assert_eq!(eval_input(&input).unwrap(), 1.0);
+
+        // Comma-separated `let` bindings nest, one level each.
+        let bindings = |n: usize| {
+            let names: Vec<String> = (0..n).map(|i| format!("a{i} = 1")).collect();
+            format!("let {} in a0", names.join(", "))
+        };
+        assert!(matches!(
+            parse(&bindings(10_000)).unwrap_err(),
+            CalcError::NestingTooDeep { max_depth: 256, .. }
+        ));
+        assert_eq!(eval(&bindings(100)).unwrap(), 1.0);
+        assert_eq!(eval_i64(&bindings(100)).unwrap(), 1);
+    }
+
+    #[test]
+    fn test_calculator_max_depth() {
+        let mut calc = Calculator::new();
+        calc.set_max_depth(3);
+        assert_eq!(calc.eval("((1))").unwrap(), 1.0);
+        assert_eq!(
+            calc.eval("(((1)))").unwrap_err(),
+            CalcError::NestingTooDeep {
+                max_depth: 3,
+                pos: 3
+            }
+        );
+        assert_eq!(calc.max_depth(), 3);
+    }
+
+    #[test]
+    fn test_eval_implicit_multiplication() {
+        assert_close(eval_input("2pi").unwrap(), 2.0 * std::f64::consts::PI);
+        assert_eq!(eval_input("3(4+5)").unwrap(), 27.0);
+        assert_eq!(eval_input("(1+2)(3+4)").unwrap(), 21.0);
+        assert_eq!(eval_input("(1+2)3").unwrap(), 9.0);
+        assert_eq!(eval_input("2(3)^2").unwrap(), 18.0);
+        assert_eq!(eval_input("1 + 2(3)").unwrap(), 7.0);
+        let mut ctx = Context::new();
+        ctx.set("x", 4.0);
+        ctx.set("y", 5.0);
+        assert_eq!(eval_with("3x + x y", &ctx).unwrap(), 32.0);
+    }
+
+    #[test]
+    fn test_parse_implicit_multiplication_keeps_calls() {
+        assert_eq!(
+            parse("2sin(0)").unwrap(),
+            Expression::BinaryOp {
+                op: "*",
+                left: Box::new(Expression::Number(2.0)),
+                right: Box::new(Expression::FunctionCall {
+                    name: "sin".to_string(),
+                    args: vec![Expression::Number(0.0)],
+                }),
+            }
+        );
+        assert_eq!(
+            parse("sin(0)").unwrap(),
+            Expression::FunctionCall {
+                name: "sin".to_string(),
+                args: vec![Expression::Number(0.0)],
+            }
+        );
+        assert_eq!(eval_input("sqrt(4)sqrt(9)").unwrap(), 6.0);
+    }
+
+    #[test]
+    fn test_error_adjacent_number_literals() {
+        assert_eq!(
+            parse("2 3").unwrap_err(),
+            CalcError::UnexpectedTokenAfterExpression {
+                got: Token::Number(3),
+                pos: 2
+            }
+        );
+    }
+
+    #[test]
+    fn test_calculator_angle_mode() {
+        let mut calc = Calculator::new();
+        assert_eq!(calc.angle_mode(), AngleMode::Radians);
+        assert_close(calc.eval("sin(pi/2)").unwrap(), 1.0);
+
+        calc.set_angle_mode(AngleMode::Degrees);
+        assert_close(calc.eval("sin(90)").unwrap(), 1.0);
+        assert_close(calc.eval("cos(180)").unwrap(), -1.0);
+        assert_close(calc.eval("tan(45)").unwrap(), 1.0);
+        assert_close(calc.eval("asin(1)").unwrap(), 90.0);
+        assert_close(calc.eval("acos(0)").unwrap(), 90.0);
+        assert_close(calc.eval("atan(1)").unwrap(), 45.0);
+        assert_close(calc.eval("atan2(1, 1)").unwrap(), 45.0);
+
+        // Non-trig functions and the free functions are unaffected.
+        assert_close(calc.eval("sqrt(16) + exp(0)").unwrap(), 5.0);
+        assert_close(eval_input("sin(pi/2)").unwrap(), 1.0);
+    }
+
+    #[test]
+    fn test_calculator_angle_mode_skips_custom_functions() {
+        let mut calc = Calculator::new();
+        calc.set_angle_mode(AngleMode::Degrees);
+        calc.register_function("sin", 1, Some(1), |args| Ok(args[0]));
+        assert_eq!(calc.eval("sin(90)").unwrap(), 90.0);
+    }
+
+    #[test]
+    fn test_parse_input_tokens_comparisons() {
+        assert_eq!(
+            crate::lexer::tokenize("1<=2!=3==4").unwrap(),
+            vec![
+                Token::Number(1),
+                Token::Op("<="),
+                Token::Number(2),
+                Token::Op("!="),
+                Token::Number(3),
+                Token::Op("=="),
+                Token::Number(4),
+                Token::Eof,
+            ]
+        );
+    }
+
+    #[test]
+    fn test_eval_comparisons() {
+        assert_eq!(eval_input("3 > 2").unwrap(), 1.0);
+        assert_eq!(eval_input("3 < 2").unwrap(), 0.0);
+        assert_eq!(eval_input("2 <= 2").unwrap(), 1.0);
+        assert_eq!(eval_input("1 >= 2").unwrap(), 0.0);
+        assert_eq!(eval_input("2 == 2").unwrap(), 1.0);
+        assert_eq!(eval_input("2 != 2").unwrap(), 0.0);
+        assert_eq!(eval_input("3 > 2 == 1").unwrap(), 1.0);
+        assert_eq!(eval_input("1 < 0 == 0").unwrap(), 1.0);
+        assert_eq!(eval_input("1 + 1 == 2").unwrap(), 1.0);
+        assert_eq!(eval_input("2 * 3 > 5").unwrap(), 1.0);
+        assert_eq!(eval_input("inf > 1e300").unwrap(), 1.0);
+    }
+
+    #[test]
+    fn test_eval_equality_is_exact() {
+        assert_eq!(eval_input("0.1 + 0.2 == 0.3").unwrap(), 0.0);
+        assert_eq!(eval_input("nan == nan").unwrap(), 0.0);
+        assert_eq!(eval_input("nan != nan").unwrap(), 1.0);
+    }
+
+    #[test]
+    fn test_parse_comparison_vs_assignment() {
+        assert_eq!(
+            parse_statement("x == 1").unwrap(),
+            Statement::Expression(parse("x == 1").unwrap())
+        );
+        assert!(matches!(
+            parse_statement("x = 1 == 1").unwrap(),
+            Statement::Assignment { .. }
+        ));
+        // A lone `!` is postfix factorial, so it cannot start an operand.
+        assert!(parse("1 + !2").is_err());
+    }
+
+    #[test]
+    fn test_eval_if() {
+        assert_eq!(eval_input("if(1, 2, 3)").unwrap(), 2.0);
+        assert_eq!(eval_input("if(0, 2, 3)").unwrap(), 3.0);
+        assert_eq!(eval_input("if(2 > 1, 10, 20) + 1").unwrap(), 11.0);
+        assert_eq!(eval_input("IF(-0.5, 1, 0)").unwrap(), 1.0);
+    }
+
+    #[test]
+    fn test_eval_if_short_circuits() {
+        let mut ctx = Context::new();
+        ctx.set("x", 0.0);
+        assert_eq!(eval_with("if(x, 1/x, 0)", &ctx).unwrap(), 0.0);
+        assert_eq!(eval_with("if(x == 0, 5, ln(x))", &ctx).unwrap(), 5.0);
+        assert_eq!(eval_with("if(1, 1, undefined)", &ctx).unwrap(), 1.0);
+        assert_eq!(
+            eval_with("if(x == 0, 1/x, 0)", &ctx).unwrap_err(),
+            CalcError::DivideByZero
+        );
+        assert_eq!(
+            eval_with("if(1/x, 1, 0)", &ctx).unwrap_err(),
+            CalcError::DivideByZero
+        );
+    }
+
+    #[test]
+    fn test_error_if_arity() {
+        assert_eq!(
+            eval_input("if(1, 2)").unwrap_err(),
+            CalcError::WrongArity {
+                name: "if".to_string(),
+                expected: 3,
+                got: 2
+            }
+        );
+        let calc = Calculator::new();
+        assert_eq!(calc.eval("if(0, 1/0, 7)").unwrap(), 7.0);
+    }
+
+    #[test]
+    fn test_eval_aggregates() {
+        assert_eq!(eval_input("mean(1,2,3)").unwrap(), 2.0);
+        assert_eq!(eval_input("avg(1,2,3,4)").unwrap(), 2.5);
+        assert_eq!(eval_input("sum(1,2,3,4)").unwrap(), 10.0);
+        assert_eq!(eval_input("product(2,3,4)").unwrap(), 24.0);
+        assert_eq!(eval_input("median(1,3,2)").unwrap(), 2.0);
+        assert_eq!(eval_input("median(1,2,3,4)").unwrap(), 2.5);
+        assert_eq!(eval_input("median(-5)").unwrap(), -5.0);
+        assert_eq!(
+            eval_input("sum()").unwrap_err(),
+            CalcError::WrongArity {
+                name: "sum".to_string(),
+                expected: 1,
+                got: 0
+            }
+        );
+    }
+
+    #[test]
+    fn test_eval_combinatorics() {
+        assert_eq!(eval_input("ncr(5,2)").unwrap(), 10.0);
+        assert_eq!(eval_input("nCr(5,3)").unwrap(), 10.0);
+        assert_eq!(eval_input("npr(5,2)").unwrap(), 20.0);
+        assert_eq!(eval_input("nPr(5,0)").unwrap(), 1.0);
+        assert_eq!(eval_input("ncr(7,0)").unwrap(), 1.0);
+        assert_eq!(eval_input("ncr(7,7)").unwrap(), 1.0);
+        assert_eq!(eval_input("ncr(60,30)").unwrap(), 118264581564861424.0);
+        assert_eq!(eval_input("npr(2^53, 1)").unwrap(), 2f64.powi(53));
+        // Huge arguments overflow quickly instead of looping k times.
+        assert_eq!(eval_input("npr(1e9, 1e9)").unwrap(), f64::INFINITY);
+        assert_eq!(eval_input("ncr(1e9, 5e8)").unwrap(), f64::INFINITY);
+        assert_eq!(eval_input("ncr(2^53, 2^52)").unwrap(), f64::INFINITY);
+    }
+
+    #[test]
+    fn test_error_combinatorics_domain() {
+        assert_eq!(
+            eval_input("ncr(2,5)").unwrap_err(),
+            CalcError::DomainError {
+                name: "ncr".to_string(),
+                arg: 5.0
+            }
+        );
+        assert_eq!(
+            eval_input("npr(-1,0)").unwrap_err(),
+            CalcError::DomainError {
+                name: "npr".to_string(),
+                arg: -1.0
+            }
+        );
+        assert_eq!(
+            eval_input("ncr(5,1.5)").unwrap_err(),
+            CalcError::DomainError {
+                name: "ncr".to_string(),
+                arg: 1.5
+            }
+        );
+        // Past 2^53 `n - 1` rounds back to `n`.
+        assert_eq!(
+            eval_input("npr(1e300, 3)").unwrap_err(),
+            CalcError::DomainError {
+                name: "npr".to_string(),
+                arg: 1e300
+            }
+        );
+    }
+
+    #[test]
+    fn test_eval_clamp_and_hypot() {
+        assert_eq!(eval_input("clamp(5, 0, 3)").unwrap(), 3.0);
+        assert_eq!(eval_input("clamp(-1, 0, 3)").unwrap(), 0.0);
+        assert_eq!(eval_input("clamp(2, 0, 3)").unwrap(), 2.0);
+        assert_eq!(eval_input("hypot(3, 4)").unwrap(), 5.0);
+        assert_eq!(
+            eval_input("hypot(1e200, 1e200)").unwrap(),
+            1e200 * 2f64.sqrt()
+        );
+    }
+
+    #[test]
+    fn test_error_clamp_bounds() {
+        assert_eq!(
+            eval_input("clamp(1, 3, 0)").unwrap_err(),
+            CalcError::DomainError {
+                name: "clamp".to_string(),
+                arg: 3.0
+            }
+        );
+        assert!(eval_input("clamp(1, nan, 0)").is_err());
+    }
+
+    #[test]
+    fn test_eval_pow_and_root() {
+        assert_eq!(eval_input("pow(2,10)").unwrap(), 1024.0);
+        assert_eq!(
+            eval_input("pow(2,10)").unwrap(),
+            eval_input("2^10").unwrap()
+        );
+        assert_close(eval_input("root(2, 9)").unwrap(), 3.0);
+        assert_close(eval_input("root(3, 27)").unwrap(), 3.0);
+        assert_close(eval_input("root(3, -8)").unwrap(), -2.0);
+        assert_close(eval_input("root(-1, 4)").unwrap(), 0.25);
+    }
+
+    #[test]
+    fn test_error_root_domain() {
+        assert_eq!(
+            eval_input("root(0, 8)").unwrap_err(),
+            CalcError::DomainError {
+                name: "root".to_string(),
+                arg: 0.0
+            }
+        );
+        assert_eq!(
+            eval_input("root(2, -4)").unwrap_err(),
+            CalcError::DomainError {
+                name: "root".to_string(),
+                arg: -4.0
+            }
+        );
+    }
+
+    #[test]
+    fn test_eval_program_statements() {
+        let mut ctx = Context::new();
+        let program = parse_program("x = 2; y = 3; x*y").unwrap();
+        assert_eq!(program.len(), 3);
+        assert_eq!(eval_program(&program, &mut ctx).unwrap(), 6.0);
+        assert_eq!(ctx.get("y"), Some(3.0));
+
+        let program = parse_program("1 + 2").unwrap();
+        assert_eq!(
+            program,
+            vec![Statement::Expression(parse("1 + 2").unwrap())]
+        );
+        assert_eq!(eval_program(&program, &mut ctx).unwrap(), 3.0);
+    }
+
+    #[test]
+    fn test_eval_program_skips_empty_statements() {
+        let mut ctx = Context::new();
+        let program = parse_program(";a = 4;; a + 1;").unwrap();
+        assert_eq!(program.len(), 2);
+        assert_eq!(eval_program(&program, &mut ctx).unwrap(), 5.0);
+
+        let calc = Calculator::new();
+        let program = calc.parse_program("b = 2; b^3;").unwrap();
+        assert_eq!(calc.eval_program(&program, &mut ctx).unwrap(), 8.0);
+    }
+
+    #[test]
+    fn test_error_program_without_statements() {
+        for input in [";", ";;", " ; ;", ""] {
+            assert_eq!(
+                parse_program(input),
+                Err(CalcError::EmptyInput),
+                "{input:?}"
+            );
+        }
+        assert_eq!(
+            Calculator::new().parse_program(";"),
+            Err(CalcError::EmptyInput)
+        );
+        // An empty slice evaluates to the same error rather than panicking.
+        let mut ctx = Context::new();
+        assert_eq!(eval_program(&[], &mut ctx), Err(CalcError::EmptyInput));
+        assert_eq!(
+            Calculator::new().eval_program(&[], &mut ctx),
+            Err(CalcError::EmptyInput)
+        );
+        assert_eq!(
+            parse_program("1; 2 3").unwrap_err(),
+            CalcError::UnexpectedTokenAfterExpression {
+                got: Token::Number(3),
+                pos: 5
+            }
+        );
+        assert!(parse("1; 2").is_err());
+    }
+
+    #[test]
+    fn test_ans_carries_between_evaluations() {
+        let mut ctx = Context::new();
+        assert_eq!(
+            eval_with("ans * 10", &ctx).unwrap_err(),
+            CalcError::UnknownIdentifier("ans".to_string())
+        );
+
+        let first = parse_program("2+2").unwrap();
+        let value = eval_program(&first, &mut ctx).unwrap();
+        ctx.set("ans", value);
+        let second = parse_program("ans*10").unwrap();
+        assert_eq!(eval_program(&second, &mut ctx).unwrap(), 40.0);
+    }
+
+    #[test]
+    fn test_format_number_precision() {
+        let digits = |precision| NumberFormat {
+            precision: Some(precision),
+            scientific: false,
+        };
+        assert_eq!(
+            format_number(0.1 + 0.2, NumberFormat::default()),
+            "0.30000000000000004"
+        );
+        assert_eq!(format_number(0.1 + 0.2, digits(4)), "0.3");
+        assert_eq!(format_number(2.0 / 3.0, digits(4)), "0.6667");
+        assert_eq!(format_number(-1234.5678, digits(6)), "-1234.57");
+        assert_eq!(format_number(123456.0, digits(2)), "120000");
+        assert_eq!(format_number(9.996, digits(3)), "10");
+        assert_eq!(format_number(0.0, digits(3)), "0");
+        assert_eq!(format_number(f64::INFINITY, digits(3)), "inf");
+    }
+
+    #[test]
+    fn test_format_number_scientific() {
+        let sci = |precision| NumberFormat {
+            precision,
+            scientific: true,
+        };
+        assert_eq!(format_number(6.02214076e23, sci(Some(4))), "6.022e23");
+        assert_eq!(format_number(-0.000012345, sci(Some(3))), "-1.23e-5");
+        assert_eq!(format_number(1.5e20, sci(None)), "1.5e20");
+        // Ordinary magnitudes stay in positional notation.
+        assert_eq!(format_number(1234.5, sci(Some(3))), "1230");
+        assert_eq!(format_number(0.25, sci(None)), "0.25");
+    }
+
+    #[test]
+    fn test_to_tree_string() {
+        let tree = parse("1+2*3").unwrap().to_tree_string();
+        assert_eq!(
+            tree,
+            "BinaryOp +\n  Number 1\n  BinaryOp *\n    Number 2\n    Number 3\n"
+        );
+
+        let tree = parse("-max(x, (y))").unwrap().to_tree_string();
+        assert_eq!(
+            tree,
+            concat!(
+                "UnaryOp -\n",
+                "  FunctionCall max\n",
+                "    Identifier x\n",
+                "    Parenthesis\n",
+                "      Identifier y\n",
+            )
+        );
+    }
+
+    #[test]
+    fn test_visit_collects_identifiers() {
+        let expr = parse("x + sin(y)*z").unwrap();
+        let mut names = Vec::new();
+        expr.visit(|node| {
+            if let Expression::Identifier(name) = node {
+                names.push(name.clone());
+            }
+        });
+        assert_eq!(names, ["x", "y", "z"]);
+    }
+
+    #[test]
+    fn test_fold_and_map() {
+        let expr = parse("x + sin(y)*z").unwrap();
+        let count = expr.fold(&mut |_, children: Vec<usize>| 1 + children.iter().sum::<usize>());
+        assert_eq!(count, 6);
+
+        // Substitute a variable and drop redundant parentheses.
+        let rewritten = parse("(x) * (y + 1)").unwrap().map(&mut |node| match node {
+            Expression::Identifier(ref name) if name == "y" => Expression::Number(2.0),
+            Expression::Parenthesis(ref inner) if inner.children().is_empty() => (**inner).clone(),
+            other => other,
+        });
+        assert_eq!(rewritten.to_string(), "x * (2 + 1)");
+
+        // Both walk the tree without recursing, however deep it is.
+        let deep = parse(&format!("1{}", "+1".repeat(200_000))).unwrap();
+        let count = deep.fold(&mut |_, children: Vec<usize>| 1 + children.iter().sum::<usize>());
+        assert_eq!(count, 400_001);
+        let doubled = deep.map(&mut |node| match node {
+            Expression::Number(n) => Expression::Number(2.0 * n),
+            other => other,
+        });
+        assert_eq!(doubled.evaluate().unwrap(), 400_002.0);
+    }
+
+    #[test]
+    fn test_simplify_folds_constants() {
+        let simplify = |input| parse(input).unwrap().simplify();
+        assert_eq!(simplify("(2+3)*x"), parse("5*x").unwrap());
+        assert_eq!(simplify("-(2^3) + y").to_string(), "-8 + y");
+        assert_eq!(simplify("(0-2)^x").to_string(), "(-2) ^ x");
+        assert_eq!(simplify("(x)"), Expression::Identifier("x".to_string()));
+        assert_eq!(simplify("2 < 3"), Expression::Number(1.0));
+    }
+
+    #[test]
+    fn test_simplify_identities() {
+        let simplify = |input| parse(input).unwrap().simplify();
+        let x = Expression::Identifier("x".to_string());
+        assert_eq!(simplify("x*1"), x);
+        assert_eq!(simplify("1*x"), x);
+        assert_eq!(simplify("x+0"), x);
+        assert_eq!(simplify("0+x - 0"), x);
+        assert_eq!(simplify("x^(3-2)"), x);
+        assert_eq!(simplify("(x+y)*0"), Expression::Number(0.0));
+    }
+
+    #[test]
+    fn test_simplify_keeps_failing_subexpressions() {
+        let simplify = |input| parse(input).unwrap().simplify();
+        assert_eq!(simplify("1/0"), parse("1/0").unwrap());
+        assert_eq!(simplify("(1/0)*0"), parse("(1/0)*0").unwrap());
+        assert_eq!(simplify("sqrt(4) + 1"), parse("sqrt(4) + 1").unwrap());
+        assert_eq!(simplify("5 % (1-1)"), parse("5 % 0").unwrap());
+    }
+
+    #[test]
+    fn test_parse_all_errors() {
+        assert_eq!(
+            parse_all_errors("1 + @ + $"),
+            vec![
+                CalcError::UnexpectedChar { ch: '@', pos: 4 },
+                CalcError::UnexpectedChar { ch: '$', pos: 8 },
+            ]
+        );
+        assert_eq!(
+            parse_all_errors("(1 + ) * 2 @"),
+            vec![
+                CalcError::ExpectedPrimary {
+                    got: Token::CloseParen,
+                    pos: 5
+                },
+                CalcError::UnexpectedChar { ch: '@', pos: 11 },
+            ]
+        );
+        assert_eq!(parse_all_errors("max(1, 2) * x"), vec![]);
+        // The first reported error is the one `parse` stops at.
+        assert_eq!(
+            parse_all_errors("1 + @ + $")[0],
+            parse("1 + @ + $").unwrap_err()
+        );
+    }
+
+    #[test]
+    fn test_lexer_iterator_matches_tokenize() {
+        for input in ["1 + 2*x", "max(1_000, 2.5e-3); y = 4", "3 <= 4 != 0"] {
+            let streamed: Vec<Token> = Lexer::new(input).map(Result::unwrap).collect();
+            assert_eq!(streamed, crate::lexer::tokenize(input).unwrap());
+            assert_eq!(streamed.last(), Some(&Token::Eof));
+        }
+    }
+
+    #[test]
+    fn test_lexer_iterator_reports_error_in_place() {
+        let items: Vec<_> = Lexer::new("1 + #2").collect();
+        assert_eq!(
+            items,
+            vec![
+                Ok(Token::Number(1)),
+                Ok(Token::Op("+")),
+                Err(CalcError::UnexpectedChar { ch: '#', pos: 4 }),
+                Ok(Token::Number(2)),
+                Ok(Token::Eof),
+            ]
+        );
+        assert_eq!(Lexer::new("").collect::<Vec<_>>(), vec![Ok(Token::Eof)]);
+    }
+
+    #[test]
+    fn test_eval_i64() {
+        assert_eq!(eval_i64("2^10"), Ok(1024));
+        assert_eq!(eval_i64("2^62"), Ok(4_611_686_018_427_387_904));
+        assert_eq!(eval_i64("-2^62 * 2"), Ok(i64::MIN));
+        assert_eq!(eval_i64("6/3 + 7 % 4"), Ok(5));
+        assert_eq!(eval_i64("max(3, abs(-9), 4) < 10"), Ok(1));
+        assert_eq!(eval_i64("(-1)^-3"), Ok(-1));
+    }
+
+    #[test]
+    fn test_error_eval_i64() {
+        assert_eq!(eval_i64("2^63"), Err(CalcError::IntegerOverflow));
+        assert_eq!(eval_i64("2^62 * 2"), Err(CalcError::IntegerOverflow));
+        assert_eq!(eval_i64("7/2"), Err(CalcError::NonInteger));
+        assert_eq!(eval_i64("2^-1"), Err(CalcError::NonInteger));
+        assert_eq!(eval_i64("1.5 + 1.5"), Err(CalcError::NonInteger));
+        assert_eq!(eval_i64("pi"), Err(CalcError::NonInteger));
+        assert_eq!(eval_i64("5 % 0"), Err(CalcError::DivideByZero));
+        assert_eq!(
+            eval_i64("sqrt(4)"),
+            Err(CalcError::UnknownFunction("sqrt".to_string()))
+        );
+    }
+
+    #[test]
+    fn test_div_by_zero_modes() {
+        let mut calc = Calculator::new();
+        assert_eq!(calc.div_by_zero(), DivByZero::Error);
+        for input in ["1/0", "-1/0", "0/0"] {
+            assert_eq!(calc.eval(input), Err(CalcError::DivideByZero));
+        }
+
+        calc.set_div_by_zero(DivByZero::Infinity);
+        assert_eq!(calc.eval("1/0").unwrap(), f64::INFINITY);
+        assert_eq!(calc.eval("-1/0").unwrap(), f64::NEG_INFINITY);
+        assert!(calc.eval("0/0").unwrap().is_nan());
+        assert!(calc.eval("5 % 0").unwrap().is_nan());
+        let mut ctx = Context::new();
+        let program = calc.parse_program("x = 0; 2/x").unwrap();
+        assert_eq!(
+            calc.eval_program(&program, &mut ctx).unwrap(),
+            f64::INFINITY
+        );
+
+        // The free functions keep the default.
+        assert_eq!(eval("1/0"), Err(CalcError::DivideByZero));
+    }
+
+    #[test]
+    fn test_require_finite() {
+        let mut calc = Calculator::new();
+        assert!(calc.eval("sqrt(-1)").unwrap().is_nan());
+
+        calc.set_require_finite(true);
+        assert_eq!(calc.eval("sqrt(4)").unwrap(), 2.0);
+        assert_eq!(calc.eval("sqrt(-1)"), Err(CalcError::NonFinite));
+        assert_eq!(calc.eval("1e308 * 10"), Err(CalcError::NonFinite));
+        // Caught where it appears, even if later steps would hide it.
+        assert_eq!(calc.eval("1 / inf"), Err(CalcError::NonFinite));
+
+        calc.set_div_by_zero(DivByZero::Infinity);
+        assert_eq!(calc.eval("1/0"), Err(CalcError::NonFinite));
+    }
+
+    #[test]
+    fn test_unicode_symbols() {
+        assert_eq!(eval_input("2×3==6").unwrap(), 1.0);
+        assert_eq!(eval_input("6÷2==3").unwrap(), 1.0);
+        assert_eq!(eval_input("2−1==1").unwrap(), 1.0);
+        assert_eq!(eval_input("√9==3").unwrap(), 1.0);
+        assert_eq!(eval_input("−√16").unwrap(), -4.0);
+        assert_close(eval_input("2π").unwrap(), 2.0 * std::f64::consts::PI);
+
+        // Aliases lex to the canonical operators.
+        assert_eq!(parse("6÷2×3").unwrap(), parse("6/2*3").unwrap());
+        assert_eq!(parse("√(x+1)").unwrap().to_string(), "√(x + 1)");
+        assert_eq!(
+            crate::lexer::tokenize("1 × π").unwrap(),
+            vec![
+                Token::Number(1),
+                Token::Op("*"),
+                Token::Ident("pi".to_string()),
+                Token::Eof
+            ]
+        );
+    }
+
+    #[test]
+    fn test_double_star_power() {
+        assert_eq!(eval_input("2**3**2").unwrap(), 512.0);
+        assert_eq!(eval_input("2*3").unwrap(), 6.0);
+        assert_eq!(eval_input("2**3*2").unwrap(), 16.0);
+        assert_eq!(eval_input("2*3**2").unwrap(), 18.0);
+        assert_eq!(eval_input("-2**2").unwrap(), -4.0);
+        assert_eq!(parse("2**3").unwrap(), parse("2^3").unwrap());
+        assert_eq!(
+            crate::lexer::tokenize("2***3").unwrap(),
+            vec![
+                Token::Number(2),
+                Token::Op("^"),
+                Token::Op("*"),
+                Token::Number(3),
+                Token::Eof
+            ]
+        );
+        assert!(eval_input("2***3").is_err());
+    }
+
+    #[test]
+    fn test_floor_division() {
+        assert_eq!(eval_input("7 // 2").unwrap(), 3.0);
+        assert_eq!(eval_input("-7 // 2").unwrap(), -4.0);
+        assert_eq!(eval_input("7 // -2").unwrap(), -4.0);
+        assert_eq!(eval_input("7.5 // 2.5").unwrap(), 3.0);
+        assert_eq!(eval_input("1 + 9 // 2 * 2").unwrap(), 9.0);
+        assert_eq!(eval_input("5 // 0"), Err(CalcError::DivideByZero));
+        assert_eq!(eval_input("8 / 2 / 2").unwrap(), 2.0);
+
+        assert_eq!(eval_i64("-7 // 2"), Ok(-4));
+        assert_eq!(eval_i64("-8 // 2"), Ok(-4));
+        assert_eq!(eval_i64("5 // 0"), Err(CalcError::DivideByZero));
+    }
+
+    #[test]
+    fn test_mod_keyword() {
+        assert_eq!(eval_input("7 mod 3 == 1").unwrap(), 1.0);
+        assert_eq!(eval_input("1 + 6 mod 4 == 3").unwrap(), 1.0);
+        assert_eq!(eval_input("2 * 7 MOD 4").unwrap(), 2.0);
+        assert_eq!(parse("7 mod 3").unwrap(), parse("7 % 3").unwrap());
+
+        let mut ctx = Context::new();
+        let program = parse_program("modulus = 10; modulus mod 4").unwrap();
+        assert_eq!(eval_program(&program, &mut ctx).unwrap(), 2.0);
+        assert_eq!(ctx.get("modulus"), Some(10.0));
+    }
+
+    #[test]
+    fn test_absolute_value_bars() {
+        assert_eq!(eval_input("|(-5)|==5").unwrap(), 1.0);
+        assert_eq!(eval_input("|3-7|==4").unwrap(), 1.0);
+        assert_eq!(eval_input("|-2| * |2 - 5|").unwrap(), 6.0);
+        assert_eq!(eval_input("|1 - |2 - 5||").unwrap(), 2.0);
+        assert_eq!(parse("|x|").unwrap(), parse("abs(x)").unwrap());
+    }
+
+    #[test]
+    fn test_error_unterminated_bar() {
+        assert_eq!(
+            eval_input("|3"),
+            Err(CalcError::ExpectedToken {
+                expected: Token::Bar,
+                got: Token::Eof,
+                pos: 2
+            })
+        );
+        assert!(eval_input("2|3|").is_err());
+    }
+
+    #[test]
+    fn test_calculator_builder() {
+        let calc = Calculator::builder()
+            .angle_mode(AngleMode::Degrees)
+            .constant("Quarter", 90.0)
+            .function("half", 1, Some(1), |args| Ok(args[0] / 2.0))
+            .variable("r", 3.0)
+            .require_finite(true)
+            .build();
+        assert_eq!(calc.angle_mode(), AngleMode::Degrees);
+        assert!(calc.require_finite());
+        assert_close(
+            calc.eval("sin(quarter) + cos(half(QUARTER) * 2)").unwrap(),
+            1.0,
+        );
+        assert_eq!(calc.eval("r * 2").unwrap(), 6.0);
+        assert_eq!(calc.eval_expression(&parse("r^2").unwrap()).unwrap(), 9.0);
+        assert_eq!(calc.eval("sqrt(-r)"), Err(CalcError::NonFinite));
+
+        // Context variables shadow custom constants, which cannot be assigned.
+        let mut ctx = Context::new();
+        ctx.set("quarter", 1.0);
+        assert_eq!(calc.eval_with("quarter", &ctx).unwrap(), 1.0);
+        let stmt = calc.parse_statement("quarter = 2").unwrap();
+        assert_eq!(
+            calc.eval_statement(&stmt, &mut Context::new()),
+            Err(CalcError::ReservedName("quarter".to_string()))
+        );
+    }
+
+    #[test]
+    fn test_calculator_builder_defaults() {
+        let calc = Calculator::builder().build();
+        assert_eq!(calc.angle_mode(), AngleMode::Radians);
+        assert_eq!(calc.div_by_zero(), DivByZero::Error);
+        assert_eq!(calc.eval("1/0"), Err(CalcError::DivideByZero));
+        assert_eq!(calc.constant("pi"), Some(std::f64::consts::PI));
+
+        let mut calc = Calculator::builder()
+            .div_by_zero(DivByZero::Infinity)
+            .context(Context::new())
+            .build();
+        calc.context_mut().set("z", 0.0);
+        assert_eq!(calc.eval("1/z").unwrap(), f64::INFINITY);
+    }
+
+    #[test]
+    fn test_error_classification() {
+        let lex_error = eval_input("1 + @").unwrap_err();
+        assert!(lex_error.is_parse_error() && !lex_error.is_eval_error());
+
+        let parse_error = eval_input("(1 + 2").unwrap_err();
+        assert!(parse_error.is_parse_error() && !parse_error.is_eval_error());
+
+        let eval_error = eval_input("1/0").unwrap_err();
+        assert_eq!(eval_error, CalcError::DivideByZero);
+        assert!(eval_error.is_eval_error() && !eval_error.is_parse_error());
+    }
+
+    #[test]
+    fn test_expression_evaluate() {
+        let expr = parse("3*4 + x").unwrap();
+        assert_eq!(
+            expr.evaluate(),
+            Err(CalcError::UnknownIdentifier("x".to_string()))
+        );
+        let mut ctx = Context::new();
+        ctx.set("x", 1.0);
+        assert_eq!(expr.evaluate_with(&ctx).unwrap(), 13.0);
+        assert_eq!(parse("3*4").unwrap().evaluate().unwrap(), 12.0);
+    }
+
+    #[test]
+    fn test_expression_hash_dedup() {
+        use std::collections::HashSet;
+
+        let mut set = HashSet::new();
+        set.insert(parse("1 + 2*x").unwrap());
+        set.insert(parse("1+2*x").unwrap());
+        assert_eq!(set.len(), 1);
+
+        // Structural, not mathematical, equality.
+        set.insert(parse("2*x + 1").unwrap());
+        assert_eq!(set.len(), 2);
+
+        let mut numbers = HashSet::new();
+        numbers.insert(Expression::Number(f64::NAN));
+        numbers.insert(Expression::Number(-f64::NAN));
+        numbers.insert(Expression::Number(0.0));
+        numbers.insert(Expression::Number(-0.0));
+        assert_eq!(numbers.len(), 2);
+        assert_eq!(Expression::Number(f64::NAN), Expression::Number(f64::NAN));
+
+        // Deep trees compare and hash without recursing.
+        let chain = format!("1{}", "+1".repeat(200_000));
+        let mut deep = HashSet::new();
+        deep.insert(parse(&chain).unwrap());
+        deep.insert(parse(&chain).unwrap());
+        assert_eq!(deep.len(), 1);
+        assert_ne!(
+            parse(&chain).unwrap(),
+            parse(&format!("{chain}+2")).unwrap()
+        );
+        assert_ne!(parse(&chain).unwrap(), parse(&format!("2{chain}")).unwrap());
+    }
+
+    #[test]
+    fn test_node_count_and_depth() {
+        let number = parse("42").unwrap();
+        assert_eq!(number.node_count(), 1);
+        assert_eq!(number.depth(), 1);
+
+        let expr = parse("1+2*3").unwrap();
+        assert_eq!(expr.node_count(), 5);
+        assert_eq!(expr.depth(), 3);
+
+        let call = parse("max(1, (2), -x)").unwrap();
+        assert_eq!(call.node_count(), 6);
+        assert_eq!(call.depth(), 3);
+
+        let deep = parse(&format!("1{}", "+1".repeat(50_000))).unwrap();
+        assert_eq!(deep.node_count(), 100_001);
+        assert_eq!(deep.depth(), 50_001);
+    }
+
+    #[test]
+    fn test_postfix_percent() {
+        assert_eq!(eval_input("50%").unwrap(), 0.5);
+        assert_eq!(eval_input("200 + 10%").unwrap(), 220.0);
+        assert_eq!(eval_input("200 - 10%").unwrap(), 180.0);
+        assert_eq!(eval_input("200 + 10% == 220").unwrap(), 1.0);
+        assert_eq!(eval_input("(200 - 10%) - 5").unwrap(), 175.0);
+        // Only a direct right operand of `+`/`-` is relative.
+        assert_close(eval_input("200 + (10%)").unwrap(), 200.1);
+        assert_close(eval_input("200 * 10%").unwrap(), 20.0);
+        assert_eq!(eval_input("-50%").unwrap(), -0.5);
+        assert_eq!(eval_input("max(50%, 1)").unwrap(), 1.0);
+        // With an operand after it, `%` is still modulo.
+        assert_eq!(eval_input("7 % 3").unwrap(), 1.0);
+        assert_eq!(eval_input("7 % √9").unwrap(), 1.0);
+        // A sign after it continues the expression instead.
+        assert_close(eval_input("10% + 5").unwrap(), 5.1);
+        assert_close(eval_input("50% - 5").unwrap(), -4.5);
+        assert_eq!(eval_input("200 + 10% - 5").unwrap(), 215.0);
+
+        for input in [
+            "50%",
+            "200 + 10%",
+            "(200 - 10%) - 5",
+            "5 % (-3)",
+            "5 % (-3 + 1)",
+            "(1+2)%",
+            "(-5)%",
+            "-5%",
+            "2^50%",
+        ] {
+            let expr = parse(input).unwrap();
+            let printed = expr.to_string();
+            assert_eq!(
+                parse(&printed).unwrap(),
+                expr,
+                "{input} printed as {printed}"
+            );
+        }
+        assert_eq!(parse("200 - 10% - 5").unwrap().to_string(), "200 - 10% - 5");
+        assert_eq!(parse("5 % (-3)").unwrap().to_string(), "5 % (-3)");
+        // Without the parentheses from the input, the sign still needs them.
+        let modulo = parse("x % (-y)").unwrap().simplify();
+        assert_eq!(modulo.to_string(), "x % (-y)");
+        assert_eq!(parse(&modulo.to_string()).unwrap().simplify(), modulo);
+
+        assert_eq!(
+            parse("200 + 10%").unwrap().simplify(),
+            Expression::Number(220.0)
+        );
+        assert_eq!(parse("0 + x%").unwrap().simplify().to_string(), "0 + x%");
+        assert_eq!(eval_i64("200 + 10%"), Ok(220));
+        assert_eq!(eval_i64("50%"), Err(CalcError::NonInteger));
+    }
+
+    #[test]
+    fn test_bitwise_operators() {
+        assert_eq!(eval_input("6 & 3").unwrap(), 2.0);
+        assert_eq!(eval_input("6 | 1").unwrap(), 7.0);
+        assert_eq!(eval_input("6 xor 3").unwrap(), 5.0);
+        assert_eq!(eval_input("6 ~^ 3").unwrap(), 5.0);
+        assert_eq!(eval_input("-1 & 255").unwrap(), 255.0);
+        // Looser than comparisons, and `&` before xor before `|`.
+        assert_eq!(eval_input("(6 & 3) == 2").unwrap(), 1.0);
+        assert_eq!(eval_input("6 & 3 == 2").unwrap(), 0.0);
+        assert_eq!(eval_input("1 | 2 ~^ 3 & 6").unwrap(), 1.0);
+        assert_eq!(
+            eval_input("1.5 & 1").unwrap_err(),
+            CalcError::NonIntegerOperand("&".to_string())
+        );
+        assert_eq!(
+            eval_input("1e30 | 1").unwrap_err(),
+            CalcError::IntegerOverflow
+        );
+
+        // Bars still delimit absolute values; a bar after an operand outside
+        // of them is or.
+        assert_eq!(eval_input("|2 - 7| | 2").unwrap(), 7.0);
+        assert_eq!(eval_input("|(2 | 4) - 9|").unwrap(), 3.0);
+        assert_eq!(eval_input("|-3*|-2||").unwrap(), 6.0);
+        assert_eq!(eval_input("|50%|").unwrap(), 0.5);
+        assert_eq!(eval_input("max(1 | 2, 0)").unwrap(), 3.0);
+
+        for input in ["6 & 3 | 1", "6 xor (3 | 1)", "|x| | 1", "50% | 1"] {
+            let expr = parse(input).unwrap();
+            let printed = expr.to_string();
+            assert_eq!(
+                parse(&printed).unwrap(),
+                expr,
+                "{input} printed as {printed}"
+            );
+        }
+        assert_eq!(parse("6 xor 3").unwrap().to_string(), "6 ~^ 3");
+        assert_eq!(eval_i64("12 & 10 | 1"), Ok(9));
+    }
+
+    #[test]
+    fn test_shift_operators() {
+        assert_eq!(eval_input("1 << 4").unwrap(), 16.0);
+        assert_eq!(eval_input("256 >> 2").unwrap(), 64.0);
+        assert_eq!(eval_input("-8 >> 1").unwrap(), -4.0);
+        assert_eq!(eval_input("-1 >> 63").unwrap(), -1.0);
+        assert_eq!(eval_input("1 << 2 + 1").unwrap(), 8.0);
+        assert_eq!(eval_input("1 << 2 < 5").unwrap(), 1.0);
+        assert_eq!(eval_input("2 <= 2").unwrap(), 1.0);
+        assert_eq!(
+            eval_input("1 << 1.5").unwrap_err(),
+            CalcError::NonIntegerOperand("<<".to_string())
+        );
+        assert_eq!(
+            eval_input("1 << 64").unwrap_err(),
+            CalcError::DomainError {
+                name: "<<".to_string(),
+                arg: 64.0
+            }
+        );
+        assert!(matches!(
+            eval_input("8 >> -1"),
+            Err(CalcError::DomainError { .. })
+        ));
+        assert_eq!(
+            eval_input("1 << 63").unwrap_err(),
+            CalcError::IntegerOverflow
+        );
+        assert_eq!(eval_i64("3 << 2 | 1"), Ok(13));
+        assert_eq!(parse("1<<2>>1").unwrap().to_string(), "1 << 2 >> 1");
+    }
+
+    #[test]
+    fn test_eval_rpn() {
+        assert_eq!(eval_rpn("3 4 +").unwrap(), 7.0);
+        assert_eq!(eval_rpn("3 4 + 5 *").unwrap(), 35.0);
+        assert_eq!(eval_rpn("10 4 -").unwrap(), 6.0);
+        assert_eq!(eval_rpn("2 3 ^ neg").unwrap(), -8.0);
+        assert_eq!(eval_rpn("1.5e1 9 √ /").unwrap(), 5.0);
+        assert_eq!(eval_rpn("2 pi *").unwrap(), 2.0 * std::f64::consts::PI);
+        assert_eq!(eval_rpn("16 sqrt 2 pow").unwrap(), 16.0);
+        assert_eq!(eval_rpn("1 5 3 max(3)").unwrap(), 5.0);
+        assert_eq!(eval_rpn("7 3 mod").unwrap(), 1.0);
+
+        assert_eq!(
+            eval_rpn("3 +").unwrap_err(),
+            CalcError::StackUnderflow("+".to_string())
+        );
+        assert_eq!(
+            eval_rpn("1 2 3 +").unwrap_err(),
+            CalcError::LeftoverOperands(1)
+        );
+        assert_eq!(eval_rpn("1 0 /").unwrap_err(), CalcError::DivideByZero);
+        assert!(matches!(
+            eval_rpn("1 , 2"),
+            Err(CalcError::ExpectedPrimary {
+                got: Token::Comma,
+                pos: 2
+            })
+        ));
+        assert!(matches!(
+            eval_rpn(""),
+            Err(CalcError::ExpectedPrimary { .. })
+        ));
+        assert_eq!(
+            eval_rpn("1 foo").unwrap_err(),
+            CalcError::UnknownFunction("foo".to_string())
+        );
+    }
+
+    #[test]
+    fn test_to_rpn() {
+        assert_eq!(parse("1+2*3").unwrap().to_rpn().unwrap(), "1 2 3 * +");
+        assert_eq!(parse("(1+2)*3").unwrap().to_rpn().unwrap(), "1 2 + 3 *");
+        assert_eq!(
+            parse("-sqrt(16) + max(1, 2, 3)").unwrap().to_rpn().unwrap(),
+            "16 sqrt neg 1 2 3 max(3) +"
+        );
+        assert_eq!(parse("foo()").unwrap().to_rpn().unwrap(), "foo(0)");
+        assert_eq!(Expression::Number(-2.5).to_rpn().unwrap(), "2.5 neg");
+
+        // Everything the builtins can evaluate reads back with `eval_rpn`.
+        for input in [
+            "1+2*3",
+            "2^-1 - +3",
+            "hypot(3, 4) * pi",
+            "log(8, 2) + max(1, 7, 3)",
+            "(200 + 10%) - 50%",
+            "|-3| | 4",
+            "√16 // 3 mod 2",
+        ] {
+            let rpn = parse(input).unwrap().to_rpn().unwrap();
+            assert_eq!(eval_rpn(&rpn), eval(input), "{input} as {rpn}");
+        }
+    }
+
+    #[test]
+    fn test_to_latex() {
+        let latex = |input: &str| parse(input).unwrap().to_latex();
+        assert_eq!(latex("(1+2)/3"), r"\frac{1 + 2}{3}");
+        assert_eq!(latex("2^10"), "2^{10}");
+        assert_eq!(latex("2^(1+x)"), "2^{1 + x}");
+        assert_eq!(latex("(2^3)^2"), r"\left(2^{3}\right)^{2}");
+        assert_eq!(latex("-2^2"), "-2^{2}");
+        assert_eq!(latex("sqrt(x)*pi"), r"\sqrt{x} \cdot \pi");
+        assert_eq!(latex("2*(3+4)"), r"2 \cdot \left(3 + 4\right)");
+        assert_eq!(
+            latex("root(3, x) + |x - 1|"),
+            r"\sqrt[3]{x} + \left|x - 1\right|"
+        );
+        assert_eq!(
+            latex("sin(theta) <= max(a, 2)"),
+            r"\sin\left(\mathrm{theta}\right) \le \max\left(a, 2\right)"
+        );
+        assert_eq!(
+            latex("7 // 2 + 50%"),
+            r"\left\lfloor \frac{7}{2} \right\rfloor + 50\%"
+        );
+        assert_eq!(latex("my_fn(1)"), r"\operatorname{my\_fn}\left(1\right)");
+
+        // Constructed trees get parentheses where precedence needs them.
+        let sum = Expression::BinaryOp {
+            op: "+",
+            left: Box::new(Expression::Number(1.0)),
+            right: Box::new(Expression::Identifier("x".to_string())),
+        };
+        let product = Expression::BinaryOp {
+            op: "*",
+            left: Box::new(sum),
+            right: Box::new(Expression::Number(f64::INFINITY)),
+        };
+        assert_eq!(product.to_latex(), r"\left(1 + x\right) \cdot \infty");
+
+        let deep = parse(&format!("1{}", "-1/2".repeat(100_000))).unwrap();
+        let latex = deep.to_latex();
+        assert!(latex.starts_with(r"1 - \frac{1}{2} - \frac{1}{2}"));
+        assert_eq!(latex.matches(r"\frac").count(), 100_000);
+    }
+
+    #[test]
+    fn test_eval_complex() {
+        assert_eq!(eval_complex("sqrt(-1)").unwrap(), Complex::I);
+        assert_eq!(
+            eval_complex("(1+2i)*(1-2i)").unwrap(),
+            Complex::new(5.0, 0.0)
+        );
+        assert_eq!(eval_complex("i^2").unwrap(), Complex::new(-1.0, 0.0));
+        assert_eq!(eval_complex("i^-1").unwrap(), Complex::new(0.0, -1.0));
+        assert_eq!(
+            eval_complex("(3+4i)/(1+2i)").unwrap(),
+            Complex::new(2.2, -0.4)
+        );
+        assert_eq!(eval_complex("abs(3+4i)").unwrap(), Complex::new(5.0, 0.0));
+        assert_eq!(eval_complex("2 + sin(0)").unwrap(), Complex::new(2.0, 0.0));
+
+        let z = eval_complex("exp(i*pi)").unwrap();
+        assert_close(z.re, -1.0);
+        assert!(z.im.abs() < 1e-12);
+        let z = eval_complex("i^i").unwrap();
+        assert_close(z.re, (-std::f64::consts::FRAC_PI_2).exp());
+        assert_eq!(z.im, 0.0);
+
+        assert_eq!(eval_complex("i/0").unwrap_err(), CalcError::DivideByZero);
+        assert_eq!(
+            eval_complex("sin(i)").unwrap_err(),
+            CalcError::ComplexArgument("sin".to_string())
+        );
+        assert_eq!(
+            eval_complex("i < 1").unwrap_err(),
+            CalcError::ComplexArgument("<".to_string())
+        );
+        // The real evaluator is unchanged.
+        assert!(eval("sqrt(-1)").unwrap().is_nan());
+
+        assert_eq!(Complex::new(1.0, -2.0).to_string(), "1 - 2i");
+        assert_eq!(Complex::new(0.0, -1.0).to_string(), "-i");
+        assert_eq!(Complex::new(0.5, 1.0).to_string(), "0.5 + i");
+        assert_eq!(Complex::new(3.0, 0.0).to_string(), "3");
+    }
+
+    #[test]
+    fn test_eval_rational() {
+        assert_eq!(eval_rational("1/3 + 1/3 + 1/3").unwrap(), Rational::from(1));
+        assert_eq!(eval_rational("0.1 + 0.2").unwrap(), Rational::new(3, 10));
+        assert_eq!(eval_rational("(2/3)^-2").unwrap(), Rational::new(9, 4));
+        assert_eq!(eval_rational("-6/4").unwrap(), Rational::new(-3, 2));
+        assert_eq!(
+            eval_rational("7/2 // 1 + 7/2 % 1").unwrap(),
+            Rational::new(7, 2)
+        );
+        assert_eq!(
+            eval_rational("max(1/3, 0.3) == 1/3").unwrap(),
+            Rational::from(1)
+        );
+        assert_eq!(eval_rational("200 + 10%").unwrap(), Rational::from(220));
+        assert_eq!(eval_rational("6/2 & 1").unwrap(), Rational::from(1));
+        assert_eq!(Rational::new(6, -4).to_string(), "-3/2");
+        assert_eq!(Rational::new(1, 3).to_f64(), 1.0 / 3.0);
+
+        assert_eq!(
+            eval_rational("sqrt(2)").unwrap_err(),
+            CalcError::NotRational("sqrt".to_string())
+        );
+        assert_eq!(
+            eval_rational("2^(1/2)").unwrap_err(),
+            CalcError::NotRational("^".to_string())
+        );
+        assert_eq!(
+            eval_rational("pi").unwrap_err(),
+            CalcError::NotRational("pi".to_string())
+        );
+        assert_eq!(
+            eval_rational("1/(1-1)").unwrap_err(),
+            CalcError::DivideByZero
+        );
+        assert_eq!(
+            eval_rational("2^64").unwrap_err(),
+            CalcError::IntegerOverflow
+        );
+        assert_eq!(eval_rational("1^1000000000000").unwrap(), Rational::from(1));
+    }
+
+    #[test]
+    fn test_factorial() {
+        assert_eq!(eval("5!").unwrap(), 120.0);
+        assert_eq!(eval("0!").unwrap(), 1.0);
+        assert_eq!(eval("-3!").unwrap(), -6.0);
+        assert_eq!(eval("2^3!").unwrap(), 64.0);
+        assert_eq!(eval("3!!").unwrap(), 720.0);
+        assert_eq!(eval("3! - 1").unwrap(), 5.0);
+        assert_eq!(eval("5!=120").unwrap(), 1.0);
+        assert_eq!(eval("171!").unwrap(), f64::INFINITY);
+        assert_eq!(eval_i64("20!").unwrap(), 2_432_902_008_176_640_000);
+        assert_eq!(eval_i64("21!").unwrap_err(), CalcError::IntegerOverflow);
+        assert_eq!(eval_rational("(6/2)!").unwrap(), Rational::from(6));
+        assert_eq!(parse("(1+2)! - 1").unwrap().to_string(), "(1 + 2)! - 1");
+        assert_eq!(parse("n!").unwrap().to_latex(), "n!");
+        assert!(matches!(
+            eval("2.5!").unwrap_err(),
+            CalcError::DomainError { ref name, .. } if name == "!"
+        ));
+        assert!(matches!(
+            eval("(-1)!").unwrap_err(),
+            CalcError::DomainError { ref name, .. } if name == "!"
+        ));
+    }
+
+    #[cfg(feature = "bigint")]
+    #[test]
+    fn test_eval_bigint() {
+        assert_eq!(eval_bigint("2^64").unwrap(), "18446744073709551616");
+        assert_eq!(
+            eval_bigint("100!").unwrap(),
+            "93326215443944152681699238856266700490715968264381621468592963895217599993229915\
+             608941463976156518286253697920827223758251185210916864000000000000000000000000"
+        );
+        assert_eq!(
+            eval_bigint("3^100").unwrap(),
+            "515377520732011331036461129765621272702107522001"
+        );
+        assert_eq!(
+            eval_bigint("2^127 - 1").unwrap(),
+            "170141183460469231731687303715884105727"
+        );
+        assert_eq!(eval_bigint("100! / 98!").unwrap(), "9900");
+        assert_eq!(eval_bigint("-(2^70) // 3^40").unwrap(), "-98");
+        assert_eq!(eval_bigint("-(10^20) % 7").unwrap(), "-2");
+        assert_eq!(eval_bigint("2^64 - 2^64").unwrap(), "0");
+        assert_eq!(eval_bigint("(-2)^63").unwrap(), "-9223372036854775808");
+        assert_eq!(eval_bigint("10^18 * 10^18 > 10^35").unwrap(), "1");
+        assert_eq!(
+            eval_bigint("max(2^80, -(3^60))").unwrap(),
+            "1208925819614629174706176"
+        );
+
+        assert_eq!(eval_bigint("2^64 / 3").unwrap_err(), CalcError::NonInteger);
+        assert_eq!(
+            eval_bigint("2^64 / 0").unwrap_err(),
+            CalcError::DivideByZero
+        );
+        assert_eq!(eval_bigint("2^-1").unwrap_err(), CalcError::NonInteger);
+        assert_eq!(eval_bigint("1.5 * 2").unwrap_err(), CalcError::NonInteger);
+        assert_eq!(
+            eval_bigint("sqrt(4)").unwrap_err(),
+            CalcError::UnknownFunction("sqrt".to_string())
+        );
+        assert_eq!(
+            eval_bigint("10^100000").unwrap_err(),
+            CalcError::IntegerOverflow
+        );
+        assert_eq!(eval_bigint("(-1)^(10^30)").unwrap(), "1");
+    }
+
+    #[test]
+    fn test_seeded_random() {
+        let mut calc = Calculator::new();
+        calc.seed_random(42);
+        let first = [
+            calc.eval("random()").unwrap(),
+            calc.eval("random()").unwrap(),
+        ];
+        assert_ne!(first[0], first[1]);
+        assert!(first.iter().all(|x| (0.0..1.0).contains(x)));
+
+        let calc = Calculator::builder().seed_random(42).build();
+        let second = [
+            calc.eval("random()").unwrap(),
+            calc.eval("random()").unwrap(),
+        ];
+        assert_eq!(first, second);
+
+        for _ in 0..100 {
+            let x = calc.eval("random(-5, 5)").unwrap();
+            assert!((-5.0..5.0).contains(&x));
+        }
+        assert_eq!(calc.eval("random(3, 3)").unwrap(), 3.0);
+        assert!(matches!(
+            calc.eval("random(2, 1)").unwrap_err(),
+            CalcError::DomainError { ref name, .. } if name == "random"
+        ));
+        assert!(matches!(
+            calc.eval("random(1)").unwrap_err(),
+            CalcError::WrongArity {
+                expected: 2,
+                got: 1,
+                ..
+            }
+        ));
+        assert_eq!(
+            eval("random()").unwrap_err(),
+            CalcError::UnknownFunction("random".to_string())
+        );
+    }
+
+    #[test]
+    fn test_angle_conversions() {
+        assert_eq!(eval("rad2deg(pi) == 180").unwrap(), 1.0);
+        assert_eq!(eval("deg2rad(180) == pi").unwrap(), 1.0);
+        assert_close(eval("deg2rad(90)").unwrap(), std::f64::consts::FRAC_PI_2);
+        assert_eq!(eval("dms(30, 15, 0)").unwrap(), 30.25);
+        assert_eq!(eval("dms(-30, 15, 0)").unwrap(), -30.25);
+        assert_eq!(eval("dms(10, 0, 36)").unwrap(), 10.01);
+        let calc = Calculator::builder().angle_mode(AngleMode::Degrees).build();
+        assert_eq!(calc.eval("rad2deg(pi)").unwrap(), 180.0);
+
+        assert!(matches!(
+            eval("dms(1, 60, 0)").unwrap_err(),
+            CalcError::DomainError { ref name, .. } if name == "dms"
+        ));
+        for input in ["deg2rad()", "rad2deg(1, 2)", "dms(1, 2)"] {
+            assert!(matches!(
+                eval(input).unwrap_err(),
+                CalcError::WrongArity { .. }
+            ));
+        }
+    }
+
+    #[test]
+    fn test_error_messages_show_tokens() {
+        assert_eq!(
+            parse("(1 + 2").unwrap_err().to_string(),
+            "unclosed '(': missing ')'"
+        );
+        assert_eq!(
+            parse("max(1 2").unwrap_err().to_string(),
+            "expected ')', got number 2"
+        );
+        assert_eq!(parse("(1 + 2))").unwrap_err().to_string(), "unmatched ')'");
+        assert_eq!(
+            parse("1 + 2 (").unwrap_err().to_string(),
+            "expected expression, got end of input"
+        );
+        assert_eq!(
+            parse("1 + *").unwrap_err().to_string(),
+            "expected expression, got '*'"
+        );
+        assert_eq!(Token::Op("+").to_string(), "+");
+        assert_eq!(Token::Number(5).to_string(), "number 5");
+        assert_eq!(Token::Eof.to_string(), "end of input");
+    }
+
+    #[test]
+    fn test_error_kind() {
+        let unexpected_char = eval("1 @ 2").unwrap_err();
+        assert!(matches!(unexpected_char, CalcError::UnexpectedChar { .. }));
+        assert_eq!(unexpected_char.kind(), ErrorKind::Lex);
+        let expected_primary = eval("1 +").unwrap_err();
+        assert!(matches!(
+            expected_primary,
+            CalcError::ExpectedPrimary { .. }
+        ));
+        assert_eq!(expected_primary.kind(), ErrorKind::Parse);
+        assert_eq!(CalcError::DivideByZero.kind(), ErrorKind::Eval);
+        assert_eq!(eval("nope(1)").unwrap_err().kind(), ErrorKind::Eval);
+        for input in ["1 @ 2", "(1", "1 +", "1 / 0", "x"] {
+            let err = eval(input).unwrap_err();
+            assert_eq!(err.is_parse_error(), err.kind() != ErrorKind::Eval);
+        }
+    }
+
+    #[test]
+    fn test_empty_input() {
+        assert_eq!(eval("").unwrap_err(), CalcError::EmptyInput);
+        assert_eq!(eval("   ").unwrap_err(), CalcError::EmptyInput);
+        assert_eq!(parse_statement(" ").unwrap_err(), CalcError::EmptyInput);
+        assert_eq!(parse_program("").unwrap_err(), CalcError::EmptyInput);
+        assert_eq!(
+            Calculator::new().eval("").unwrap_err(),
+            CalcError::EmptyInput
+        );
+        assert!(CalcError::EmptyInput.is_parse_error());
+        assert_eq!(CalcError::EmptyInput.position(), None);
+        assert_eq!(
+            CalcError::EmptyInput.render(""),
+            "empty input: enter an expression"
+        );
+    }
+
+    #[test]
+    fn test_list_builtins() {
+        let functions = list_functions();
+        assert!(functions.contains(&("sqrt", 1, Some(1))));
+        assert!(functions.contains(&("max", 1, None)));
+        assert!(functions.contains(&("if", 3, Some(3))));
+        for (name, min, max) in functions {
+            assert!(max.is_none_or(|max| max >= min), "{name}");
+        }
+        assert!(list_constants().contains(&("pi", std::f64::consts::PI)));
+    }
+
+    #[test]
+    fn test_unknown_name_suggestions() {
+        let err = eval("sqrrt(9)").unwrap_err();
+        assert_eq!(err, CalcError::UnknownFunction("sqrrt".to_string()));
+        assert_eq!(err.suggestion(), Some("sqrt"));
+        assert_eq!(
+            err.to_string(),
+            "unknown function: sqrrt (did you mean 'sqrt'?)"
+        );
+        assert_eq!(
+            eval("pii").unwrap_err().to_string(),
+            "unknown identifier: pii (did you mean 'pi'?)"
+        );
+        assert_eq!(eval("Maxx(1, 2)").unwrap_err().suggestion(), Some("max"));
+
+        let err = eval("zzzzz(1)").unwrap_err();
+        assert_eq!(err.suggestion(), None);
+        assert_eq!(err.to_string(), "unknown function: zzzzz");
+        assert_eq!(eval("x").unwrap_err().suggestion(), None);
+        assert_eq!(CalcError::DivideByZero.suggestion(), None);
+    }
+
+    #[test]
+    fn test_eval_batch() {
+        let mut ctx = Context::new();
+        ctx.set("x", 4.0);
+        let results = eval_batch(&["x * 2", "1 / 0", "x +", "sqrt(x)", "x * 2"], &ctx);
+        assert_eq!(results.len(), 5);
+        assert_eq!(results[0], Ok(8.0));
+        assert_eq!(results[1], Err(CalcError::DivideByZero));
+        assert!(matches!(results[2], Err(CalcError::ExpectedPrimary { .. })));
+        assert_eq!(results[3], Ok(2.0));
+        assert_eq!(results[4], Ok(8.0));
+        assert!(eval_batch(&[], &ctx).is_empty());
+    }
+
+    #[test]
+    fn test_compiled_expression() {
+        let f = compile("x^2 + 1").unwrap();
+        assert_eq!(f.expression(), &parse("x^2 + 1").unwrap());
+        let mut ctx = Context::new();
+        for x in 0..=100 {
+            ctx.set("x", f64::from(x));
+            assert_eq!(f.eval(&ctx).unwrap(), f64::from(x * x + 1));
+        }
+        assert_eq!(
+            f.eval(&Context::new()).unwrap_err(),
+            CalcError::UnknownIdentifier("x".to_string())
+        );
+        assert!(matches!(
+            compile("x^"),
+            Err(CalcError::ExpectedPrimary { .. })
+        ));
+    }
+
+    #[test]
+    fn test_caret_mode() {
+        let mut calc = Calculator::new();
+        assert_eq!(calc.caret_mode(), CaretMode::Power);
+        assert_eq!(calc.eval("2^3 == 8").unwrap(), 1.0);
+        calc.set_caret_mode(CaretMode::Xor);
+        assert_eq!(calc.eval("6^3 == 5").unwrap(), 1.0);
+        assert_eq!(calc.eval("1 + 6^3").unwrap(), 6.0);
+        assert_eq!(calc.eval("pow(2, 3)").unwrap(), 8.0);
+        assert_eq!(
+            calc.eval("1.5^2").unwrap_err(),
+            CalcError::NonIntegerOperand("^".to_string())
+        );
+        let calc = Calculator::builder().caret_mode(CaretMode::Xor).build();
+        assert_eq!(calc.eval("5^1").unwrap(), 4.0);
+        assert_eq!(eval("6^3").unwrap(), 216.0);
+    }
+
+    #[cfg(feature = "serde_json")]
+    #[test]
+    fn test_eval_json() {
+        let ok: serde_json::Value = serde_json::from_str(&eval_json("2+2")).unwrap();
+        assert_eq!(ok["input"], "2+2");
+        assert_eq!(ok["expression"], "2 + 2");
+        assert_eq!(ok["result"], 4.0);
+        assert_eq!(ok["ast"]["type"], "BinaryOp");
+        assert!(ok.get("error").is_none());
+
+        let err: serde_json::Value = serde_json::from_str(&eval_json("1/0")).unwrap();
+        assert!(err.get("result").is_none());
+        assert_eq!(err["error"]["kind"], "DivideByZero");
+        assert_eq!(err["error"]["category"], "Eval");
+        assert_eq!(err["error"]["message"], "division by zero");
+        assert!(err["error"]["position"].is_null());
+
+        let parse: serde_json::Value = serde_json::from_str(&eval_json("1 +")).unwrap();
+        assert!(parse["expression"].is_null());
+        assert_eq!(parse["error"]["kind"], "ExpectedPrimary");
+        assert_eq!(parse["error"]["category"], "Parse");
+        assert_eq!(parse["error"]["position"], 3);
+    }
+
+    #[test]
+    fn test_factor() {
+        assert_eq!(factor(360), "2^3 * 3^2 * 5");
+        assert_eq!(factor(97), "97");
+        assert_eq!(factor(1), "1");
+        assert_eq!(factor(0), "0");
+        assert_eq!(factor(-12), "-2^2 * 3");
+        assert_eq!(factor(-1), "-1");
+        assert_eq!(factor(1 << 40), "2^40");
+        assert_eq!(factor(i64::MIN), "-2^63");
+        assert_eq!(factor(600_851_475_143), "71 * 839 * 1471 * 6857");
+        for n in [360, 97, -12, 1_000_001] {
+            assert_eq!(eval_i64(&factor(n)).unwrap(), n);
+        }
+    }
+
+    #[test]
+    fn test_stacked_unary_operators() {
+        assert_eq!(eval("--5").unwrap(), 5.0);
+        assert_eq!(eval("-+-5").unwrap(), 5.0);
+        assert_eq!(eval("- - -5").unwrap(), -5.0);
+        assert_eq!(eval("3 - -2").unwrap(), 5.0);
+        assert_eq!(eval("3--2").unwrap(), 5.0);
+        assert_eq!(eval("3 + -+2").unwrap(), 1.0);
+        assert_eq!(eval("2 * -3").unwrap(), -6.0);
+        assert_eq!(eval("2^-3").unwrap(), 0.125);
+        assert_eq!(eval("2^--3").unwrap(), 8.0);
+        assert_eq!(eval("-2^-2").unwrap(), -0.25);
+        assert_eq!(eval("2^-(1 + 2)").unwrap(), 0.125);
+        assert_eq!(eval_i64("3 - -2").unwrap(), 5);
+        assert_eq!(eval_i64("--5").unwrap(), 5);
+        assert_eq!(eval_rational("2^-3").unwrap(), Rational::new(1, 8));
+        for input in ["--5", "-+-5", "3 - -2", "2^-3", "2^--3", "-2^-2"] {
+            let expr = parse(input).unwrap();
+            assert_eq!(parse(&expr.to_string()).unwrap(), expr, "{input}");
+        }
+    }
+
+    #[test]
+    fn test_calculator_case_sensitive() {
+        let mut calc = Calculator::builder()
+            .case_sensitive(true)
+            .function("f", 1, Some(1), |args| Ok(args[0] + 1.0))
+            .function("F", 1, Some(1), |args| Ok(args[0] * 10.0))
+            .constant("K", 2.0)
+            .build();
+        assert!(calc.case_sensitive());
+        assert_eq!(calc.eval("f(1)").unwrap(), 2.0);
+        assert_eq!(calc.eval("F(1)").unwrap(), 10.0);
+        assert_eq!(calc.eval("K").unwrap(), 2.0);
+        assert_eq!(
+            calc.eval("k"),
+            Err(CalcError::UnknownIdentifier("k".to_string()))
+        );
+        // Builtins keep working under any spelling.
+        assert_eq!(calc.eval("SQRT(16) + Abs(-1)").unwrap(), 5.0);
+        assert_eq!(calc.eval("PI").unwrap(), core::f64::consts::PI);
+        assert!(calc.has_function("Random"));
+
+        let mut ctx = Context::new();
+        ctx.set("x", 1.0);
+        ctx.set("X", 2.0);
+        assert_eq!(calc.eval_with("x + 10 * X", &ctx).unwrap(), 21.0);
+        let program = calc.parse_program("y = 3; Y = 4; y * Y").unwrap();
+        assert_eq!(calc.eval_program(&program, &mut ctx).unwrap(), 12.0);
+        assert_eq!(ctx.get("y"), Some(3.0));
+        assert_eq!(ctx.get("Y"), Some(4.0));
+
+        // Off by default: registered names match any spelling.
+        calc.set_case_sensitive(false);
+        calc.register_function("Twice", 1, Some(1), |args| Ok(args[0] * 2.0));
+        assert_eq!(calc.eval("TWICE(2) + twice(1)").unwrap(), 6.0);
+
+        // Names registered before switching keep their registered spelling.
+        let mut calc = Calculator::new();
+        calc.register_function("Foo", 0, Some(0), |_| Ok(7.0));
+        calc.register_constant("Bar", 3.0);
+        calc.set_case_sensitive(true);
+        assert_eq!(calc.eval("Foo() + Bar").unwrap(), 10.0);
+        assert_eq!(
+            calc.eval("foo()"),
+            Err(CalcError::UnknownFunction("foo".to_string()))
+        );
+        assert_eq!(calc.constant("bar"), None);
+    }
+
+    #[test]
+    fn test_to_postfix() {
+        let postfix = |input: &str| to_postfix(&tokenize(input).unwrap());
+        let ident = |name: &str| Token::Ident(name.to_string());
+        assert_eq!(
+            postfix("3 + 4 * 2").unwrap(),
+            vec![
+                Token::Number(3),
+                Token::Number(4),
+                Token::Number(2),
+                Token::Op("*"),
+                Token::Op("+"),
+                Token::Eof,
+            ]
+        );
+        assert_eq!(
+            postfix("max(1, 2*3)").unwrap(),
+            vec![
+                Token::Number(1),
+                Token::Number(2),
+                Token::Number(3),
+                Token::Op("*"),
+                ident("max"),
+                Token::OpenParen,
+                Token::Number(2),
+                Token::CloseParen,
+                Token::Eof,
+            ]
+        );
+        assert_eq!(
+            postfix("-2^2").unwrap(),
+            vec![
+                Token::Number(2),
+                Token::Number(2),
+                Token::Op("^"),
+                ident("neg"),
+                Token::Eof,
+            ]
+        );
+        assert_eq!(
+            postfix("sqrt(2.5)").unwrap(),
+            vec![
+                Token::Number(2),
+                Token::DecimalPoint,
+                Token::Fraction("5".to_string()),
+                ident("sqrt"),
+                Token::Eof,
+            ]
+        );
+
+        // The same tree as the parser builds, for every kind of operator.
+        let inputs = [
+            "1 - 2 - 3",
+            "2^3^2",
+            "-2^-2 + +1",
+            "(1 + 2) * 3",
+            "2pi(1 + 1)",
+            "200 + 10% - 50%",
+            "50% - 5",
+            "7 % 3 * 2",
+            "3! + 2^3!",
+            "√9 * 2",
+            "|1 - |2 - 5|| | 8",
+            "|(1 | 2)|",
+            "1 << 2 < 5 == 1 & 3",
+            "if(1 > 2, sin(0), max(1, 2, atan2(1, 2)))",
+            "rand() + f(x, y)",
+            "1.5 * 2_000",
+        ];
+        for input in inputs {
+            let via_tree = tokenize(&parse(input).unwrap().to_rpn().unwrap()).unwrap();
+            assert_eq!(postfix(input).unwrap(), via_tree, "{input}");
+        }
+
+        assert_eq!(postfix(""), Err(CalcError::EmptyInput));
+        assert_eq!(postfix("(1 + 2"), Err(CalcError::UnclosedParen { pos: 0 }));
+        assert_eq!(postfix("2 * f(1"), Err(CalcError::UnclosedParen { pos: 3 }));
+        assert!(matches!(
+            postfix("|1 + 2)"),
+            Err(CalcError::ExpectedToken {
+                expected: Token::Bar,
+                ..
+            })
+        ));
+        assert_eq!(
+            postfix("1 + 2)"),
+            Err(CalcError::UnexpectedCloseParen { pos: 3 })
+        );
+        assert!(matches!(
+            postfix("1 + * 2"),
+            Err(CalcError::ExpectedPrimary { .. })
+        ));
+        assert!(matches!(
+            postfix("(1, 2)"),
+            Err(CalcError::ExpectedToken { .. })
+        ));
+
+        // Iterative, so nesting is not limited like the parser's.
+        let deep = format!("{}1{}", "(".repeat(1000), ")".repeat(1000));
+        assert_eq!(postfix(&deep).unwrap(), vec![Token::Number(1), Token::Eof]);
+    }
+
+    #[test]
+    fn test_format_result() {
+        let options = |precision, notation| FormatOptions {
+            precision,
+            notation,
+            thousands_separator: None,
+        };
+        assert_eq!(
+            format_result(12345.0, options(None, Notation::Engineering)),
+            "12.345e3"
+        );
+        assert_eq!(
+            format_result(0.00042, options(Some(2), Notation::Scientific)),
+            "4.2e-4"
+        );
+        assert_eq!(
+            format_result(0.00042, options(None, Notation::Scientific)),
+            "4.2e-4"
+        );
+        assert_eq!(
+            format_result(0.00042, options(None, Notation::Engineering)),
+            "420e-6"
+        );
+        assert_eq!(
+            format_result(-123456.0, options(Some(2), Notation::Engineering)),
+            "-120e3"
+        );
+        assert_eq!(
+            format_result(1.5, options(Some(3), Notation::Engineering)),
+            "1.50e0"
+        );
+        assert_eq!(
+            format_result(999.96, options(Some(4), Notation::Engineering)),
+            "1.000e3"
+        );
+        assert_eq!(
+            format_result(2.0 / 3.0, options(Some(4), Notation::Fixed)),
+            "0.6667"
+        );
+        assert_eq!(
+            format_result(f64::NAN, options(Some(4), Notation::Engineering)),
+            "NaN"
+        );
+
+        let grouped = |value, precision| {
+            format_result(
+                value,
+                FormatOptions {
+                    precision,
+                    thousands_separator: Some(','),
+                    ..FormatOptions::default()
+                },
+            )
+        };
+        assert_eq!(grouped(1234567.5, None), "1,234,567.5");
+        assert_eq!(grouped(-1234567.5, Some(3)), "-1,230,000");
+        assert_eq!(grouped(999.0, None), "999");
+        assert_eq!(grouped(1000.0, None), "1,000");
+        assert_eq!(grouped(0.5, None), "0.5");
+    }
+
+    #[test]
+    fn test_iterated_sum_and_product() {
+        assert_eq!(eval("sum(i, 1, 4, i)").unwrap(), 10.0);
+        assert_eq!(eval("prod(i, 1, 4, i)").unwrap(), 24.0);
+        assert_eq!(eval("product(k, 1, 5, k)").unwrap(), 120.0);
+        assert_eq!(eval("sum(i, 1, 5, i^2)").unwrap(), 55.0);
+        assert_eq!(eval("sum(i, 1, 3, sum(j, 1, i, j))").unwrap(), 10.0);
+        // An empty range is the empty sum or product.
+        assert_eq!(eval("sum(i, 5, 1, i)").unwrap(), 0.0);
+        assert_eq!(eval("prod(i, 5, 1, i)").unwrap(), 1.0);
+
+        // The index shadows variables only inside the body, and like a `let`
+        // name it cannot be a constant.
+        let mut ctx = Context::new();
+        ctx.set("i", 100.0);
+        ctx.set("n", 3.0);
+        assert_eq!(eval_with("sum(i, 1, n, i) + i", &ctx).unwrap(), 106.0);
+        assert_eq!(
+            eval("sum(e, 1, 3, e)"),
+            Err(CalcError::ReservedName("e".to_string()))
+        );
+        assert_eq!(ctx.get("i"), Some(100.0));
+
+        // Other argument lists are the variadic builtins.
+        assert_eq!(eval("sum(1, 2, 3, 4)").unwrap(), 10.0);
+        assert_eq!(eval("prod(2, 3)").unwrap(), 6.0);
+        assert_eq!(eval_with("sum(n, 1)", &ctx).unwrap(), 4.0);
+
+        assert_eq!(
+            eval("sum(i, 1, 2.5, i)"),
+            Err(CalcError::DomainError {
+                name: "sum".to_string(),
+                arg: 2.5,
+            })
+        );
+        assert!(matches!(
+            eval("sum(i, 1, 1e12, i)"),
+            Err(CalcError::DomainError { .. })
+        ));
+        assert_eq!(
+            eval("sum(i, 1, 3, j)"),
+            Err(CalcError::UnknownIdentifier("j".to_string()))
+        );
+        assert_eq!(
+            eval("prod(i, 1, 3, 1/(i - 2))"),
+            Err(CalcError::DivideByZero)
+        );
+    }
+
+    #[test]
+    fn test_let_binding() {
+        assert_eq!(
+            parse("let r = 2 in pi*r^2").unwrap(),
+            Expression::LetBinding {
+                name: "r".to_string(),
+                value: Box::new(Expression::Number(2.0)),
+                body: Box::new(parse("pi*r^2").unwrap()),
+            }
+        );
+        assert_eq!(
+            eval("let r = 2 in pi*r^2").unwrap(),
+            4.0 * core::f64::consts::PI
+        );
+        // The inner binding shadows the outer one only in its own body.
+        assert_eq!(
+            eval("let x = 1 in (let x = x + 10 in x * 2) + x").unwrap(),
+            23.0
+        );
+        assert_eq!(eval("let a = 2, b = a + 1 in a * b").unwrap(), 6.0);
+        assert_eq!(eval("let a = 2 in let b = a + 1 in a * b").unwrap(), 6.0);
+        assert_eq!(eval("let a = let b = 3 in b * b in a + 1").unwrap(), 10.0);
+        assert_eq!(eval("1 + let x = 2 in x * 3").unwrap(), 7.0);
+        assert_eq!(eval("max(let x = 2 in x, 1)").unwrap(), 2.0);
+
+        // The value sees the outer variables; the binding does not leak out.
+        let mut ctx = Context::new();
+        ctx.set("x", 5.0);
+        assert_eq!(eval_with("let y = x * 2 in y + x", &ctx).unwrap(), 15.0);
+        let program = parse_program("let x = 1 in x; x").unwrap();
+        assert_eq!(eval_program(&program, &mut ctx).unwrap(), 5.0);
+
+        // `let` and `in` are only keywords in that position.
+        let program = parse_program("let = 3; in = 4; let * in").unwrap();
+        assert_eq!(eval_program(&program, &mut Context::new()).unwrap(), 12.0);
+        assert!(matches!(
+            parse("let x = 2 x"),
+            Err(CalcError::ExpectedToken {
+                got: Token::Eof,
+                ..
+            })
+        ));
+        assert_eq!(
+            eval("let pi = 3 in pi"),
+            Err(CalcError::ReservedName("pi".to_string()))
+        );
+
+        // Printing keeps the body together.
+        for input in [
+            "let r = 2 in pi * r^2",
+            "(let x = 2 in x) + 1",
+            "(let x = 2 in x)!",
+            "let a = let b = 3 in b in a",
+            "-let x = 1 in x + 1",
+        ] {
+            let expr = parse(input).unwrap();
+            assert_eq!(parse(&expr.to_string()).unwrap(), expr, "{input}");
+        }
+        assert_eq!(
+            parse("let a = 2, b = a in b").unwrap().to_string(),
+            "let a = 2 in let b = a in b"
+        );
+
+        // The exact evaluators bind values without a context.
+        assert_eq!(eval_i64("let x = 7 in x * x").unwrap(), 49);
+        assert_eq!(
+            eval_rational("let x = 1/3 in let x = x + x in x * 3").unwrap(),
+            Rational::from(2)
+        );
+        assert_eq!(
+            eval_complex("let z = 1 + i in z * z").unwrap(),
+            Complex::new(0.0, 2.0)
+        );
+        assert_eq!(
+            eval_complex("let i = 2 in i"),
+            Err(CalcError::ReservedName("i".to_string()))
+        );
+        assert_eq!(
+            parse("let x = 1 + 2 in x * x").unwrap().to_rpn(),
+            Err(CalcError::NoRpnForm("let".to_string()))
+        );
+        assert_eq!(
+            parse("let r = 2 in pi*r").unwrap().to_latex(),
+            r"\text{let } r = 2 \text{ in } \pi \cdot r"
+        );
+    }
+
+    #[test]
+    fn test_chained_let_bindings() {
+        // Each binding doubles the last; substituting the values into the body
+        // instead of binding them would copy the chain 2^29 times.
+        let bindings: Vec<String> = (1..30)
+            .map(|i| format!("a{i} = a{0} + a{0}", i - 1))
+            .collect();
+        let input = format!("let a0 = 1, {} in a29", bindings.join(", "));
+        assert_eq!(eval(&input).unwrap(), 2f64.powi(29));
+        assert_eq!(eval_i64(&input).unwrap(), 1 << 29);
+        assert_eq!(eval_rational(&input).unwrap(), Rational::from(1 << 29));
+        assert_eq!(eval_complex(&input).unwrap(), Complex::from(2f64.powi(29)));
+        #[cfg(feature = "bigint")]
+        assert_eq!(eval_bigint(&input).unwrap(), (1 << 29).to_string());
+        assert_eq!(
+            parse(&input).unwrap().to_rpn(),
+            Err(CalcError::NoRpnForm("let".to_string()))
+        );
+    }
+
+    #[test]
+    fn test_unbalanced_parentheses() {
+        assert_eq!(parse("(1+2"), Err(CalcError::UnclosedParen { pos: 0 }));
+        assert_eq!(
+            parse("1+2)"),
+            Err(CalcError::UnexpectedCloseParen { pos: 3 })
+        );
+        // The unclosed one is the outer `(`, reported where it opens.
+        assert_eq!(parse("((1)"), Err(CalcError::UnclosedParen { pos: 0 }));
+        assert_eq!(parse("(1 + (2)"), Err(CalcError::UnclosedParen { pos: 0 }));
+        assert_eq!(
+            parse("2 * max(1, 2"),
+            Err(CalcError::UnclosedParen { pos: 7 })
+        );
+        assert_eq!(
+            parse("(1))"),
+            Err(CalcError::UnexpectedCloseParen { pos: 3 })
+        );
+        assert_eq!(
+            parse_program("x = 1); x"),
+            Err(CalcError::UnexpectedCloseParen { pos: 5 })
+        );
+
+        let err = parse("((1)").unwrap_err();
+        assert_eq!(err.kind(), ErrorKind::Parse);
+        assert_eq!(err.render("((1)"), "((1)\n^ unclosed '(': missing ')'");
+        assert_eq!(
+            parse("1+2)").unwrap_err().render("1+2)"),
+            "1+2)\n   ^ unmatched ')'"
+        );
+
+        // Other mismatches keep their general errors.
+        assert!(matches!(
+            parse("(1 2)"),
+            Err(CalcError::ExpectedToken { .. })
+        ));
+        assert!(matches!(
+            parse("1 + )"),
+            Err(CalcError::ExpectedPrimary { .. })
+        ));
+    }
+
+    #[test]
+    fn test_min_max_nan() {
+        assert!(eval("max(nan, 1)").unwrap().is_nan());
+        assert!(eval("min(1, nan, 0)").unwrap().is_nan());
+        assert_eq!(eval("fmax(nan, 1)").unwrap(), 1.0);
+        assert_eq!(eval("fmin(3, nan, 2)").unwrap(), 2.0);
+        assert!(eval("fmax(nan, nan)").unwrap().is_nan());
+        assert_eq!(
+            eval("max(3, 1, 2)").unwrap(),
+            eval("fmax(3, 1, 2)").unwrap()
+        );
+        assert_eq!(eval("min(inf, -inf)").unwrap(), f64::NEG_INFINITY);
+        // `sqrt(-1)` is NaN, so bad data shows up in the result.
+        assert!(eval("max(sqrt(-1), 0)").unwrap().is_nan());
+    }
+
+    #[test]
+    fn test_round_to_digits() {
+        assert_eq!(eval("round(3.14159, 0)").unwrap(), 3.0);
+        assert_eq!(eval("round(3.14159, 2)"), eval("3.14"));
+        assert_eq!(eval("round(3.14159, 4)"), eval("3.1416"));
+        // Decimal halves round away from zero even when the double is just below.
+        assert_eq!(eval("round(2.675, 2)").unwrap(), 2.68);
+        assert_eq!(eval("round(1.005, 2)").unwrap(), 1.01);
+        assert_eq!(eval("round(-2.675, 2)").unwrap(), -2.68);
+        assert_eq!(eval("round(2.5, 0)").unwrap(), 3.0);
+        assert_eq!(eval("round(0.0004, 2)").unwrap(), 0.0);
+        assert_eq!(eval("round(0.006, 2)").unwrap(), 0.01);
+        assert_eq!(eval("round(9.996, 2)").unwrap(), 10.0);
+        assert_eq!(eval("round(123.456, 10)").unwrap(), 123.456);
+        assert_eq!(eval("round(1e300, 2)").unwrap(), 1e300);
+        assert_eq!(eval("round(inf, 2)").unwrap(), f64::INFINITY);
+        for digits in ["-1", "1.5", "nan"] {
+            assert!(matches!(
+                eval(&format!("round(1.25, {digits})")),
+                Err(CalcError::DomainError { ref name, .. }) if name == "round"
+            ));
+        }
+    }
+
+    #[test]
+    fn test_lerp_and_clamp01() {
+        assert_eq!(eval("lerp(0, 10, 0.5)").unwrap(), 5.0);
+        assert_eq!(eval("lerp(10, 20, 0)").unwrap(), 10.0);
+        assert_eq!(eval("lerp(10, 20, 1)").unwrap(), 20.0);
+        assert_eq!(eval("lerp(0, 10, 1.5)").unwrap(), 15.0);
+        assert_eq!(eval("clamp01(1.5)").unwrap(), 1.0);
+        assert_eq!(eval("clamp01(-0.2)").unwrap(), 0.0);
+        assert_eq!(eval("clamp01(0.25)").unwrap(), 0.25);
+        assert!(eval("clamp01(nan)").unwrap().is_nan());
+        assert!(matches!(
+            eval("lerp(1, 2)"),
+            Err(CalcError::WrongArity { .. })
+        ));
+    }
+
+    #[test]
+    fn test_normalize_angle() {
+        use std::f64::consts::{PI, TAU};
+        assert_close(eval("normalize_angle(2*pi + 0.5)").unwrap(), 0.5);
+        assert_close(eval("normalize_angle(-0.5)").unwrap(), TAU - 0.5);
+        assert_close(eval("normalize_angle(7*tau + 1)").unwrap(), 1.0);
+        assert_close(eval("normalize_angle(-5*tau - pi)").unwrap(), PI);
+        assert_eq!(eval("normalize_angle(0)").unwrap(), 0.0);
+        assert_eq!(eval("normalize_angle(tau)").unwrap(), 0.0);
+        // A remainder too small to survive `+ tau` still lands in range.
+        assert_eq!(eval("normalize_angle(-1e-20)").unwrap(), 0.0);
+        for x in [-100.0, -TAU, -1.0, 3.0, 50.0, 1e6] {
+            let wrapped = eval(&format!("normalize_angle({x})")).unwrap();
+            assert!((0.0..TAU).contains(&wrapped), "{x} wrapped to {wrapped}");
+        }
+        assert!(eval("normalize_angle(inf)").unwrap().is_nan());
+
+        let calc = Calculator::builder().angle_mode(AngleMode::Degrees).build();
+        assert_close(calc.eval("normalize_angle(370)").unwrap(), 10.0);
+        assert_close(calc.eval("normalize_angle(-90)").unwrap(), 270.0);
+        assert_close(calc.eval("normalize_angle(3 * 360 + 45)").unwrap(), 45.0);
+    }
+
+    #[test]
+    fn test_leading_decimal_point() {
+        assert_eq!(eval(".5").unwrap(), 0.5);
+        assert_eq!(eval(".025").unwrap(), 0.025);
+        assert_eq!(eval(".25 + .25").unwrap(), 0.5);
+        assert_eq!(eval("1+.5==1.5").unwrap(), 1.0);
+        assert_eq!(eval("-.5").unwrap(), -0.5);
+        assert_eq!(eval(".5e2").unwrap(), 50.0);
+        assert_eq!(eval("max(.5, .25)").unwrap(), 0.5);
+        assert_eq!(eval("7 % .5").unwrap(), 0.0);
+        assert_eq!(eval_rpn(".5 .25 +").unwrap(), 0.75);
+        assert_eq!(parse(".5").unwrap().to_string(), "0.5");
+        assert_eq!(
+            eval("."),
+            Err(CalcError::ExpectedPrimary {
+                got: Token::DecimalPoint,
+                pos: 0
+            })
+        );
+    }
+
+    #[test]
+    fn test_constant_nodes() {
+        assert_eq!(
+            parse("pi").unwrap(),
+            Expression::Constant {
+                name: "pi".to_string(),
+                value: std::f64::consts::PI
+            }
+        );
+        assert_eq!(parse("x").unwrap(), Expression::Identifier("x".to_string()));
+        assert!(matches!(
+            parse("PI").unwrap(),
+            Expression::Constant { ref name, .. } if name == "PI"
+        ));
+        assert!(matches!(parse("e(2)"), Ok(Expression::FunctionCall { .. })));
+        assert_eq!(parse("2*pi").unwrap().to_string(), "2 * pi");
+        assert_eq!(parse("(e)").unwrap().simplify().to_string(), "e");
+        assert_eq!(
+            parse("tau + x").unwrap().to_tree_string(),
+            "BinaryOp +\n  Constant tau\n  Identifier x\n"
+        );
+
+        // Context variables still shadow constants when evaluating.
+        let mut ctx = Context::new();
+        ctx.set("e", 2.0);
+        assert_eq!(eval_with("e * 3", &ctx).unwrap(), 6.0);
+        assert_eq!(eval_i64("pi").unwrap_err(), CalcError::NonInteger);
+    }
+
+    #[test]
+    fn test_validate() {
+        use std::collections::BTreeSet;
+        let known = |names: &[&str]| -> BTreeSet<String> {
+            names.iter().map(|name| name.to_string()).collect()
+        };
+        let check = |input: &str, names: &[&str]| validate(&parse(input).unwrap(), &known(names));
+
+        assert_eq!(check("x + sin(y)", &["x", "y"]), Ok(()));
+        assert_eq!(
+            check("x + sin(y)", &["x"]),
+            Err(CalcError::UnknownIdentifier("y".to_string()))
+        );
+        assert_eq!(
+            check("foo(x) + z", &["x"]),
+            Err(CalcError::UnknownFunction("foo".to_string()))
+        );
+        // The first offender from the left is reported.
+        assert_eq!(
+            check("a * b", &[]),
+            Err(CalcError::UnknownIdentifier("a".to_string()))
+        );
+        assert_eq!(check("2 * PI + e + if(1, 2, 3)", &[]), Ok(()));
+
+        // `let` and iterated sums bind their names only inside the body.
+        assert_eq!(check("let r = 2 in pi * r^2", &[]), Ok(()));
+        assert_eq!(
+            check("(let r = 2 in r) + r", &[]),
+            Err(CalcError::UnknownIdentifier("r".to_string()))
+        );
+        assert_eq!(
+            check("let r = r in r", &[]),
+            Err(CalcError::UnknownIdentifier("r".to_string()))
+        );
+        assert_eq!(check("sum(k, 1, n, k^2)", &["n"]), Ok(()));
+        assert_eq!(
+            check("sum(k, 1, k, 1)", &[]),
+            Err(CalcError::UnknownIdentifier("k".to_string()))
+        );
+        // Names are only checked, so a division by zero is still valid.
+        assert_eq!(check("x / 0", &["x"]), Ok(()));
+    }
+
+    #[test]
+    fn test_free_variables() {
+        let free = |input: &str| parse(input).unwrap().free_variables();
+        let names = |names: &[&str]| -> BTreeSet<String> {
+            names.iter().map(|name| name.to_string()).collect()
+        };
+        assert_eq!(free("x + y*2 + pi"), names(&["x", "y"]));
+        assert_eq!(free("let r = 1 in r*x"), names(&["x"]));
+        assert_eq!(free("z + b + a + z"), names(&["a", "b", "z"]));
+        assert_eq!(free("(let r = 1 in r) + r"), names(&["r"]));
+        assert_eq!(free("let r = r in r"), names(&["r"]));
+        assert_eq!(free("sum(k, 1, n, k*x)"), names(&["n", "x"]));
+        assert_eq!(free("f(a, 2)"), names(&["a"]));
+        assert!(free("2 * e + sin(tau)").is_empty());
+    }
+
+    #[test]
+    fn test_whitespace_characters() {
+        for input in [
+            "1\t+\t2",
+            "1\n+\n2",
+            "1\r\n+ 2",
+            "  1 + 2  ",
+            "\n\t1+2\n",
+            "1\u{a0}+\u{3000}2",
+        ] {
+            assert_eq!(eval(input).unwrap(), 3.0, "{input:?}");
+        }
+        assert_eq!(
+            tokenize("1\t+2").unwrap(),
+            vec![
+                Token::Number(1),
+                Token::Op("+"),
+                Token::Number(2),
+                Token::Eof
+            ]
+        );
+        assert_eq!(eval(" \t\n"), Err(CalcError::EmptyInput));
+        let mut ctx = Context::new();
+        assert_eq!(
+            eval_program(&parse_program("x = 2;\n x + 1").unwrap(), &mut ctx).unwrap(),
+            3.0
+        );
+    }
+
+    #[test]
+    fn test_comma_thousands_separators() {
+        let calc = Calculator::builder().comma_separators(true).build();
+        assert!(calc.comma_separators());
+        assert_eq!(calc.eval("1,000 + 1 == 1001").unwrap(), 1.0);
+        assert_eq!(calc.eval("1,234,567.5").unwrap(), 1_234_567.5);
+        assert_eq!(calc.eval("-2,500e-3").unwrap(), -2.5);
+        // Argument commas are untouched unless three digits follow directly.
+        assert_eq!(calc.eval("max(1, 2)").unwrap(), 2.0);
+        assert_eq!(calc.eval("max(1,2)").unwrap(), 2.0);
+        assert_eq!(calc.eval("max(1,2345)").unwrap(), 2345.0);
+        assert_eq!(calc.eval("max(1,234)").unwrap(), 1234.0);
+        assert_eq!(calc.eval("max(1,000, 2)").unwrap(), 1000.0);
+        assert_eq!(
+            calc.parse_program("x = 1,000; x * 2")
+                .map(|program| program.len()),
+            Ok(2)
+        );
+
+        // Off by default, where `1,000` is two arguments.
+        assert_eq!(eval("max(1,000)").unwrap(), 1.0);
+        assert!(matches!(
+            Calculator::new().eval("1,000"),
+            Err(CalcError::UnexpectedTokenAfterExpression { .. })
+        ));
+    }
+
+    #[test]
+    fn test_step_budget() {
+        let mut ctx = Context::new();
+        assert_eq!(ctx.step_budget(), None);
+        // `1+2+3+4+5` is 9 nodes.
+        ctx.set_step_budget(Some(9));
+        assert_eq!(eval_with("1+2+3+4+5", &ctx).unwrap(), 15.0);
+        ctx.set_step_budget(Some(8));
+        assert_eq!(eval_with("1+2+3+4+5", &ctx), Err(CalcError::BudgetExceeded));
+        assert!(CalcError::BudgetExceeded.is_eval_error());
+
+        // Sum terms and `let` bodies draw on the same budget.
+        ctx.set_step_budget(Some(1000));
+        assert_eq!(eval_with("sum(k, 1, 100, k)", &ctx).unwrap(), 5050.0);
+        assert_eq!(
+            eval_with("sum(k, 1, 1e5, k)", &ctx),
+            Err(CalcError::BudgetExceeded)
+        );
+        assert_eq!(
+            eval_with("let x = 1 in sum(k, 1, 1000, x)", &ctx),
+            Err(CalcError::BudgetExceeded)
+        );
+
+        // Each evaluation starts over with the full budget.
+        let mut calc = Calculator::new();
+        calc.context_mut().set_step_budget(Some(4));
+        assert_eq!(calc.eval("2 * 3").unwrap(), 6.0);
+        assert_eq!(calc.eval("2 * 3").unwrap(), 6.0);
+        assert_eq!(calc.eval("1 + 2 * 3"), Err(CalcError::BudgetExceeded));
+    }
+
+    #[test]
+    fn test_percent_helpers() {
+        assert_eq!(eval("percent_change(100, 150)").unwrap(), 50.0);
+        assert_eq!(eval("percent_change(200, 150)").unwrap(), -25.0);
+        assert_eq!(eval("percent_change(-50, -25)").unwrap(), -50.0);
+        assert_eq!(eval("percent_of(25, 200)").unwrap(), 12.5);
+        assert_eq!(eval("percent_of(0, 5)").unwrap(), 0.0);
+        assert_eq!(eval("percent_change(0, 5)"), Err(CalcError::DivideByZero));
+        assert_eq!(eval("percent_of(5, 0)"), Err(CalcError::DivideByZero));
+    }
+
+    #[test]
+    fn test_cbrt() {
+        assert_eq!(eval("cbrt(27)").unwrap(), 3.0);
+        assert_eq!(eval("cbrt(-8)").unwrap(), -2.0);
+        assert_eq!(eval("cbrt(0)").unwrap(), 0.0);
+        assert_eq!(eval("cbrt(1e-9)").unwrap(), 1e-3);
+        assert!(eval("(-8)^(1/3)").unwrap().is_nan());
+        assert_eq!(eval("cbrt(-inf)").unwrap(), f64::NEG_INFINITY);
+        assert_eq!(parse("cbrt(x+1)").unwrap().to_latex(), r"\sqrt[3]{x + 1}");
+    }
+
+    #[test]
+    fn test_series_bounds_beyond_exact_integers() {
+        // Past 2^53 the index could not advance; such bounds are rejected
+        // instead of looping forever.
+        assert!(matches!(
+            eval("sum(i, 1e17, 1e17+100, 1)"),
+            Err(CalcError::DomainError { ref name, .. }) if name == "sum"
+        ));
+        assert!(matches!(
+            eval("prod(i, -1e17, -1e17+2, 1)"),
+            Err(CalcError::DomainError { .. })
+        ));
+        assert_eq!(eval("sum(i, 2^53 - 2, 2^53, 1)").unwrap(), 3.0);
+    }
+
+    #[test]
+    fn test_eval_i64_min_divided_by_minus_one() {
+        for input in ["-(2^62)*2 / -1", "-(2^62)*2 // -1"] {
+            assert_eq!(eval_i64(input), Err(CalcError::IntegerOverflow), "{input}");
+        }
+        assert_eq!(
+            eval_i64("-(2^62)*2 % (-1)"),
+            Err(CalcError::IntegerOverflow)
+        );
+        assert_eq!(eval_i64("-(2^62)*2 / 2").unwrap(), -(1 << 62));
+    }
+
+    #[test]
+    fn test_registered_constant_overrides_builtin() {
+        let mut calc = Calculator::new();
+        calc.register_constant("e", 2.0);
+        calc.register_constant("TAU", 3.0);
+        assert_eq!(calc.constant("e"), Some(2.0));
+        assert_eq!(calc.eval("e").unwrap(), 2.0);
+        assert_eq!(calc.eval("tau * e").unwrap(), 6.0);
+        let expr = calc.parse("e + 1").unwrap();
+        assert_eq!(calc.eval_expression(&expr).unwrap(), 3.0);
+        // Other calculators and the free functions keep the builtin values.
+        assert_eq!(Calculator::new().eval("e").unwrap(), std::f64::consts::E);
+        assert_eq!(expr.evaluate().unwrap(), std::f64::consts::E + 1.0);
+    }
+
+    #[test]
+    fn test_complex_negation_keeps_principal_branch() {
+        use std::f64::consts::PI;
+        let ln = eval_complex("ln(-1)").unwrap();
+        assert_eq!(ln, eval_complex("ln(0-1)").unwrap());
+        assert_close(ln.re, 0.0);
+        assert_close(ln.im, PI);
+
+        let root = eval_complex("(-8)^(1/3)").unwrap();
+        assert_close(root.re, 1.0);
+        assert_close(root.im, 3f64.sqrt());
+        let root = eval_complex("(-1)^0.5").unwrap();
+        assert_close(root.re, 0.0);
+        assert_close(root.im, 1.0);
+        assert_eq!(eval_complex("sqrt(-4)").unwrap(), Complex::new(0.0, 2.0));
+        assert!((-Complex::new(1.0, 0.0)).im.is_sign_positive());
+    }
+
+    #[test]
+    fn test_median_nan() {
+        assert!(eval("median(nan, 1, 2)").unwrap().is_nan());
+        assert!(eval("median(1, 2, 3, nan)").unwrap().is_nan());
+        assert_eq!(eval("median(3, 1, 2)").unwrap(), 2.0);
+        assert_eq!(eval("median(inf, 1, 2)").unwrap(), 2.0);
+    }
 }
@@ -1,29 +1,313 @@
-use std::io;
+use rustcalc::{Calculator, Context, NumberFormat, Token, format_number};
 
-fn main() {
-    loop {
-        let input = read_input();
+const ANS: &str = "ans";
+const MEM: &str = "mem";
 
+fn main() {
+    let calc = Calculator::new();
+    let mut ctx = Context::new();
+    let mut registers = Registers::default();
+    let mut format = NumberFormat::default();
+    let mut reader = input::Reader::new();
+    while let Some(mut input) = reader.read_line("> ") {
         if input == "exit" {
             break;
         }
+        while needs_more_input(&input) {
+            let Some(line) = reader.read_line("... ") else {
+                break;
+            };
+            input.push(' ');
+            input.push_str(&line);
+        }
+        if input.is_empty() {
+            continue;
+        }
+        if let Some(command) = input.strip_prefix(':') {
+            if let Err(message) = run_command(command, &mut format) {
+                eprintln!("Error: {message}");
+            }
+            continue;
+        }
+        if let Some(result) = registers.memory_command(&input) {
+            match result {
+                Ok(Some(value)) => println!("Memory: {}", format_number(value, format)),
+                Ok(None) => println!("Memory cleared"),
+                Err(message) => eprintln!("Error: {message}"),
+            }
+            continue;
+        }
 
-        match rustcalc::parse(&input) {
-            Ok(expr) => {
-                println!("Parsed Expression: {:?}", expr);
-                match rustcalc::eval_expression(&expr) {
-                    Ok(value) => println!("Evaluated Expression: {}", value),
+        registers.bind(&mut ctx);
+        match calc.parse_program(&input) {
+            Ok(statements) => {
+                println!("Parsed Expression: {:?}", statements);
+                match calc.eval_program(&statements, &mut ctx) {
+                    Ok(value) => {
+                        println!("Evaluated Expression: {}", format_number(value, format));
+                        registers.ans = Some(value);
+                    }
                     Err(err) => eprintln!("Error: {err}"),
                 }
             }
-            Err(err) => eprintln!("Error: {err}"),
+            Err(err) => eprintln!("{}", err.render(&input)),
+        }
+    }
+    reader.save_history();
+}
+
+// An input continues on the next line while a parenthesis is still open or it
+// ends with an operator, comma or `=` that is waiting for an operand. A postfix
+// operator (`50%`) can end an input, so those only continue if the input does
+// not parse yet.
+fn needs_more_input(input: &str) -> bool {
+    let Ok(tokens) = rustcalc::tokenize(input) else {
+        return false;
+    };
+    let depth: i64 = tokens
+        .iter()
+        .map(|token| match token {
+            Token::OpenParen => 1,
+            Token::CloseParen => -1,
+            _ => 0,
+        })
+        .sum();
+    if depth > 0 {
+        return true;
+    }
+    let trailing = tokens.iter().rev().nth(1);
+    matches!(trailing, Some(Token::Op(_) | Token::Comma | Token::Equals))
+        && rustcalc::parse_program(input).is_err()
+}
+
+// Values kept between lines: `ans` is the previous result and `mem` the
+// pocket-calculator memory register.
+#[derive(Debug, Default)]
+struct Registers {
+    ans: Option<f64>,
+    memory: Option<f64>,
+}
+
+impl Registers {
+    // Exposes the registers as variables; until one holds a value its name
+    // stays unbound.
+    fn bind(&self, ctx: &mut Context) {
+        if let Some(value) = self.ans {
+            ctx.set(ANS, value);
+        }
+        match self.memory {
+            Some(value) => ctx.set(MEM, value),
+            None => {
+                ctx.remove(MEM);
+            }
+        }
+    }
+
+    // `M+` adds the last result to memory, `MR` shows it and `MC` clears it
+    // (case-insensitive). Returns `None` for any other input, and otherwise the
+    // memory afterwards.
+    fn memory_command(&mut self, input: &str) -> Option<Result<Option<f64>, String>> {
+        let result = match input.to_ascii_uppercase().as_str() {
+            "M+" => match self.ans {
+                Some(value) => {
+                    self.memory = Some(self.memory.unwrap_or(0.0) + value);
+                    Ok(self.memory)
+                }
+                None => Err("no result to add to memory".to_string()),
+            },
+            "MR" => match self.memory {
+                Some(value) => Ok(Some(value)),
+                None => Err("memory is empty".to_string()),
+            },
+            "MC" => {
+                self.memory = None;
+                Ok(None)
+            }
+            _ => return None,
+        };
+        Some(result)
+    }
+}
+
+const HELP: &str = "\
+Enter an expression such as `2 * (3 + 4)` or an assignment such as `x = 5`.
+Commands:
+  :help             show this help
+  :functions        list the builtin functions
+  :constants        list the builtin constants
+  :precision [N]    print N significant digits (no N resets)
+  :sci on|off       scientific notation for very large or small results
+  :factor <expr>    prime factorization of an integer
+  M+ / MR / MC      add the last result to memory, show it, clear it
+  exit              quit
+`ans` is the previous result, `mem` the memory, and `random()` /
+`random(lo, hi)` give random numbers.";
+
+// `:precision N` sets significant digits (`:precision` alone resets it);
+// `:sci on|off` toggles scientific notation for extreme magnitudes.
+// `:help`, `:functions` and `:constants` print reference information, and
+// `:factor <expr>` prints the prime factorization of an integer expression.
+fn run_command(command: &str, format: &mut NumberFormat) -> Result<(), String> {
+    if let Some(arg) = command.strip_prefix("factor ") {
+        let n = rustcalc::eval_i64(arg).map_err(|err| err.to_string())?;
+        println!("{}", rustcalc::factor(n));
+        return Ok(());
+    }
+    let mut words = command.split_whitespace();
+    match (words.next(), words.next(), words.next()) {
+        (Some("precision"), None, None) => format.precision = None,
+        (Some("precision"), Some(digits), None) => match digits.parse::<usize>() {
+            Ok(digits) if digits > 0 => format.precision = Some(digits),
+            _ => return Err(format!("invalid precision: {digits}")),
+        },
+        (Some("sci"), Some("on"), None) => format.scientific = true,
+        (Some("sci"), Some("off"), None) => format.scientific = false,
+        (Some("help"), None, None) => println!("{HELP}"),
+        (Some("functions"), None, None) => {
+            for (name, min, max) in rustcalc::list_functions() {
+                println!("  {name}({})", arity(min, max));
+            }
+        }
+        (Some("constants"), None, None) => {
+            for (name, value) in rustcalc::list_constants() {
+                println!("  {name} = {}", format_number(value, *format));
+            }
+        }
+        _ => return Err(format!("unknown command: :{command}")),
+    }
+    Ok(())
+}
+
+// `1`, `1-2` or `1+` (any number from 1 up) arguments.
+fn arity(min: usize, max: Option<usize>) -> String {
+    match max {
+        Some(max) if max == min => min.to_string(),
+        Some(max) => format!("{min}-{max}"),
+        None => format!("{min}+"),
+    }
+}
+
+#[cfg(feature = "rustyline")]
+mod input {
+    use rustyline::DefaultEditor;
+    use rustyline::error::ReadlineError;
+    use std::path::PathBuf;
+
+    pub struct Reader {
+        editor: DefaultEditor,
+        history: Option<PathBuf>,
+    }
+
+    impl Reader {
+        pub fn new() -> Self {
+            let mut editor = DefaultEditor::new().expect("Failed to start line editor");
+            let history = dirs::config_dir().map(|dir| dir.join("rustcalc").join("history.txt"));
+            if let Some(path) = &history {
+                // A missing history file just means this is the first session.
+                let _ = editor.load_history(path);
+            }
+            Reader { editor, history }
+        }
+
+        // Ctrl-D and Ctrl-C end the session like `exit`.
+        pub fn read_line(&mut self, prompt: &str) -> Option<String> {
+            match self.editor.readline(prompt) {
+                Ok(line) => {
+                    let line = line.trim().to_string();
+                    if !line.is_empty() {
+                        let _ = self.editor.add_history_entry(line.as_str());
+                    }
+                    Some(line)
+                }
+                Err(ReadlineError::Eof | ReadlineError::Interrupted) => None,
+                Err(err) => panic!("Failed to read line: {err}"),
+            }
+        }
+
+        pub fn save_history(&mut self) {
+            let Some(path) = &self.history else {
+                return;
+            };
+            if let Some(dir) = path.parent() {
+                let _ = std::fs::create_dir_all(dir);
+            }
+            if let Err(err) = self.editor.save_history(path) {
+                eprintln!("Failed to save history: {err}");
+            }
         }
     }
 }
 
-fn read_input() -> String {
-    println!();
-    let mut input = String::new();
-    io::stdin().read_line(&mut input).expect("Failed to read line");
-    input.trim().to_string()
+#[cfg(not(feature = "rustyline"))]
+mod input {
+    use std::io;
+
+    pub struct Reader;
+
+    impl Reader {
+        pub fn new() -> Self {
+            Reader
+        }
+
+        // Without line editing there is no prompt, just a blank line.
+        pub fn read_line(&mut self, _prompt: &str) -> Option<String> {
+            println!();
+            let mut input = String::new();
+            let read = io::stdin()
+                .read_line(&mut input)
+                .expect("Failed to read line");
+            if read == 0 {
+                return None;
+            }
+            Some(input.trim().to_string())
+        }
+
+        pub fn save_history(&mut self) {}
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_memory_register() {
+        let calc = Calculator::new();
+        let mut ctx = Context::new();
+        let mut registers = Registers::default();
+        assert_eq!(registers.memory_command("2 + 2"), None);
+        assert!(registers.memory_command("M+").unwrap().is_err());
+        assert!(registers.memory_command("MR").unwrap().is_err());
+
+        registers.ans = Some(calc.eval("6 * 7").unwrap());
+        assert_eq!(registers.memory_command("M+"), Some(Ok(Some(42.0))));
+        registers.ans = Some(8.0);
+        assert_eq!(registers.memory_command("m+"), Some(Ok(Some(50.0))));
+        assert_eq!(registers.memory_command("MR"), Some(Ok(Some(50.0))));
+
+        registers.bind(&mut ctx);
+        assert_eq!(calc.eval_with("mem / 2 + ans", &ctx).unwrap(), 33.0);
+
+        assert_eq!(registers.memory_command("MC"), Some(Ok(None)));
+        registers.bind(&mut ctx);
+        assert_eq!(
+            calc.eval_with("mem", &ctx).unwrap_err(),
+            rustcalc::CalcError::UnknownIdentifier("mem".to_string())
+        );
+    }
+
+    #[test]
+    fn test_needs_more_input() {
+        assert!(needs_more_input("(1 + 2"));
+        assert!(needs_more_input("max(1,"));
+        assert!(needs_more_input("2 *"));
+        assert!(needs_more_input("x ="));
+        assert!(needs_more_input("(1 + (2 * 3) -"));
+        assert!(!needs_more_input("1 + 2"));
+        assert!(!needs_more_input("50%"));
+        assert!(!needs_more_input("3!"));
+        assert!(!needs_more_input("(1 + 2))"));
+        assert!(!needs_more_input("exit"));
+        assert!(!needs_more_input("1 @"));
+    }
 }
@@ -0,0 +1,96 @@
+// Under `no_std`, `f64` lacks the math methods that `std` provides. This trait
+// supplies them from `libm` under the same names, so call sites read the same
+// in both builds.
+pub(crate) trait FloatExt {
+    fn sqrt(self) -> f64;
+    fn cbrt(self) -> f64;
+    fn sin(self) -> f64;
+    fn cos(self) -> f64;
+    fn tan(self) -> f64;
+    fn asin(self) -> f64;
+    fn acos(self) -> f64;
+    fn atan(self) -> f64;
+    fn atan2(self, x: f64) -> f64;
+    fn exp(self) -> f64;
+    fn ln(self) -> f64;
+    fn log10(self) -> f64;
+    fn log2(self) -> f64;
+    fn log(self, base: f64) -> f64;
+    fn powf(self, n: f64) -> f64;
+    fn powi(self, n: i32) -> f64;
+    fn hypot(self, other: f64) -> f64;
+    fn floor(self) -> f64;
+    fn ceil(self) -> f64;
+    fn round(self) -> f64;
+    fn trunc(self) -> f64;
+    fn fract(self) -> f64;
+}
+
+impl FloatExt for f64 {
+    fn sqrt(self) -> f64 {
+        libm::sqrt(self)
+    }
+    fn cbrt(self) -> f64 {
+        libm::cbrt(self)
+    }
+    fn sin(self) -> f64 {
+        libm::sin(self)
+    }
+    fn cos(self) -> f64 {
+        libm::cos(self)
+    }
+    fn tan(self) -> f64 {
+        libm::tan(self)
+    }
+    fn asin(self) -> f64 {
+        libm::asin(self)
+    }
+    fn acos(self) -> f64 {
+        libm::acos(self)
+    }
+    fn atan(self) -> f64 {
+        libm::atan(self)
+    }
+    fn atan2(self, x: f64) -> f64 {
+        libm::atan2(self, x)
+    }
+    fn exp(self) -> f64 {
+        libm::exp(self)
+    }
+    fn ln(self) -> f64 {
+        libm::log(self)
+    }
+    fn log10(self) -> f64 {
+        libm::log10(self)
+    }
+    fn log2(self) -> f64 {
+        libm::log2(self)
+    }
+    fn log(self, base: f64) -> f64 {
+        libm::log(self) / libm::log(base)
+    }
+    fn powf(self, n: f64) -> f64 {
+        libm::pow(self, n)
+    }
+    fn powi(self, n: i32) -> f64 {
+        libm::pow(self, n as f64)
+    }
+    fn hypot(self, other: f64) -> f64 {
+        libm::hypot(self, other)
+    }
+    fn floor(self) -> f64 {
+        libm::floor(self)
+    }
+    fn ceil(self) -> f64 {
+        libm::ceil(self)
+    }
+    fn round(self) -> f64 {
+        libm::round(self)
+    }
+    fn trunc(self) -> f64 {
+        libm::trunc(self)
+    }
+    fn fract(self) -> f64 {
+        self - libm::trunc(self)
+    }
+}
@@ -1,37 +1,258 @@
+use alloc::boxed::Box;
+use alloc::string::{String, ToString};
+use alloc::vec;
+use alloc::vec::Vec;
+use core::hash::{Hash, Hasher};
+use core::mem;
+
 use crate::error::CalcError;
 use crate::lexer::Token;
 use crate::{builtins, builtins::Operator};
 
-#[derive(Debug, PartialEq, Clone)]
+// With the `serde` feature, variants serialize as `{"type": "<Variant>", "value": ...}`.
+// `Clone`, `Debug`, `PartialEq` and `Hash` are written by hand to avoid
+// recursing once per level, like `Drop` below.
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(tag = "type", content = "value"))]
 pub enum Expression {
     Number(f64),
     Identifier(String),
+    /// A builtin constant such as `pi`, resolved when parsing. `name` keeps
+    /// the spelling from the input. When evaluating, a context variable or a
+    /// constant registered on the `Calculator` under that name takes
+    /// precedence over `value`.
+    Constant {
+        name: String,
+        value: f64,
+    },
     UnaryOp {
+        #[cfg_attr(
+            feature = "serde",
+            serde(deserialize_with = "builtins::deserialize_operator")
+        )]
         op: Operator,
         expr: Box<Expression>,
     },
     BinaryOp {
+        #[cfg_attr(
+            feature = "serde",
+            serde(deserialize_with = "builtins::deserialize_operator")
+        )]
         op: Operator,
         left: Box<Expression>,
         right: Box<Expression>,
     },
     FunctionCall { name: String, args: Vec<Expression> },
     Parenthesis(Box<Expression>),
+    /// `let name = value in body`: `body` evaluated with `name` bound to
+    /// `value`.
+    LetBinding {
+        name: String,
+        value: Box<Expression>,
+        body: Box<Expression>,
+    },
+}
+
+// The derived drop glue recurses once per level, which overflows the stack for
+// very deep trees (e.g. `1+1+...+1`). Detach children onto a heap stack instead.
+impl Drop for Expression {
+    fn drop(&mut self) {
+        let mut pending = Vec::new();
+        detach_children(self, &mut pending);
+        while let Some(mut expr) = pending.pop() {
+            detach_children(&mut expr, &mut pending);
+        }
+    }
+}
+
+impl Clone for Expression {
+    fn clone(&self) -> Self {
+        self.fold(&mut |expr, children| with_children(expr, children))
+    }
+}
+
+// A copy of `expr` whose children are `children`, in `children()` order.
+fn with_children(expr: &Expression, children: Vec<Expression>) -> Expression {
+    let mut children = children.into_iter();
+    let mut next = || Box::new(children.next().expect("one child per slot"));
+    match expr {
+        Expression::Number(n) => Expression::Number(*n),
+        Expression::Identifier(name) => Expression::Identifier(name.clone()),
+        Expression::Constant { name, value } => Expression::Constant {
+            name: name.clone(),
+            value: *value,
+        },
+        Expression::UnaryOp { op, .. } => Expression::UnaryOp { op, expr: next() },
+        Expression::BinaryOp { op, .. } => Expression::BinaryOp {
+            op,
+            left: next(),
+            right: next(),
+        },
+        Expression::Parenthesis(_) => Expression::Parenthesis(next()),
+        Expression::LetBinding { name, .. } => Expression::LetBinding {
+            name: name.clone(),
+            value: next(),
+            body: next(),
+        },
+        Expression::FunctionCall { name, .. } => Expression::FunctionCall {
+            name: name.clone(),
+            args: children.collect(),
+        },
+    }
+}
+
+// Equality is structural, not mathematical: `1+2` and `2+1` differ. Numbers
+// compare by value except that NaN equals NaN, which makes `Eq` lawful and lets
+// expressions key a `HashMap` or `HashSet` for subexpression caching.
+impl PartialEq for Expression {
+    fn eq(&self, other: &Self) -> bool {
+        // Pairs of subtrees still to compare, on a heap stack like `drop`.
+        let mut pending = vec![(self, other)];
+        while let Some((a, b)) = pending.pop() {
+            if !same_node(a, b) {
+                return false;
+            }
+            pending.extend(a.children().into_iter().zip(b.children()));
+        }
+        true
+    }
+}
+
+impl Eq for Expression {}
+
+impl Hash for Expression {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        // Nodes in preorder. Each node's variant fixes how many children
+        // follow it (a call hashes its argument count), so the sequence
+        // identifies the tree.
+        self.visit(|expr| {
+            mem::discriminant(expr).hash(state);
+            match expr {
+                Expression::Number(n) => number_key(*n).hash(state),
+                Expression::Identifier(name) => name.hash(state),
+                Expression::Constant { name, value } => {
+                    name.hash(state);
+                    number_key(*value).hash(state);
+                }
+                Expression::UnaryOp { op, .. } | Expression::BinaryOp { op, .. } => op.hash(state),
+                Expression::FunctionCall { name, args } => {
+                    name.hash(state);
+                    args.len().hash(state);
+                }
+                Expression::Parenthesis(_) => {}
+                Expression::LetBinding { name, .. } => name.hash(state),
+            }
+        });
+    }
+}
+
+// Whether two nodes agree in everything but their children, including how
+// many they have.
+fn same_node(a: &Expression, b: &Expression) -> bool {
+    match (a, b) {
+        (Expression::Number(a), Expression::Number(b)) => number_key(*a) == number_key(*b),
+        (Expression::Identifier(a), Expression::Identifier(b)) => a == b,
+        (
+            Expression::Constant { name: a, value: x },
+            Expression::Constant { name: b, value: y },
+        ) => a == b && number_key(*x) == number_key(*y),
+        (Expression::UnaryOp { op: a, .. }, Expression::UnaryOp { op: b, .. })
+        | (Expression::BinaryOp { op: a, .. }, Expression::BinaryOp { op: b, .. }) => a == b,
+        (
+            Expression::FunctionCall { name: a, args: x },
+            Expression::FunctionCall { name: b, args: y },
+        ) => a == b && x.len() == y.len(),
+        (Expression::Parenthesis(_), Expression::Parenthesis(_)) => true,
+        (Expression::LetBinding { name: a, .. }, Expression::LetBinding { name: b, .. }) => a == b,
+        _ => false,
+    }
+}
+
+// Bit pattern used for number equality and hashing: `0.0` and `-0.0` share
+// one key, as do all NaNs.
+fn number_key(n: f64) -> u64 {
+    if n.is_nan() {
+        f64::NAN.to_bits()
+    } else if n == 0.0 {
+        0
+    } else {
+        n.to_bits()
+    }
+}
+
+fn detach_children(expr: &mut Expression, pending: &mut Vec<Expression>) {
+    let mut detach = |child: &mut Box<Expression>| {
+        if !matches!(
+            **child,
+            Expression::Number(_) | Expression::Identifier(_) | Expression::Constant { .. }
+        ) {
+            pending.push(mem::replace(&mut **child, Expression::Number(0.0)));
+        }
+    };
+    match expr {
+        Expression::Number(_) | Expression::Identifier(_) | Expression::Constant { .. } => {}
+        Expression::UnaryOp { expr, .. } => detach(expr),
+        Expression::BinaryOp { left, right, .. } => {
+            detach(left);
+            detach(right);
+        }
+        Expression::FunctionCall { args, .. } => pending.append(args),
+        Expression::Parenthesis(inner) => detach(inner),
+        Expression::LetBinding { value, body, .. } => {
+            detach(value);
+            detach(body);
+        }
+    }
+}
+
+#[derive(Debug, PartialEq, Eq, Hash, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(tag = "type", content = "value"))]
+pub enum Statement {
+    Assignment { name: String, expr: Expression },
+    Expression(Expression),
 }
 
+pub(crate) const DEFAULT_MAX_DEPTH: usize = 256;
+
+pub(crate) const IMPLICIT_MUL: Operator = "*";
+
+// `|x|` parses as a call to this function.
+pub(crate) const ABS_FUNCTION: &str = "abs";
+
+// A `|` after an operand is bitwise or, unless it closes an absolute value.
+pub(crate) const BIT_OR: Operator = "|";
+
+// `let x = 2 in x^2`. Both words are only keywords in that position, so they
+// stay usable as names elsewhere.
+const LET_KEYWORD: &str = "let";
+const IN_KEYWORD: &str = "in";
+
 struct Parser<'a> {
     tokens: &'a [Token],
+    positions: &'a [usize], // source offset per token; falls back to the token index
     pos: usize,
+    depth: usize,
+    max_depth: usize,
+    in_abs: bool,       // directly inside `|...|`, where the next bar closes it
+    in_let_value: bool, // directly inside the value of a `let`, which `in` ends
 }
 
 impl<'a> Parser<'a> {
+    fn source_pos(&self) -> usize {
+        match self.positions.get(self.pos) {
+            Some(&offset) => offset,
+            None => self.positions.last().copied().unwrap_or(self.pos),
+        }
+    }
+
     fn peek(&self) -> &Token {
-        self.tokens.get(self.pos).unwrap_or(&Token::EOF)
+        self.tokens.get(self.pos).unwrap_or(&Token::Eof)
     }
 
     fn bump(&mut self) -> Token {
         if self.pos >= self.tokens.len() {
-            return Token::EOF;
+            return Token::Eof;
         }
         let token = self.tokens[self.pos].clone();
         self.pos += 1;
@@ -39,25 +260,160 @@ impl<'a> Parser<'a> {
     }
 
     fn expect(&mut self, expected: Token) -> Result<(), CalcError> {
+        let pos = self.source_pos();
         let got = self.bump();
         if got != expected {
-            return Err(CalcError::ExpectedToken { expected, got });
+            return Err(CalcError::ExpectedToken { expected, got, pos });
         }
         Ok(())
     }
 
+    // The `)` matching the `(` at source offset `open`. Running out of input
+    // first is reported against the `(`.
+    fn expect_close(&mut self, open: usize) -> Result<(), CalcError> {
+        if matches!(self.peek(), Token::Eof) {
+            return Err(CalcError::UnclosedParen { pos: open });
+        }
+        self.expect(Token::CloseParen)
+    }
+
+    fn expect_end(&self) -> Result<(), CalcError> {
+        match self.peek() {
+            Token::Eof => Ok(()),
+            Token::CloseParen => Err(CalcError::UnexpectedCloseParen {
+                pos: self.source_pos(),
+            }),
+            other => Err(CalcError::UnexpectedTokenAfterExpression {
+                got: other.clone(),
+                pos: self.source_pos(),
+            }),
+        }
+    }
+
+    // Statements separated by `;`. Empty statements (`;;`, a trailing `;`) are
+    // skipped, but at least one statement is required.
+    fn parse_program(&mut self) -> Result<Vec<Statement>, CalcError> {
+        let mut statements = Vec::new();
+        loop {
+            while matches!(self.peek(), Token::Semicolon) {
+                self.bump();
+            }
+            if matches!(self.peek(), Token::Eof) {
+                break;
+            }
+            statements.push(self.parse_statement()?);
+            if !matches!(self.peek(), Token::Semicolon) {
+                break;
+            }
+        }
+        // Nothing but empty statements (`;;`) is as blank as no input at all.
+        if statements.is_empty() {
+            return Err(CalcError::EmptyInput);
+        }
+        Ok(statements)
+    }
+
+    fn parse_statement(&mut self) -> Result<Statement, CalcError> {
+        if let (Token::Ident(name), Some(Token::Equals)) =
+            (self.peek(), self.tokens.get(self.pos + 1))
+        {
+            let name = name.clone();
+            self.pos += 2;
+            let expr = self.parse_expression()?;
+            return Ok(Statement::Assignment { name, expr });
+        }
+        Ok(Statement::Expression(self.parse_expression()?))
+    }
+
+    // Error recovery for `parse_all_errors`: after a failure, skip past the next
+    // infix operator or comma and resume with a fresh operand.
+    fn collect_errors(&mut self) -> Vec<CalcError> {
+        let mut errors = Vec::new();
+        loop {
+            match self.parse_expression().and_then(|_| self.expect_end()) {
+                Ok(()) => break,
+                Err(err) => errors.push(err),
+            }
+            if !self.synchronize() {
+                break;
+            }
+        }
+        errors
+    }
+
+    fn synchronize(&mut self) -> bool {
+        loop {
+            match self.bump() {
+                Token::Eof => return false,
+                Token::Op(op) if builtins::infix_binding_power(op).is_some() => return true,
+                Token::Comma | Token::Semicolon => return true,
+                _ => {}
+            }
+        }
+    }
+
     fn parse_expression(&mut self) -> Result<Expression, CalcError> {
         self.parse_expr_bp(0)
     }
 
+    // A full expression inside brackets: parentheses and call arguments reset
+    // `in_abs` (so `|(a | b)|` is or), absolute value bars set it. Neither
+    // ends at `in`.
+    fn parse_enclosed(&mut self, in_abs: bool) -> Result<Expression, CalcError> {
+        let outer = mem::replace(&mut self.in_abs, in_abs);
+        let outer_let = mem::replace(&mut self.in_let_value, false);
+        let result = self.parse_expression();
+        self.in_abs = outer;
+        self.in_let_value = outer_let;
+        result
+    }
+
+    // Every nested sub-expression (parentheses, call arguments, prefix operands,
+    // right-hand sides) passes through here, so this is where depth is bounded.
     fn parse_expr_bp(&mut self, min_bp: u8) -> Result<Expression, CalcError> {
+        self.nested(|parser| parser.parse_expr_bp_inner(min_bp))
+    }
+
+    // Runs `parse` one level deeper, failing past `max_depth`.
+    fn nested(
+        &mut self,
+        parse: impl FnOnce(&mut Self) -> Result<Expression, CalcError>,
+    ) -> Result<Expression, CalcError> {
+        if self.depth >= self.max_depth {
+            return Err(CalcError::NestingTooDeep {
+                max_depth: self.max_depth,
+                pos: self.source_pos(),
+            });
+        }
+        self.depth += 1;
+        let result = parse(self);
+        self.depth -= 1;
+        result
+    }
+
+    fn parse_expr_bp_inner(&mut self, min_bp: u8) -> Result<Expression, CalcError> {
         let mut left = self.parse_prefix()?;
 
         loop {
-            let Token::Op(op) = self.peek().clone() else {
-                break;
-            };
+            if let Some(op) = self.postfix_operator() {
+                if builtins::postfix_binding_power(op).unwrap_or(0) < min_bp {
+                    break;
+                }
+                self.bump();
+                left = Expression::UnaryOp {
+                    op,
+                    expr: Box::new(left),
+                };
+                continue;
+            }
 
+            let (op, implicit) = match self.peek() {
+                Token::Ident(word) if self.in_let_value && is_keyword(word, IN_KEYWORD) => break,
+                Token::Op(op) => (*op, false),
+                Token::Bar if !self.in_abs => (BIT_OR, false),
+                _ if self.at_implicit_operand() => (IMPLICIT_MUL, true),
+                _ => break,
+            };
             let Some((l_bp, r_bp)) = builtins::infix_binding_power(op) else {
                 break;
             };
@@ -65,7 +421,9 @@ impl<'a> Parser<'a> {
                 break;
             }
 
-            self.bump(); // consume operator
+            if !implicit {
+                self.bump(); // consume operator
+            }
             let right = self.parse_expr_bp(r_bp)?;
             left = Expression::BinaryOp {
                 op,
@@ -77,6 +435,14 @@ impl<'a> Parser<'a> {
         Ok(left)
     }
 
+    fn postfix_operator(&self) -> Option<Operator> {
+        postfix_operator_at(self.tokens, self.pos)
+    }
+
+    fn at_implicit_operand(&self) -> bool {
+        implicit_operand_at(self.tokens, self.pos)
+    }
+
     fn parse_prefix(&mut self) -> Result<Expression, CalcError> {
         match self.peek().clone() {
             Token::Op(op) => {
@@ -96,69 +462,323 @@ impl<'a> Parser<'a> {
 
     fn parse_primary(&mut self) -> Result<Expression, CalcError> {
         match self.peek() {
-            Token::Number(_) => self.parse_number(),
+            Token::Number(_) | Token::DecimalPoint if number_starts_at(self.tokens, self.pos) => {
+                self.parse_number()
+            }
             Token::Ident(_) => {
+                let pos = self.source_pos();
                 let token = self.bump();
                 let Token::Ident(name) = token else {
-                    return Err(CalcError::ExpectedPrimary(token));
+                    return Err(CalcError::ExpectedPrimary { got: token, pos });
                 };
 
+                if is_keyword(&name, LET_KEYWORD) && self.at_binding() {
+                    return self.parse_let();
+                }
                 if matches!(self.peek(), Token::OpenParen) {
+                    let open = self.source_pos();
                     self.bump();
                     let mut args = Vec::new();
                     if !matches!(self.peek(), Token::CloseParen) {
-                        args.push(self.parse_expression()?);
+                        args.push(self.parse_enclosed(false)?);
                         while matches!(self.peek(), Token::Comma) {
                             self.bump();
-                            args.push(self.parse_expression()?);
+                            args.push(self.parse_enclosed(false)?);
                         }
                     }
-                    self.expect(Token::CloseParen)?;
+                    self.expect_close(open)?;
                     Ok(Expression::FunctionCall {
                         name,
                         args,
                     })
+                } else if let Some(value) = builtins::eval_constant(&name) {
+                    Ok(Expression::Constant { name, value })
                 } else {
                     Ok(Expression::Identifier(name))
                 }
             }
             Token::OpenParen => {
+                let open = self.source_pos();
                 self.bump();
-                let inner = self.parse_expression()?;
-                self.expect(Token::CloseParen)?;
+                let inner = self.parse_enclosed(false)?;
+                self.expect_close(open)?;
                 Ok(Expression::Parenthesis(Box::new(inner)))
             }
-            other => Err(CalcError::ExpectedPrimary(other.clone())),
+            // A `|` where an operand is expected opens an absolute value; one
+            // after an operand closes it, or is bitwise or outside of bars. So
+            // `|a*|b||` is `abs(a*abs(b))`, but a bar cannot start a new value
+            // right after an operand: write `2*|x|`, not `2|x|`.
+            Token::Bar => {
+                self.bump();
+                let inner = self.parse_enclosed(true)?;
+                self.expect(Token::Bar)?;
+                Ok(Expression::FunctionCall {
+                    name: ABS_FUNCTION.to_string(),
+                    args: vec![inner],
+                })
+            }
+            other => Err(CalcError::ExpectedPrimary {
+                got: other.clone(),
+                pos: self.source_pos(),
+            }),
         }
     }
 
-    fn parse_number(&mut self) -> Result<Expression, CalcError> {
-        let token = self.bump();
-        let Token::Number(n) = token else {
-            return Err(CalcError::ExpectedNumber(token));
+    // `name =` follows, as after `let` or between comma-separated bindings.
+    fn at_binding(&self) -> bool {
+        matches!(
+            (self.peek(), self.tokens.get(self.pos + 1)),
+            (Token::Ident(_), Some(Token::Equals))
+        )
+    }
+
+    // Called after `let`. The body extends as far as possible, like the right
+    // side of `=`: `let x = 1 in x + 1` is `let x = 1 in (x + 1)`. Further
+    // bindings separated by commas nest: `let a = 1, b = a in b` is
+    // `let a = 1 in let b = a in b`, so each binding counts towards `max_depth`.
+    fn parse_let(&mut self) -> Result<Expression, CalcError> {
+        let Token::Ident(name) = self.bump() else {
+            unreachable!("at_binding checked for a name");
         };
+        self.bump(); // `=`
+        let outer = mem::replace(&mut self.in_let_value, true);
+        let value = self.parse_expression();
+        self.in_let_value = outer;
+        let value = value?;
 
-        if matches!(self.peek(), Token::DecimalPoint) {
+        let body = if matches!(self.peek(), Token::Comma)
+            && matches!(
+                (self.tokens.get(self.pos + 1), self.tokens.get(self.pos + 2)),
+                (Some(Token::Ident(_)), Some(Token::Equals))
+            ) {
             self.bump();
+            self.nested(Self::parse_let)?
+        } else {
+            let pos = self.source_pos();
             match self.bump() {
-                Token::Number(frac) => {
-                    let digits = frac.abs().to_string().len() as i32;
-                    let decimal_part = (frac as f64) / 10f64.powi(digits);
-                    Ok(Expression::Number(n as f64 + decimal_part))
+                Token::Ident(word) if is_keyword(&word, IN_KEYWORD) => {}
+                got => {
+                    return Err(CalcError::ExpectedToken {
+                        expected: Token::Ident(IN_KEYWORD.to_string()),
+                        got,
+                        pos,
+                    });
                 }
-                other => Err(CalcError::ExpectedFractionDigits(other)),
             }
-        } else {
-            Ok(Expression::Number(n as f64))
+            self.parse_expression()?
+        };
+        Ok(Expression::LetBinding {
+            name,
+            value: Box::new(value),
+            body: Box::new(body),
+        })
+    }
+
+    fn parse_number(&mut self) -> Result<Expression, CalcError> {
+        let pos = self.source_pos();
+        let start = self.pos;
+        // Rebuild the literal text so `str::parse` does the rounding. A literal
+        // may start at its decimal point: `.5` reads as `0.5`.
+        let mut literal = match self.peek() {
+            Token::Number(n) => {
+                let text = n.to_string();
+                self.bump();
+                text
+            }
+            _ if number_starts_at(self.tokens, self.pos) => String::from("0"),
+            _ => {
+                let got = self.bump();
+                return Err(CalcError::ExpectedNumber { got, pos });
+            }
+        };
+        // A trailing point with no digits after it (`5.`) is still a number.
+        if matches!(self.peek(), Token::DecimalPoint) {
+            self.bump();
+            if let Token::Fraction(digits) = self.peek() {
+                literal.push('.');
+                literal.push_str(digits);
+                self.bump();
+            }
+        }
+        if let Token::Exponent(exp) = self.peek() {
+            literal.push('e');
+            literal.push_str(exp);
+            self.bump();
         }
+
+        literal
+            .parse::<f64>()
+            .map(Expression::Number)
+            .map_err(|_| CalcError::ExpectedNumber {
+                got: self.tokens[start].clone(),
+                pos,
+            })
+    }
+}
+
+fn is_keyword(word: &str, keyword: &str) -> bool {
+    word.eq_ignore_ascii_case(keyword)
+}
+
+// An operator with a postfix form is infix only when a number, identifier,
+// `(` or purely prefix operator follows it, so `7 % 3` and `7 % √9` are modulo.
+// Anything else makes it postfix: `50%`, `(10%)`, `50% * 2`, and also
+// `50% - 5`, since `+` and `-` can continue an expression as infix operators;
+// modulo by a negative number needs `5 % (-3)`. A bar after `%` closes an
+// absolute value or is bitwise or, so `|50%|` works but modulo by one needs
+// `5 % (|x|)`. An operator with no infix form (`!`) is always postfix.
+pub(crate) fn postfix_operator_at(tokens: &[Token], pos: usize) -> Option<Operator> {
+    let Some(Token::Op(op)) = tokens.get(pos) else {
+        return None;
+    };
+    builtins::postfix_binding_power(op)?;
+    if builtins::infix_binding_power(op).is_none() {
+        return Some(*op);
+    }
+    let operand_follows = match tokens.get(pos + 1) {
+        _ if number_starts_at(tokens, pos + 1) => true,
+        Some(Token::Ident(_) | Token::OpenParen) => true,
+        Some(Token::Op(next)) => {
+            builtins::prefix_binding_power(next).is_some()
+                && builtins::infix_binding_power(next).is_none()
+        }
+        _ => false,
+    };
+    (!operand_follows).then_some(*op)
+}
+
+// Juxtaposition means multiplication: `2pi`, `3(4+5)`, `(1+2)(3+4)`. Two
+// numeric literals in a row (`2 3`) are still rejected as a likely typo.
+pub(crate) fn implicit_operand_at(tokens: &[Token], pos: usize) -> bool {
+    match tokens.get(pos) {
+        Some(Token::Ident(_) | Token::OpenParen) => true,
+        Some(Token::Number(_)) => !matches!(
+            pos.checked_sub(1).and_then(|prev| tokens.get(prev)),
+            Some(Token::Number(_) | Token::DecimalPoint | Token::Fraction(_) | Token::Exponent(_))
+        ),
+        _ => false,
+    }
+}
+
+// Whether a number literal starts at `tokens[pos]`: a whole number, or a
+// decimal point directly followed by fraction digits (`.5`).
+pub(crate) fn number_starts_at(tokens: &[Token], pos: usize) -> bool {
+    match tokens.get(pos) {
+        Some(Token::Number(_)) => true,
+        Some(Token::DecimalPoint) => matches!(tokens.get(pos + 1), Some(Token::Fraction(_))),
+        _ => false,
+    }
+}
+
+// Reads the number literal starting at `tokens[start]`, fraction and exponent
+// included, and returns its value with the index of the token after it.
+pub(crate) fn parse_number_at(
+    tokens: &[Token],
+    positions: &[usize],
+    start: usize,
+) -> Result<(f64, usize), CalcError> {
+    let mut parser = Parser {
+        tokens,
+        positions,
+        pos: start,
+        depth: 0,
+        max_depth: DEFAULT_MAX_DEPTH,
+        in_abs: false,
+        in_let_value: false,
+    };
+    match parser.parse_number()? {
+        Expression::Number(value) => Ok((value, parser.pos)),
+        _ => unreachable!("parse_number only yields numbers"),
     }
 }
 
+#[cfg(test)]
 pub(crate) fn parse_tokens(tokens: &[Token]) -> Result<Expression, CalcError> {
-    let mut parser = Parser { tokens, pos: 0 };
-    let expr = parser.parse_expression()?;
-    match parser.peek() {
-        Token::EOF => Ok(expr),
-        other => Err(CalcError::UnexpectedTokenAfterExpression(other.clone())),
+    parse_tokens_at(tokens, &[], DEFAULT_MAX_DEPTH)
+}
+
+// Blank input gets its own error rather than "expected expression, got end of
+// input".
+pub(crate) fn check_not_empty(tokens: &[Token]) -> Result<(), CalcError> {
+    if matches!(tokens, [] | [Token::Eof]) {
+        return Err(CalcError::EmptyInput);
     }
+    Ok(())
+}
+
+pub(crate) fn parse_tokens_at(
+    tokens: &[Token],
+    positions: &[usize],
+    max_depth: usize,
+) -> Result<Expression, CalcError> {
+    check_not_empty(tokens)?;
+    let mut parser = Parser {
+        tokens,
+        positions,
+        pos: 0,
+        depth: 0,
+        max_depth,
+        in_abs: false,
+        in_let_value: false,
+    };
+    let expr = parser.parse_expression()?;
+    parser.expect_end()?;
+    Ok(expr)
+}
+
+pub(crate) fn parse_statement_tokens_at(
+    tokens: &[Token],
+    positions: &[usize],
+    max_depth: usize,
+) -> Result<Statement, CalcError> {
+    check_not_empty(tokens)?;
+    let mut parser = Parser {
+        tokens,
+        positions,
+        pos: 0,
+        depth: 0,
+        max_depth,
+        in_abs: false,
+        in_let_value: false,
+    };
+    let stmt = parser.parse_statement()?;
+    parser.expect_end()?;
+    Ok(stmt)
+}
+
+pub(crate) fn parse_program_tokens_at(
+    tokens: &[Token],
+    positions: &[usize],
+    max_depth: usize,
+) -> Result<Vec<Statement>, CalcError> {
+    check_not_empty(tokens)?;
+    let mut parser = Parser {
+        tokens,
+        positions,
+        pos: 0,
+        depth: 0,
+        max_depth,
+        in_abs: false,
+        in_let_value: false,
+    };
+    let statements = parser.parse_program()?;
+    parser.expect_end()?;
+    Ok(statements)
+}
+
+pub(crate) fn collect_parse_errors(
+    tokens: &[Token],
+    positions: &[usize],
+    max_depth: usize,
+) -> Vec<CalcError> {
+    let mut parser = Parser {
+        tokens,
+        positions,
+        pos: 0,
+        depth: 0,
+        max_depth,
+        in_abs: false,
+        in_let_value: false,
+    };
+    parser.collect_errors()
 }
@@ -0,0 +1,58 @@
+use alloc::string::ToString;
+use core::cell::Cell;
+
+use crate::error::CalcError;
+
+pub(crate) const RANDOM_FUNCTION: &str = "random";
+
+// One SplitMix64 step. Small and statistically sound for simulations and
+// games, but predictable, so not for anything security-sensitive.
+fn next_u64(state: &Cell<u64>) -> u64 {
+    let next = state.get().wrapping_add(0x9E37_79B9_7F4A_7C15);
+    state.set(next);
+    let mut z = next;
+    z = (z ^ (z >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+    z = (z ^ (z >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+    z ^ (z >> 31)
+}
+
+// Uniform in `[0, 1)`: the top 53 bits fill an `f64` mantissa exactly.
+fn next_f64(state: &Cell<u64>) -> f64 {
+    (next_u64(state) >> 11) as f64 / (1u64 << 53) as f64
+}
+
+// `random()` is uniform in `[0, 1)`, `random(lo, hi)` in `[lo, hi)`.
+pub(crate) fn random_impl(state: &Cell<u64>, args: &[f64]) -> Result<f64, CalcError> {
+    match *args {
+        [] => Ok(next_f64(state)),
+        [lo, hi] => {
+            if lo.is_nan() || hi.is_nan() || lo > hi {
+                return Err(CalcError::DomainError {
+                    name: RANDOM_FUNCTION.to_string(),
+                    arg: lo,
+                });
+            }
+            Ok(lo + (hi - lo) * next_f64(state))
+        }
+        _ => Err(CalcError::WrongArity {
+            name: RANDOM_FUNCTION.to_string(),
+            expected: 2,
+            got: args.len(),
+        }),
+    }
+}
+
+// A fresh seed per calculator, taken from the standard library's per-process
+// hash keys.
+#[cfg(feature = "std")]
+pub(crate) fn entropy_seed() -> u64 {
+    use std::hash::BuildHasher;
+    std::collections::hash_map::RandomState::new().hash_one(0u64)
+}
+
+// Without `std` there is no entropy source, so every calculator starts from
+// the same seed until `Calculator::seed_random` is called.
+#[cfg(not(feature = "std"))]
+pub(crate) fn entropy_seed() -> u64 {
+    0
+}
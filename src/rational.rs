@@ -0,0 +1,261 @@
+use alloc::string::ToString;
+use core::fmt;
+
+use crate::builtins::{self, Operator};
+use crate::error::CalcError;
+use crate::eval::{ExactValue, evaluate_exact};
+use crate::integer;
+use crate::parser::Expression;
+
+/// An exact fraction, the result of `rustcalc::eval_rational`. Always in lowest
+/// terms with a positive denominator, so equal values compare equal.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Rational {
+    num: i64,
+    den: i64,
+}
+
+impl Rational {
+    /// `num/den` in lowest terms. Panics if `den` is zero or the reduced
+    /// fraction does not fit in `i64`.
+    pub fn new(num: i64, den: i64) -> Self {
+        Rational::reduced(i128::from(num), i128::from(den)).expect("valid fraction")
+    }
+
+    pub fn numer(self) -> i64 {
+        self.num
+    }
+
+    pub fn denom(self) -> i64 {
+        self.den
+    }
+
+    pub fn is_integer(self) -> bool {
+        self.den == 1
+    }
+
+    pub fn to_f64(self) -> f64 {
+        self.num as f64 / self.den as f64
+    }
+
+    // Arithmetic runs in `i128`, which cannot overflow for products of two
+    // `i64`s; only the reduced result has to fit back into `i64`.
+    fn reduced(num: i128, den: i128) -> Result<Rational, CalcError> {
+        if den == 0 {
+            return Err(CalcError::DivideByZero);
+        }
+        let divisor = gcd(num.unsigned_abs(), den.unsigned_abs()) as i128;
+        let (num, den) = if den < 0 {
+            (-num / divisor, -den / divisor)
+        } else {
+            (num / divisor, den / divisor)
+        };
+        match (i64::try_from(num), i64::try_from(den)) {
+            (Ok(num), Ok(den)) => Ok(Rational { num, den }),
+            _ => Err(CalcError::IntegerOverflow),
+        }
+    }
+
+    fn parts(self) -> (i128, i128) {
+        (i128::from(self.num), i128::from(self.den))
+    }
+
+    fn checked_add(self, rhs: Rational) -> Result<Rational, CalcError> {
+        let ((a, b), (c, d)) = (self.parts(), rhs.parts());
+        Rational::reduced(a * d + c * b, b * d)
+    }
+
+    fn checked_sub(self, rhs: Rational) -> Result<Rational, CalcError> {
+        let ((a, b), (c, d)) = (self.parts(), rhs.parts());
+        Rational::reduced(a * d - c * b, b * d)
+    }
+
+    fn checked_neg(self) -> Result<Rational, CalcError> {
+        Rational::reduced(-i128::from(self.num), i128::from(self.den))
+    }
+
+    fn checked_mul(self, rhs: Rational) -> Result<Rational, CalcError> {
+        let ((a, b), (c, d)) = (self.parts(), rhs.parts());
+        Rational::reduced(a * c, b * d)
+    }
+
+    fn checked_div(self, rhs: Rational) -> Result<Rational, CalcError> {
+        let ((a, b), (c, d)) = (self.parts(), rhs.parts());
+        Rational::reduced(a * d, b * c)
+    }
+
+    fn checked_pow(self, exp: Rational) -> Result<Rational, CalcError> {
+        if !exp.is_integer() {
+            return Err(CalcError::NotRational("^".to_string()));
+        }
+        let base = if exp.num < 0 {
+            Rational::from(1).checked_div(self)?
+        } else {
+            self
+        };
+        // Square-and-multiply, so large exponents of 0 and ±1 stay cheap and
+        // anything else overflows within 64 squarings.
+        let (mut result, mut square, mut k) = (Rational::from(1), base, exp.num.unsigned_abs());
+        while k > 0 {
+            if k & 1 == 1 {
+                result = result.checked_mul(square)?;
+            }
+            k >>= 1;
+            if k > 0 {
+                square = square.checked_mul(square)?;
+            }
+        }
+        Ok(result)
+    }
+
+    // Rounds toward negative infinity.
+    fn floor(self) -> i64 {
+        self.num.div_euclid(self.den)
+    }
+}
+
+impl From<i64> for Rational {
+    fn from(n: i64) -> Self {
+        Rational { num: n, den: 1 }
+    }
+}
+
+impl fmt::Display for Rational {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.den == 1 {
+            write!(f, "{}", self.num)
+        } else {
+            write!(f, "{}/{}", self.num, self.den)
+        }
+    }
+}
+
+fn gcd(mut a: u128, mut b: u128) -> u128 {
+    while b != 0 {
+        (a, b) = (b, a % b);
+    }
+    a.max(1)
+}
+
+// A literal reaches the evaluator as `f64`, so it is converted through its
+// shortest decimal form: `0.1` is read as `1/10`, not the nearest binary value.
+fn from_literal(value: f64) -> Result<Rational, CalcError> {
+    if !value.is_finite() {
+        return Err(CalcError::NonFinite);
+    }
+    let text = value.to_string();
+    let (int_part, frac_part) = text.split_once('.').unwrap_or((&text, ""));
+    let digits: i128 = alloc::format!("{int_part}{frac_part}")
+        .parse()
+        .map_err(|_| CalcError::IntegerOverflow)?;
+    let scale = u32::try_from(frac_part.len())
+        .ok()
+        .and_then(|len| 10i128.checked_pow(len))
+        .ok_or(CalcError::IntegerOverflow)?;
+    Rational::reduced(digits, scale)
+}
+
+// Exact evaluation over fractions. Every operation is exact or an error:
+// irrational constants and functions give `CalcError::NotRational`, and
+// results that do not fit in `i64` give `CalcError::IntegerOverflow`.
+pub(crate) fn evaluate_rational(expr: &Expression) -> Result<Rational, CalcError> {
+    evaluate_exact(expr)
+}
+
+impl ExactValue for Rational {
+    fn number(n: f64) -> Result<Self, CalcError> {
+        from_literal(n)
+    }
+
+    fn constant(name: &str, _value: f64) -> Result<Self, CalcError> {
+        Err(CalcError::NotRational(name.to_string()))
+    }
+
+    fn unary(op: Operator, value: Self) -> Result<Self, CalcError> {
+        match op {
+            "+" => Ok(value),
+            "-" => value.checked_neg(),
+            "%" => value.checked_div(Rational::from(100)),
+            "!" if value.is_integer() => integer::factorial(value.num).map(Rational::from),
+            "!" => Err(CalcError::DomainError {
+                name: op.to_string(),
+                arg: value.to_f64(),
+            }),
+            _ => Err(CalcError::NotRational(op.to_string())),
+        }
+    }
+
+    fn infix(op: Operator, a: Self, b: Self) -> Result<Self, CalcError> {
+        infix(op, a, b)
+    }
+
+    fn call(name: &str, args: &[Self]) -> Result<Self, CalcError> {
+        call(name, args)
+    }
+}
+
+fn infix(op: Operator, a: Rational, b: Rational) -> Result<Rational, CalcError> {
+    let truth = |holds: bool| Ok(Rational::from(i64::from(holds)));
+    match op {
+        "+" => a.checked_add(b),
+        "-" => a.checked_sub(b),
+        "*" => a.checked_mul(b),
+        "/" => a.checked_div(b),
+        "^" => a.checked_pow(b),
+        "//" => Ok(Rational::from(a.checked_div(b)?.floor())),
+        // Truncated remainder, like the float operator: `a - b*trunc(a/b)`.
+        "%" => {
+            let quotient = a.checked_div(b)?;
+            let truncated = Rational::from(quotient.num / quotient.den);
+            a.checked_sub(b.checked_mul(truncated)?)
+        }
+        "<" => truth(less(a, b)),
+        ">" => truth(less(b, a)),
+        "<=" => truth(!less(b, a)),
+        ">=" => truth(!less(a, b)),
+        "==" => truth(a == b),
+        "!=" => truth(a != b),
+        // Bitwise and shift operators need integers, as in `eval_i64`.
+        _ if a.is_integer() && b.is_integer() => {
+            integer::infix(op, a.num, b.num).map(Rational::from)
+        }
+        _ => Err(CalcError::NonIntegerOperand(op.to_string())),
+    }
+}
+
+// Functions that are exact on fractions; any other builtin is reported as not
+// rational rather than rounded.
+fn call(name: &str, args: &[Rational]) -> Result<Rational, CalcError> {
+    let normalized = builtins::normalize_name(name);
+    let (min, max) = match normalized.as_str() {
+        "abs" | "sign" | "floor" | "ceil" => (1, Some(1)),
+        "min" | "max" => (1, None),
+        _ if builtins::function_arity(&normalized).is_some() => {
+            return Err(CalcError::NotRational(name.to_string()));
+        }
+        _ => return Err(CalcError::UnknownFunction(name.to_string())),
+    };
+    builtins::check_arity(name, min, max, args.len())?;
+    let x = args[0];
+    match normalized.as_str() {
+        "abs" if x.num < 0 => x.checked_neg(),
+        "abs" => Ok(x),
+        "sign" => Ok(Rational::from(x.num.signum())),
+        "floor" => Ok(Rational::from(x.floor())),
+        "ceil" => Rational::from(x.checked_neg()?.floor()).checked_neg(),
+        "min" => Ok(args
+            .iter()
+            .copied()
+            .fold(x, |m, v| if less(v, m) { v } else { m })),
+        _ => Ok(args
+            .iter()
+            .copied()
+            .fold(x, |m, v| if less(m, v) { v } else { m })),
+    }
+}
+
+fn less(a: Rational, b: Rational) -> bool {
+    // Cross-multiplying in `i128` is exact; denominators are positive.
+    let ((p, q), (r, s)) = (a.parts(), b.parts());
+    p * s < r * q
+}
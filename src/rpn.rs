@@ -0,0 +1,389 @@
+use alloc::format;
+use alloc::string::{String, ToString};
+use alloc::vec;
+use alloc::vec::Vec;
+
+use crate::builtins::{self, Operator};
+use crate::error::CalcError;
+use crate::eval::{PERCENT, eval_relative_percent};
+use crate::lexer::{self, Token};
+use crate::parser::{self, ABS_FUNCTION, BIT_OR, Expression, IMPLICIT_MUL};
+
+// Words for unary operators whose symbol means the binary operator in RPN:
+// `-` always subtracts and `%` is always modulo.
+const UNARY_WORDS: &[(&str, Operator)] = &[("neg", "-"), ("percent", "%")];
+
+fn unary_word(name: &str) -> Option<Operator> {
+    let name = builtins::normalize_name(name);
+    UNARY_WORDS
+        .iter()
+        .find(|(word, _)| *word == name)
+        .map(|&(_, op)| op)
+}
+
+// Evaluates space-separated postfix input with a value stack. Numbers and
+// constants push; operators with an infix form pop two values, prefix-only
+// ones (`√`) and the `UNARY_WORDS` pop one. A function pops its fixed argument
+// count, or the count given in parentheses (`max(3)`) when it is variadic;
+// without one, its minimum.
+pub(crate) fn evaluate_rpn(input: &str) -> Result<f64, CalcError> {
+    let (tokens, positions) = lexer::tokenize_with_positions(input)?;
+    let mut stack: Vec<f64> = Vec::new();
+    let mut i = 0;
+    // As in infix, a percentage right before `+` or `-` is relative to the
+    // other operand: `200 10 percent +` is 220. Holds the percentage when the
+    // top of the stack was just produced by `percent`.
+    let mut percent_on_top = None;
+
+    loop {
+        let pos = positions.get(i).copied().unwrap_or(i);
+        let percent = percent_on_top.take();
+        match &tokens[i] {
+            Token::Eof => break,
+            _ if parser::number_starts_at(&tokens, i) => {
+                let (value, next) = parser::parse_number_at(&tokens, &positions, i)?;
+                stack.push(value);
+                i = next;
+                continue;
+            }
+            Token::Op(op) if builtins::infix_binding_power(op).is_some() => {
+                let args = pop(&mut stack, 2, op)?;
+                stack.push(match percent {
+                    Some(percent) if matches!(*op, "+" | "-") => {
+                        eval_relative_percent(op, args[0], percent)?
+                    }
+                    _ => builtins::eval_infix(op, args[0], args[1])?,
+                });
+            }
+            // Bars are only absolute values in infix; here `|` is bitwise or.
+            Token::Bar => {
+                let args = pop(&mut stack, 2, BIT_OR)?;
+                stack.push(builtins::eval_infix(BIT_OR, args[0], args[1])?);
+            }
+            Token::Op(op) => {
+                let args = pop(&mut stack, 1, op)?;
+                stack.push(builtins::eval_unary(op, args[0])?);
+            }
+            Token::Ident(name) if let Some(op) = unary_word(name) => {
+                let args = pop(&mut stack, 1, name)?;
+                stack.push(builtins::eval_unary(op, args[0])?);
+                if op == PERCENT {
+                    percent_on_top = Some(args[0]);
+                }
+            }
+            Token::Ident(name) => {
+                if let Some(value) = builtins::eval_constant(name) {
+                    stack.push(value);
+                } else {
+                    let (argc, next) = argument_count(name, &tokens, i)?;
+                    let args = pop(&mut stack, argc, name)?;
+                    stack.push(builtins::eval_function(name, &args)?);
+                    i = next;
+                    continue;
+                }
+            }
+            other => {
+                return Err(CalcError::ExpectedPrimary {
+                    got: other.clone(),
+                    pos,
+                });
+            }
+        }
+        i += 1;
+    }
+
+    match stack.len() {
+        0 => Err(CalcError::ExpectedPrimary {
+            got: Token::Eof,
+            pos: positions.last().copied().unwrap_or(0),
+        }),
+        1 => Ok(stack[0]),
+        n => Err(CalcError::LeftoverOperands(n - 1)),
+    }
+}
+
+// Arguments taken by the function named at `tokens[i]`, and the index after
+// its name and any explicit `(count)`.
+fn argument_count(name: &str, tokens: &[Token], i: usize) -> Result<(usize, usize), CalcError> {
+    if let [
+        Token::OpenParen,
+        Token::Number(count),
+        Token::CloseParen,
+        ..,
+    ] = &tokens[i + 1..]
+    {
+        return Ok((usize::try_from(*count).unwrap_or(usize::MAX), i + 4));
+    }
+    match builtins::function_arity(name) {
+        Some((min, _)) => Ok((min, i + 1)),
+        None => Err(CalcError::UnknownFunction(name.to_string())),
+    }
+}
+
+// Pops the top `n` values, oldest first.
+fn pop(stack: &mut Vec<f64>, n: usize, name: &str) -> Result<Vec<f64>, CalcError> {
+    if stack.len() < n {
+        return Err(CalcError::StackUnderflow(name.to_string()));
+    }
+    Ok(stack.split_off(stack.len() - n))
+}
+
+// An operator or bracket on the stack of `to_postfix`, waiting for its
+// operands. Operators carry the binding power of their right operand, and
+// parentheses the index of their `(`.
+enum Pending {
+    Prefix(Operator, u8),
+    Infix(Operator, u8),
+    Paren(usize),
+    Call {
+        name: String,
+        argc: usize,
+        open: usize,
+    },
+    Abs,
+}
+
+// Shunting-yard over the same binding powers the parser uses, so the output is
+// the parse tree in the postfix form of `Expression::to_rpn`, as tokens. It
+// needs no recursion, so nesting depth is unbounded. Errors are the parser's,
+// with token indexes as positions.
+pub(crate) fn to_postfix(tokens: &[Token]) -> Result<Vec<Token>, CalcError> {
+    parser::check_not_empty(tokens)?;
+    let mut output = Vec::new();
+    let mut stack: Vec<Pending> = Vec::new();
+    let mut i = 0;
+    let mut expect_operand = true;
+
+    loop {
+        let token = tokens.get(i).unwrap_or(&Token::Eof);
+        if expect_operand {
+            match token {
+                _ if parser::number_starts_at(tokens, i) => {
+                    let (_, next) = parser::parse_number_at(tokens, &[], i)?;
+                    output.extend_from_slice(&tokens[i..next]);
+                    i = next;
+                    expect_operand = false;
+                    continue;
+                }
+                Token::Ident(name) if tokens.get(i + 1) == Some(&Token::OpenParen) => {
+                    if tokens.get(i + 2) == Some(&Token::CloseParen) {
+                        push_call(&mut output, name, 0);
+                        i += 3;
+                        expect_operand = false;
+                    } else {
+                        let name = name.clone();
+                        stack.push(Pending::Call {
+                            name,
+                            argc: 1,
+                            open: i + 1,
+                        });
+                        i += 2;
+                    }
+                    continue;
+                }
+                Token::Ident(name) => {
+                    output.push(Token::Ident(name.clone()));
+                    expect_operand = false;
+                }
+                Token::OpenParen => stack.push(Pending::Paren(i)),
+                Token::Bar => stack.push(Pending::Abs),
+                Token::Op(op) if let Some(r_bp) = builtins::prefix_binding_power(op) => {
+                    stack.push(Pending::Prefix(op, r_bp));
+                }
+                other => {
+                    return Err(CalcError::ExpectedPrimary {
+                        got: other.clone(),
+                        pos: i,
+                    });
+                }
+            }
+            i += 1;
+            continue;
+        }
+
+        if let Some(op) = parser::postfix_operator_at(tokens, i) {
+            pop_operators(&mut stack, &mut output, builtins::postfix_binding_power(op));
+            output.push(unary_token(op));
+            i += 1;
+            continue;
+        }
+        // A bar after an operand closes the innermost absolute value, if the
+        // innermost bracket is one, and is bitwise or otherwise.
+        let in_abs = stack
+            .iter()
+            .rev()
+            .find(|pending| !matches!(pending, Pending::Prefix(..) | Pending::Infix(..)))
+            .is_some_and(|pending| matches!(pending, Pending::Abs));
+        let infix = match token {
+            Token::Op(op) => Some((*op, 1)),
+            Token::Bar if !in_abs => Some((BIT_OR, 1)),
+            _ if parser::implicit_operand_at(tokens, i) => Some((IMPLICIT_MUL, 0)),
+            _ => None,
+        };
+        if let Some((op, width)) = infix
+            && let Some((l_bp, r_bp)) = builtins::infix_binding_power(op)
+        {
+            pop_operators(&mut stack, &mut output, Some(l_bp));
+            stack.push(Pending::Infix(op, r_bp));
+            i += width;
+            expect_operand = true;
+            continue;
+        }
+
+        // Anything else ends the operand list of the innermost bracket.
+        pop_operators(&mut stack, &mut output, None);
+        match (stack.pop(), token) {
+            (None, Token::Eof) => break,
+            (Some(Pending::Paren(_)), Token::CloseParen) => {}
+            (Some(Pending::Call { name, argc, .. }), Token::CloseParen) => {
+                push_call(&mut output, &name, argc);
+            }
+            (Some(Pending::Call { name, argc, open }), Token::Comma) => {
+                stack.push(Pending::Call {
+                    name,
+                    argc: argc + 1,
+                    open,
+                });
+                expect_operand = true;
+            }
+            (Some(Pending::Abs), Token::Bar) => {
+                output.push(Token::Ident(ABS_FUNCTION.to_string()));
+            }
+            (Some(Pending::Abs), got) => {
+                return Err(CalcError::ExpectedToken {
+                    expected: Token::Bar,
+                    got: got.clone(),
+                    pos: i,
+                });
+            }
+            (Some(Pending::Paren(open) | Pending::Call { open, .. }), Token::Eof) => {
+                return Err(CalcError::UnclosedParen { pos: open });
+            }
+            (None, Token::CloseParen) => {
+                return Err(CalcError::UnexpectedCloseParen { pos: i });
+            }
+            (Some(_), got) => {
+                return Err(CalcError::ExpectedToken {
+                    expected: Token::CloseParen,
+                    got: got.clone(),
+                    pos: i,
+                });
+            }
+            (None, got) => {
+                return Err(CalcError::UnexpectedTokenAfterExpression {
+                    got: got.clone(),
+                    pos: i,
+                });
+            }
+        }
+        i += 1;
+    }
+
+    output.push(Token::Eof);
+    Ok(output)
+}
+
+// Moves operators from the stack to the output while they bind tighter than
+// `bp`, the left binding power of the next operator; `None` pops every
+// operator down to the innermost bracket.
+fn pop_operators(stack: &mut Vec<Pending>, output: &mut Vec<Token>, bp: Option<u8>) {
+    while let Some(&(Pending::Prefix(op, r_bp) | Pending::Infix(op, r_bp))) = stack.last() {
+        if bp.is_some_and(|bp| bp >= r_bp) {
+            break;
+        }
+        match stack.pop() {
+            Some(Pending::Prefix("+", _)) => {}
+            Some(Pending::Prefix(..)) => output.push(unary_token(op)),
+            _ if op == BIT_OR => output.push(Token::Bar),
+            _ => output.push(Token::Op(op)),
+        }
+    }
+}
+
+// Unary minus and postfix percent become their `UNARY_WORDS`.
+fn unary_token(op: Operator) -> Token {
+    match unary_word_for(op) {
+        word if word == op => Token::Op(op),
+        word => Token::Ident(word.to_string()),
+    }
+}
+
+// As in `to_rpn`: a variadic call, or one with an unexpected argument count,
+// is followed by the count in parentheses.
+fn push_call(output: &mut Vec<Token>, name: &str, argc: usize) {
+    output.push(Token::Ident(name.to_string()));
+    match builtins::function_arity(name) {
+        Some((min, Some(max))) if min == argc && max == argc => {}
+        _ => output.extend([
+            Token::OpenParen,
+            Token::Number(argc as u64),
+            Token::CloseParen,
+        ]),
+    }
+}
+
+impl Expression {
+    /// Flattens the tree into space-separated postfix tokens that
+    /// `rustcalc::eval_rpn` reads back: `1+2*3` becomes `1 2 3 * +`.
+    ///
+    /// Unary minus is written `neg` (so `-2` is `2 neg`) and postfix percent
+    /// `percent`; unary plus and parentheses are dropped. A function call is its
+    /// name when that builtin takes exactly that many arguments, and
+    /// `name(count)` otherwise: `max(1, 2)` becomes `1 2 max(2)`. RPN has no
+    /// variables, so an expression containing a `let` fails with
+    /// `CalcError::NoRpnForm`; writing each value in place of its name could
+    /// grow exponentially with nested bindings.
+    pub fn to_rpn(&self) -> Result<String, CalcError> {
+        let mut words: Vec<String> = Vec::new();
+        // Postorder with an explicit stack; `true` marks a node whose children
+        // have already been written.
+        let mut pending = vec![(self, false)];
+        while let Some((expr, children_done)) = pending.pop() {
+            if let Expression::LetBinding { .. } = expr {
+                return Err(CalcError::NoRpnForm("let".to_string()));
+            }
+            if !children_done {
+                pending.push((expr, true));
+                pending.extend(
+                    expr.children()
+                        .into_iter()
+                        .rev()
+                        .map(|child| (child, false)),
+                );
+                continue;
+            }
+            match expr {
+                Expression::Number(n) if n.is_sign_negative() && !n.is_nan() => {
+                    words.push(format!("{}", -n));
+                    words.push(unary_word_for("-").to_string());
+                }
+                Expression::Number(n) => words.push(format!("{n}")),
+                Expression::Identifier(name) | Expression::Constant { name, .. } => {
+                    words.push(name.clone())
+                }
+                Expression::UnaryOp { op: "+", .. }
+                | Expression::Parenthesis(_)
+                | Expression::LetBinding { .. } => {}
+                Expression::UnaryOp { op, .. } => words.push(unary_word_for(op).to_string()),
+                Expression::BinaryOp { op, .. } => words.push(op.to_string()),
+                Expression::FunctionCall { name, args } => {
+                    let argc = args.len();
+                    match builtins::function_arity(name) {
+                        Some((min, Some(max))) if min == argc && max == argc => {
+                            words.push(name.clone())
+                        }
+                        _ => words.push(format!("{name}({argc})")),
+                    }
+                }
+            }
+        }
+        Ok(words.join(" "))
+    }
+}
+
+fn unary_word_for(op: Operator) -> Operator {
+    UNARY_WORDS
+        .iter()
+        .find(|&&(_, symbol)| symbol == op)
+        .map_or(op, |&(word, _)| word)
+}
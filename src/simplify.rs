@@ -0,0 +1,116 @@
+use core::mem;
+
+use crate::builtins;
+use crate::eval::{eval_relative_percent, relative_percent};
+use crate::parser::Expression;
+
+impl Expression {
+    /// Returns an equivalent expression with constant subexpressions folded
+    /// (`2+3` becomes `5`), identities such as `x*1`, `x+0`, `x*0` and `x^1`
+    /// collapsed, and parentheses around numbers and identifiers removed.
+    ///
+    /// Anything whose evaluation fails, like `1/0`, is kept as written so the
+    /// error still surfaces when it is evaluated. Identifiers, constants and
+    /// function calls are never folded because their meaning depends on the context and
+    /// calculator used; identities assume variables hold finite values. A
+    /// postfix percentage is only folded together with the `+` or `-` it is
+    /// relative to (`200 + 10%` becomes `220`), since its value depends on it.
+    pub fn simplify(&self) -> Expression {
+        self.clone().map(&mut simplify_node)
+    }
+}
+
+// Which side of a binary operation an identity reduces to.
+enum Identity {
+    Left,
+    Right,
+    Zero,
+}
+
+fn simplify_node(mut expr: Expression) -> Expression {
+    if let Some(value) = fold_constant(&expr) {
+        return Expression::Number(value);
+    }
+    let identity = match &expr {
+        Expression::BinaryOp { op, right, .. } if relative_percent(op, right).is_some() => None,
+        Expression::BinaryOp { op, left, right } => identity(op, left, right),
+        _ => None,
+    };
+    match (&mut expr, identity) {
+        (Expression::Parenthesis(inner), _) if is_atom(inner) => take(inner),
+        (Expression::BinaryOp { left, .. }, Some(Identity::Left)) => take(left),
+        (Expression::BinaryOp { right, .. }, Some(Identity::Right)) => take(right),
+        (_, Some(Identity::Zero)) => Expression::Number(0.0),
+        _ => expr,
+    }
+}
+
+fn fold_constant(expr: &Expression) -> Option<f64> {
+    match expr {
+        Expression::UnaryOp { op, .. } if builtins::postfix_binding_power(op).is_some() => None,
+        Expression::UnaryOp { op, expr } => match **expr {
+            Expression::Number(value) => builtins::eval_unary(op, value).ok(),
+            _ => None,
+        },
+        Expression::BinaryOp { op, left, right }
+            if let Some(percent) = relative_percent(op, right) =>
+        {
+            match (&**left, percent) {
+                (Expression::Number(base), Expression::Number(percent)) => {
+                    eval_relative_percent(op, *base, *percent).ok()
+                }
+                _ => None,
+            }
+        }
+        Expression::BinaryOp { op, left, right } => match (&**left, &**right) {
+            (Expression::Number(a), Expression::Number(b)) => builtins::eval_infix(op, *a, *b).ok(),
+            _ => None,
+        },
+        _ => None,
+    }
+}
+
+fn identity(op: &str, left: &Expression, right: &Expression) -> Option<Identity> {
+    let is = |expr: &Expression, n: f64| matches!(expr, Expression::Number(value) if *value == n);
+    match op {
+        "*" if is(right, 1.0) => Some(Identity::Left),
+        "*" if is(left, 1.0) => Some(Identity::Right),
+        "*" if is(right, 0.0) && !may_fail(left) => Some(Identity::Zero),
+        "*" if is(left, 0.0) && !may_fail(right) => Some(Identity::Zero),
+        "+" if is(right, 0.0) => Some(Identity::Left),
+        "+" if is(left, 0.0) => Some(Identity::Right),
+        "-" if is(right, 0.0) => Some(Identity::Left),
+        "^" if is(right, 1.0) => Some(Identity::Left),
+        _ => None,
+    }
+}
+
+// `x*0` may only drop `x` if evaluating it cannot raise an error. Bitwise
+// and shift operators fail on fractional operands, and a `let` fails when it
+// names a constant.
+fn may_fail(expr: &Expression) -> bool {
+    let mut fails = false;
+    expr.visit(|node| {
+        fails |= matches!(
+            node,
+            Expression::FunctionCall { .. }
+                | Expression::LetBinding { .. }
+                | Expression::BinaryOp {
+                    op: "/" | "//" | "%" | "&" | "~^" | "|" | "<<" | ">>",
+                    ..
+                }
+        );
+    });
+    fails
+}
+
+fn is_atom(expr: &Expression) -> bool {
+    matches!(
+        expr,
+        Expression::Number(_) | Expression::Identifier(_) | Expression::Constant { .. }
+    )
+}
+
+fn take(expr: &mut Expression) -> Expression {
+    mem::replace(expr, Expression::Number(0.0))
+}
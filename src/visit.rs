@@ -0,0 +1,116 @@
+use alloc::vec;
+use alloc::vec::Vec;
+use core::mem;
+
+use crate::parser::Expression;
+
+impl Expression {
+    /// Direct subexpressions, left to right.
+    pub fn children(&self) -> Vec<&Expression> {
+        match self {
+            Expression::Number(_) | Expression::Identifier(_) | Expression::Constant { .. } => {
+                Vec::new()
+            }
+            Expression::UnaryOp { expr, .. } => vec![expr],
+            Expression::BinaryOp { left, right, .. } => vec![left, right],
+            Expression::FunctionCall { args, .. } => args.iter().collect(),
+            Expression::Parenthesis(inner) => vec![inner],
+            Expression::LetBinding { value, body, .. } => vec![value, body],
+        }
+    }
+
+    // Like `children`, for rewriting them in place.
+    pub(crate) fn children_mut(&mut self) -> Vec<&mut Expression> {
+        match self {
+            Expression::Number(_) | Expression::Identifier(_) | Expression::Constant { .. } => {
+                Vec::new()
+            }
+            Expression::UnaryOp { expr, .. } => vec![expr],
+            Expression::BinaryOp { left, right, .. } => vec![left, right],
+            Expression::FunctionCall { args, .. } => args.iter_mut().collect(),
+            Expression::Parenthesis(inner) => vec![inner],
+            Expression::LetBinding { value, body, .. } => vec![value, body],
+        }
+    }
+
+    /// Calls `f` on this expression and every subexpression, parents before
+    /// children, left to right.
+    pub fn visit(&self, mut f: impl FnMut(&Expression)) {
+        let mut pending = vec![self];
+        while let Some(expr) = pending.pop() {
+            f(expr);
+            pending.extend(expr.children().into_iter().rev());
+        }
+    }
+
+    /// Number of nodes in the tree, including this one.
+    pub fn node_count(&self) -> usize {
+        let mut count = 0;
+        self.visit(|_| count += 1);
+        count
+    }
+
+    /// Length of the longest path from this node to a leaf, counting both ends;
+    /// a lone number has depth 1.
+    pub fn depth(&self) -> usize {
+        let mut max_depth = 0;
+        let mut pending = vec![(self, 1)];
+        while let Some((expr, depth)) = pending.pop() {
+            max_depth = max_depth.max(depth);
+            pending.extend(expr.children().into_iter().map(|child| (child, depth + 1)));
+        }
+        max_depth
+    }
+
+    /// Reduces the tree bottom-up: `f` receives each node together with the
+    /// results already computed for its children.
+    pub fn fold<T>(&self, f: &mut impl FnMut(&Expression, Vec<T>) -> T) -> T {
+        // Postorder with an explicit stack; `true` marks a node whose
+        // children's results are already on top of `results`.
+        let mut results = Vec::new();
+        let mut pending = vec![(self, false)];
+        while let Some((expr, children_done)) = pending.pop() {
+            if children_done {
+                let children = results.split_off(results.len() - expr.children().len());
+                results.push(f(expr, children));
+            } else {
+                pending.push((expr, true));
+                pending.extend(
+                    expr.children()
+                        .into_iter()
+                        .rev()
+                        .map(|child| (child, false)),
+                );
+            }
+        }
+        results.pop().expect("the root was folded")
+    }
+
+    /// Rebuilds the tree bottom-up, replacing every node with `f(node)` once its
+    /// children have been rewritten.
+    pub fn map(self, f: &mut impl FnMut(Expression) -> Expression) -> Expression {
+        // Like `fold`, except that a node waits on the stack with its children
+        // taken out, and gets the rewritten ones back before `f` sees it.
+        let mut results = Vec::new();
+        let mut pending = vec![(self, false)];
+        while let Some((mut expr, children_done)) = pending.pop() {
+            if children_done {
+                let mut slots = expr.children_mut();
+                let start = results.len() - slots.len();
+                for (slot, child) in slots.iter_mut().zip(results.drain(start..)) {
+                    **slot = child;
+                }
+                results.push(f(expr));
+            } else {
+                let children: Vec<Expression> = expr
+                    .children_mut()
+                    .into_iter()
+                    .map(|child| mem::replace(child, Expression::Number(0.0)))
+                    .collect();
+                pending.push((expr, true));
+                pending.extend(children.into_iter().rev().map(|child| (child, false)));
+            }
+        }
+        results.pop().expect("the root was rewritten")
+    }
+}